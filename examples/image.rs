@@ -1,9 +1,15 @@
-fn main(){
-    let input = vec![];
-    let module = parse(input);
-    let executor = Executor::new(module);
-    let result = executor.exec(param{
-        func_name: "add",
-        params: vec![1, 2],
-    })
+// wasm モジュールをパースしてテキスト形式で出力する
+use wasm_interpreter_rs::{parse_module, print_wat, wat::Style};
+
+fn main() {
+    // (module (func $add (export "add") (param i32 i32) (result i32) ...))
+    let input: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+        0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type section
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export section
+        0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // code section
+    ];
+    let module = parse_module(input).unwrap();
+    print!("{}", print_wat(&module, Style::Folded).unwrap());
 }
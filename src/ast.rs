@@ -1,12 +1,38 @@
+pub mod borrowed;
 pub mod instruction;
 pub mod module;
-pub mod parse;
+pub mod name;
+mod parse;
 pub mod section;
 pub mod wasm_type;
 
-// TODO
-// parser 関数は公開しない。構造体は公開する（メソッドやフィールドは非公開がよさそう）
-// このファイルにmoduleをパースする公開関数を作る
+pub use crate::decode::DecodeError;
+pub use parse::{ErrorContext, ParseError as Error};
+
+use std::io::Read;
+
+/// バイナリ形式の wasm モジュールをパースする
+///
+/// 関数本体は最初に使った時にデコードするので、関数本体のエラーはここでは返らず
+/// `section::Code::expression` などで最初に使った時に返る
+/// まとめて確かめる場合は `section::CodeSection::decode_all` か `validator::validate` を使う
+pub fn parse_module(mut data: &[u8]) -> Result<module::Module, Error> {
+    module::Module::parse(&mut data)
+}
+
+/// 入力をコピーせず借用したままパースする
+///
+/// 関数本体は `borrowed::Code::instructions` で必要になった時にデコードする
+pub fn parse_module_borrowed(data: &[u8]) -> Result<borrowed::Module<'_>, Error> {
+    borrowed::Module::parse(data)
+}
+
+/// `reader` から読み込んだバイナリ形式の wasm モジュールをパースする
+///
+/// 入力全体をバッファせず、section ごとに読み込む
+pub fn from_reader<R: Read>(mut reader: R) -> Result<module::Module, Error> {
+    module::Module::parse(&mut reader)
+}
 
 #[cfg(test)]
 mod test {
@@ -15,7 +41,7 @@ mod test {
     use crate::ast::{
         instruction::*,
         section::SectionData,
-        wasm_type::{NumberType, ValueType},
+        wasm_type::{Mutability, NumberType, ReferenceType, ValueType, VectorType},
     };
     use std::io::{Cursor, Seek, SeekFrom};
     #[test]
@@ -70,7 +96,7 @@ mod test {
             assert_eq!(result.version, 1);
             assert!(!result.sections.is_empty());
 
-            let elm = &result.sections.get(0).unwrap().payload_data;
+            let elm = &result.sections.first().unwrap().payload_data;
             assert!(matches!(elm, SectionData::Type(_)));
 
             if let SectionData::Type(ty) = elm {
@@ -81,7 +107,7 @@ mod test {
 
                     let rs = &f.return_types;
                     assert_eq!(rs.valu_types.len(), 1);
-                    let r = rs.valu_types.get(0).unwrap();
+                    let r = rs.valu_types.first().unwrap();
                     assert!(matches!(r, ValueType::Number(NumberType::I32)));
                 }
             }
@@ -91,7 +117,7 @@ mod test {
 
             if let SectionData::Function(fs) = elm {
                 assert_eq!(fs.indexies.len(), 1);
-                assert_eq!(*fs.indexies.get(0).unwrap(), 0x00);
+                assert_eq!(*fs.indexies.first().unwrap(), 0x00);
             }
 
             let elm = &result.sections.get(2).unwrap().payload_data;
@@ -99,12 +125,12 @@ mod test {
 
             if let SectionData::Code(cs) = elm {
                 assert_eq!(cs.codes.len(), 1);
-                let c = cs.codes.get(0).unwrap();
-                assert_eq!(c.locals.len(), 0);
+                let c = cs.codes.first().unwrap();
+                assert_eq!(c.locals().unwrap().len(), 0);
 
-                let exp = &c.expression;
+                let exp = c.expression().unwrap();
                 assert_eq!(exp.instrs.len(), 1);
-                let instr = exp.instrs.get(0).unwrap();
+                let instr = exp.instrs.first().unwrap();
                 assert!(matches!(
                     instr,
                     Instruction::Numeric(NumericInstruction::Const(
@@ -156,7 +182,7 @@ mod test {
             assert_eq!(result.version, 1);
             assert!(!result.sections.is_empty());
 
-            let elm = &result.sections.get(0).unwrap().payload_data;
+            let elm = &result.sections.first().unwrap().payload_data;
             assert!(matches!(elm, SectionData::Type(_)));
 
             if let SectionData::Type(ty) = elm {
@@ -164,14 +190,14 @@ mod test {
                 for f in &ty.funcs {
                     let ps = &f.params_types;
                     assert_eq!(ps.valu_types.len(), 2);
-                    let r0 = ps.valu_types.get(0).unwrap();
+                    let r0 = ps.valu_types.first().unwrap();
                     assert!(matches!(r0, ValueType::Number(n) if matches!(n, NumberType::I32)));
                     let r1 = ps.valu_types.get(1).unwrap();
                     assert!(matches!(r1, ValueType::Number(n)if matches!(n, NumberType::I32)));
 
                     let rs = &f.return_types;
                     assert_eq!(rs.valu_types.len(), 1);
-                    let r = rs.valu_types.get(0).unwrap();
+                    let r = rs.valu_types.first().unwrap();
                     assert!(matches!(r, ValueType::Number(n) if matches!(n, NumberType::I32)));
                 }
             }
@@ -181,7 +207,7 @@ mod test {
 
             if let SectionData::Function(fs) = elm {
                 assert_eq!(fs.indexies.len(), 1);
-                assert_eq!(*fs.indexies.get(0).unwrap(), 0x00);
+                assert_eq!(*fs.indexies.first().unwrap(), 0x00);
             }
             let elm = &result.sections.get(2).unwrap().payload_data;
             assert!(matches!(elm, SectionData::Export(_)));
@@ -199,14 +225,13 @@ mod test {
 
             if let SectionData::Code(cs) = elm {
                 assert_eq!(cs.codes.len(), 1);
-                let c = cs.codes.get(0).unwrap();
-                assert_eq!(c.locals.len(), 0);
+                let c = cs.codes.first().unwrap();
+                assert_eq!(c.locals().unwrap().len(), 0);
 
-                let exp = &c.expression;
+                let exp = c.expression().unwrap();
                 assert_eq!(exp.instrs.len(), 3);
-                let x = exp.instrs.get(0);
                 assert!(matches!(
-                    exp.instrs.get(0),
+                    exp.instrs.first(),
                     Some(Instruction::Variable(VariableInstruction::LocalGet(0x00)))
                 ));
                 assert!(matches!(
@@ -225,4 +250,1575 @@ mod test {
             assert!(matches!(elm, SectionData::Custom(_)));
         }
     }
+
+    #[test]
+    fn test_parse_const_float() {
+        let input: &[u8] = &[
+            0x43, 0x00, 0x00, 0x00, 0x80, // f32.const -0
+            0x43, 0x01, 0x00, 0xa0, 0x7f, // f32.const nan:0x200001
+            0x44, 0x18, 0x2d, 0x44, 0x54, 0xfb, 0x21, 0x09,
+            0x40, // f64.const 3.141592653589793
+            0x44, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8,
+            0xff, // f64.const -nan:0x8000000000001
+            0x0b, // end
+        ];
+        let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+        assert_eq!(exp.instrs.len(), 4);
+        let bits: Vec<u64> = exp
+            .instrs
+            .iter()
+            .map(|instr| match instr {
+                Instruction::Numeric(NumericInstruction::Const(
+                    ConstNumericInstruction::ConstF32(v),
+                )) => v.to_bits() as u64,
+                Instruction::Numeric(NumericInstruction::Const(
+                    ConstNumericInstruction::ConstF64(v),
+                )) => v.to_bits(),
+                _ => panic!("unexpected instruction"),
+            })
+            .collect();
+        assert_eq!(
+            bits,
+            vec![
+                0x8000_0000,
+                0x7fa0_0001,
+                std::f64::consts::PI.to_bits(),
+                0xfff8_0000_0000_0001
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_import_section() {
+        // (module
+        //   (import "env" "f" (func (type 0)))
+        //   (import "env" "t" (table 1 funcref))
+        //   (import "env" "m" (memory 1 2))
+        //   (import "env" "g" (global (mut i32)))
+        // )
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            // import section
+            0x02, 0x26, // id, length
+            0x04, // number of element
+            0x03, 0x65, 0x6e, 0x76, 0x01, 0x66, 0x00, 0x00, // func
+            0x03, 0x65, 0x6e, 0x76, 0x01, 0x74, 0x01, 0x70, 0x00, 0x01, // table
+            0x03, 0x65, 0x6e, 0x76, 0x01, 0x6d, 0x02, 0x01, 0x01, 0x02, // memory
+            0x03, 0x65, 0x6e, 0x76, 0x01, 0x67, 0x03, 0x7f, 0x01, // global
+        ];
+        let input = &mut Cursor::new(input);
+        let result = module::Module::parse(input).unwrap();
+        let elm = &result.sections.get(1).unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Import(_)));
+
+        if let SectionData::Import(im) = elm {
+            assert_eq!(im.imports.len(), 4);
+            for i in &im.imports {
+                assert_eq!(i.module, b"env");
+            }
+            let names: Vec<&[u8]> = im.imports.iter().map(|i| i.name.as_slice()).collect();
+            assert_eq!(names, vec![b"f", b"t", b"m", b"g"]);
+            assert!(matches!(
+                im.imports[0].desc,
+                section::ImportDesc::TypeIndex(0)
+            ));
+            assert!(matches!(
+                &im.imports[1].desc,
+                section::ImportDesc::Table(t) if matches!(t.element_type, ReferenceType::FunctionRef)
+                    && t.limits.min == 1 && t.limits.max.is_none()
+            ));
+            assert!(matches!(
+                &im.imports[2].desc,
+                section::ImportDesc::Memory(m) if m.limits.min == 1 && m.limits.max == Some(2)
+            ));
+            assert!(matches!(
+                &im.imports[3].desc,
+                section::ImportDesc::Global(g) if matches!(g.value_type, ValueType::Number(NumberType::I32))
+                    && matches!(g.mutability, Mutability::Var)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_table_and_memory_section() {
+        // (module
+        //   (table 2 10 externref)
+        //   (memory 1)
+        //   (memory 0 65536)
+        // )
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x04, 0x05, 0x01, 0x6f, 0x01, 0x02, 0x0a, // table section
+            // memory section
+            0x05, 0x08, // id, length
+            0x02, // number of element
+            0x00, 0x01, // a
+            0x01, 0x00, 0x80, 0x80, 0x04, // b
+        ];
+        let input = &mut Cursor::new(input);
+        let result = module::Module::parse(input).unwrap();
+
+        let elm = &result.sections.first().unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Table(_)));
+        if let SectionData::Table(ts) = elm {
+            assert_eq!(ts.tables.len(), 1);
+            let t = ts.tables.first().unwrap();
+            assert!(matches!(t.element_type, ReferenceType::ExternRef));
+            assert_eq!(t.limits.min, 2);
+            assert_eq!(t.limits.max, Some(10));
+        }
+
+        let elm = &result.sections.get(1).unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Memory(_)));
+        if let SectionData::Memory(ms) = elm {
+            assert_eq!(ms.memories.len(), 2);
+            let m = ms.memories.first().unwrap();
+            assert_eq!(m.limits.min, 1);
+            assert_eq!(m.limits.max, None);
+            let m = ms.memories.get(1).unwrap();
+            assert_eq!(m.limits.min, 0);
+            assert_eq!(m.limits.max, Some(65536));
+        }
+
+        // limits の flag は 0x00 か 0x01 のみ
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x05, 0x03, 0x01, 0x02, 0x01, // memory section
+        ];
+        let result = module::Module::parse(&mut Cursor::new(input));
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedByteValue { got: 0x02, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_global_section() {
+        // (module
+        //   (global $__stack_pointer (mut i32) (i32.const 65536))
+        //   (global i64 (i64.const -42))
+        // )
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            // global section
+            0x06, 0x0d, // id, length
+            0x02, // number of element
+            0x7f, 0x01, 0x41, 0x80, 0x80, 0x04, 0x0b, // a
+            0x7e, 0x00, 0x42, 0x56, 0x0b, // b
+        ];
+        let input = &mut Cursor::new(input);
+        let result = module::Module::parse(input).unwrap();
+
+        let elm = &result.sections.first().unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Global(_)));
+        if let SectionData::Global(gs) = elm {
+            assert_eq!(gs.globals.len(), 2);
+
+            let g = gs.globals.first().unwrap();
+            assert!(matches!(
+                g.global_type.value_type,
+                ValueType::Number(NumberType::I32)
+            ));
+            assert!(matches!(g.global_type.mutability, Mutability::Var));
+            assert_eq!(g.init.instrs.len(), 1);
+            assert!(matches!(
+                g.init.instrs.first(),
+                Some(Instruction::Numeric(NumericInstruction::Const(
+                    ConstNumericInstruction::ConstI32(65536)
+                )))
+            ));
+
+            let g = gs.globals.get(1).unwrap();
+            assert!(matches!(
+                g.global_type.value_type,
+                ValueType::Number(NumberType::I64)
+            ));
+            assert!(matches!(g.global_type.mutability, Mutability::Const));
+            assert!(matches!(
+                g.init.instrs.first(),
+                Some(Instruction::Numeric(NumericInstruction::Const(
+                    ConstNumericInstruction::ConstI64(-42)
+                )))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_start_element_data_section() {
+        // (module
+        //   (import "env" "g" (global funcref))
+        //   (table 2 funcref)
+        //   (memory 1)
+        //   (start 0)
+        //   (elem (i32.const 0) 0)
+        //   (elem func 0)
+        //   (elem (table 0) (i32.const 1) func 0 0)
+        //   (elem declare func 0)
+        //   (elem funcref (global.get 0))
+        //   (func)
+        //   (data (i32.const 16) "hi")
+        //   (data "x")
+        // )
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x02, 0x0a, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x01, 0x67, 0x03, 0x70,
+            0x00, // import section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x02, // table section
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x08, 0x01, 0x00, // start section
+            // element section
+            0x09, 0x1e, // id, length
+            0x05, // number of element
+            0x00, 0x41, 0x00, 0x0b, 0x01, 0x00, // flag 0
+            0x01, 0x00, 0x01, 0x00, // flag 1
+            0x02, 0x00, 0x41, 0x01, 0x0b, 0x00, 0x02, 0x00, 0x00, // flag 2
+            0x03, 0x00, 0x01, 0x00, // flag 3
+            0x05, 0x70, 0x01, 0x23, 0x00, 0x0b, // flag 5
+            0x0c, 0x01, 0x02, // data count section
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+            // data section
+            0x0b, 0x0b, // id, length
+            0x02, // number of element
+            0x00, 0x41, 0x10, 0x0b, 0x02, 0x68, 0x69, // a
+            0x01, 0x01, 0x78, // b
+        ];
+        let input = &mut Cursor::new(input);
+        let result = module::Module::parse(input).unwrap();
+
+        let elm = &result.sections.get(5).unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Start(s) if s.func_index == 0));
+
+        let elm = &result.sections.get(6).unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Element(_)));
+        if let SectionData::Element(es) = elm {
+            assert_eq!(es.elements.len(), 5);
+            for e in &es.elements {
+                assert!(matches!(e.element_type, ReferenceType::FunctionRef));
+            }
+            let e = &es.elements[0];
+            assert!(matches!(
+                &e.mode,
+                section::ElementMode::Active { table: 0, offset } if offset.instrs.len() == 1
+            ));
+            assert!(matches!(&e.init, section::ElementInit::FuncIndexies(v) if v == &[0]));
+            let e = &es.elements[1];
+            assert!(matches!(e.mode, section::ElementMode::Passive));
+            assert!(matches!(&e.init, section::ElementInit::FuncIndexies(v) if v == &[0]));
+            let e = &es.elements[2];
+            assert!(matches!(
+                e.mode,
+                section::ElementMode::Active { table: 0, .. }
+            ));
+            assert!(matches!(&e.init, section::ElementInit::FuncIndexies(v) if v == &[0, 0]));
+            let e = &es.elements[3];
+            assert!(matches!(e.mode, section::ElementMode::Declarative));
+            let e = &es.elements[4];
+            assert!(matches!(e.mode, section::ElementMode::Passive));
+            assert!(matches!(
+                &e.init,
+                section::ElementInit::Expressions(v) if matches!(
+                    v[0].instrs.first(),
+                    Some(Instruction::Variable(VariableInstruction::GlobalGet(0)))
+                )
+            ));
+        }
+
+        let elm = &result.sections.get(7).unwrap().payload_data;
+        assert!(matches!(elm, SectionData::DataCount(d) if d.count == 2));
+
+        let elm = &result.sections.get(9).unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Data(_)));
+        if let SectionData::Data(ds) = elm {
+            assert_eq!(ds.data.len(), 2);
+            let d = &ds.data[0];
+            assert!(matches!(
+                d.mode,
+                section::DataMode::Active { memory: 0, .. }
+            ));
+            assert_eq!(d.init, b"hi");
+            let d = &ds.data[1];
+            assert!(matches!(d.mode, section::DataMode::Passive));
+            assert_eq!(d.init, b"x");
+        }
+    }
+
+    #[test]
+    fn test_parse_code_locals() {
+        // (module
+        //   (func (local i32 i32 i32) (local i64) (local f32 f32)
+        //     local.get 5)
+        // )
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            // code section
+            0x0a, 0x0c, // id, length
+            0x01, // number of element
+            0x0a, // length of code
+            0x03, 0x03, 0x7f, 0x01, 0x7e, 0x02, 0x7d, // locals
+            0x20, 0x05, // expr
+            0x0b, // end
+        ];
+        let input = &mut Cursor::new(input);
+        let result = module::Module::parse(input).unwrap();
+        let elm = &result.sections.get(2).unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Code(_)));
+        if let SectionData::Code(cs) = elm {
+            let c = cs.codes.first().unwrap();
+            let locals = c.locals().unwrap();
+            assert_eq!(locals.len(), 6);
+            assert!(locals[0..3]
+                .iter()
+                .all(|t| matches!(t, ValueType::Number(NumberType::I32))));
+            assert!(matches!(locals[3], ValueType::Number(NumberType::I64)));
+            assert!(locals[4..6]
+                .iter()
+                .all(|t| matches!(t, ValueType::Number(NumberType::F32))));
+            assert_eq!(c.expression().unwrap().instrs.len(), 1);
+        }
+
+        // (local i32 * 0xffffffff) (local i32 * 0xffffffff)
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            // code section
+            0x0a, 0x10, // id, length
+            0x01, // number of element
+            0x0e, // length of code
+            0x02, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x7f, 0xff, 0xff, 0xff, 0xff, 0x0f,
+            0x7f, // locals
+            0x0b, // end
+        ];
+        // 関数本体はデコードするまでエラーにならない
+        let result = module::Module::parse(&mut Cursor::new(input)).unwrap();
+        let elm = &result.sections.get(2).unwrap().payload_data;
+        assert!(matches!(elm, SectionData::Code(_)));
+        if let SectionData::Code(cs) = elm {
+            let c = cs.codes.first().unwrap();
+            assert!(matches!(
+                c.locals().as_ref().map_err(|e| e.kind()),
+                Err(parse::ParseError::TooManyLocals(0x1_ffff_fffe))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_control_instruction() {
+        let input: &[u8] = &[
+            0x02, 0x7f, // block (result i32)
+            0x03, 0x40, // loop
+            0x20, 0x00, // local.get 0
+            0x0d, 0x00, // br_if 0
+            0x0b, // end
+            0x20, 0x00, // local.get 0
+            0x04, 0x00, // if (type 0)
+            0x41, 0x01, // i32.const 1
+            0x05, // else
+            0x41, 0x02, // i32.const 2
+            0x0b, // end
+            0x0e, 0x02, 0x00, 0x01, 0x00, // br_table 0 1 0
+            0x0b, // end
+            0x04, 0x40, // if
+            0x0c, 0x00, // br 0
+            0x0b, // end
+            0x10, 0x03, // call 3
+            0x11, 0x01, 0x00, // call_indirect (type 1) 0
+            0x0f, // return
+            0x0b, // end
+        ];
+        let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+        assert_eq!(exp.instrs.len(), 5);
+
+        let block = match exp.instrs.first() {
+            Some(Instruction::Control(ControlInstruction::Block { block_type, instrs })) => {
+                assert!(matches!(
+                    block_type,
+                    BlockType::Value(ValueType::Number(NumberType::I32))
+                ));
+                instrs
+            }
+            _ => panic!("expected block"),
+        };
+        assert_eq!(block.len(), 4);
+        assert!(matches!(
+            block.first(),
+            Some(Instruction::Control(ControlInstruction::Loop {
+                block_type: BlockType::Empty,
+                instrs,
+            })) if instrs.len() == 2
+                && matches!(instrs[1], Instruction::Control(ControlInstruction::BrIf(0)))
+        ));
+        assert!(matches!(
+            block.get(2),
+            Some(Instruction::Control(ControlInstruction::IfElse {
+                block_type: BlockType::TypeIndex(0),
+                then_instrs,
+                else_instrs: Some(else_instrs),
+            })) if then_instrs.len() == 1 && else_instrs.len() == 1
+        ));
+        assert!(matches!(
+            block.get(3),
+            Some(Instruction::Control(ControlInstruction::BrTable { labels, default: 0 }))
+                if labels == &[0, 1]
+        ));
+
+        assert!(matches!(
+            exp.instrs.get(1),
+            Some(Instruction::Control(ControlInstruction::IfElse {
+                block_type: BlockType::Empty,
+                then_instrs,
+                else_instrs: None,
+            })) if matches!(then_instrs[..], [Instruction::Control(ControlInstruction::Br(0))])
+        ));
+        assert!(matches!(
+            exp.instrs.get(2),
+            Some(Instruction::Control(ControlInstruction::Call(3)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(3),
+            Some(Instruction::Control(ControlInstruction::CallIndirect {
+                type_index: 1,
+                table_index: 0
+            }))
+        ));
+        assert!(matches!(
+            exp.instrs.get(4),
+            Some(Instruction::Control(ControlInstruction::Return))
+        ));
+
+        // if の外の else
+        let input: &[u8] = &[0x02, 0x40, 0x05, 0x0b, 0x0b];
+        let result = instruction::Expression::parse(&mut &input[..]);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedValue(_))
+        ));
+        // end が足りない
+        let input: &[u8] = &[0x02, 0x40, 0x0b];
+        let result = instruction::Expression::parse(&mut &input[..]);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::Decode(
+                crate::decode::DecodeError::UnexpectedEof
+            ))
+        ));
+        // 負の型インデックス
+        let input: &[u8] = &[0x02, 0x41, 0x0b, 0x0b];
+        let result = instruction::Expression::parse(&mut &input[..]);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_deeply_nested_blocks() {
+        // 入れ子の深さだけ再帰すると、パース・書き出し・解放のどこかでスタックが溢れる
+        const DEPTH: usize = 200_000;
+        let mut body = vec![0x00]; // local なし
+        for _ in 0..DEPTH {
+            body.extend([0x02, 0x40]);
+        }
+        body.extend(std::iter::repeat_n(0x0b, DEPTH + 1));
+        let mut code = Vec::new();
+        crate::encode::encode_varint_u32(&mut code, 1);
+        crate::encode::encode_len(&mut code, &body);
+        let mut input = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x0a, // code section
+        ];
+        crate::encode::encode_len(&mut input, &code);
+
+        let module = parse_module(&input).unwrap();
+        match module.sections()[2].payload_data() {
+            SectionData::Code(cs) => cs.decode_all().unwrap(),
+            _ => panic!("expected code section"),
+        }
+        assert_eq!(module.encode(), input);
+        drop(module);
+    }
+
+    #[test]
+    fn test_parse_plain_numeric_instruction() {
+        for by in 0x45..=0xC4 {
+            let input: &[u8] = &[by, 0x0b];
+            let result = instruction::Expression::parse(&mut &input[..]);
+            assert!(result.is_ok(), "opcode=0x{:02x}", by);
+        }
+
+        let input: &[u8] = &[
+            0x45, // i32.eqz
+            0x5a, // i64.ge_u
+            0x66, // f64.ge
+            0x78, // i32.rotr
+            0x98, // f32.copysign
+            0xa7, // i32.wrap_i64
+            0xab, // i32.trunc_f64_u
+            0xbb, // f64.promote_f32
+            0xbf, // f64.reinterpret_i64
+            0xc4, // i64.extend32_s
+            0x0b, // end
+        ];
+        let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+        let expected = [
+            PlainNumericInstruction::EqzI32,
+            PlainNumericInstruction::GeUI64,
+            PlainNumericInstruction::GeF64,
+            PlainNumericInstruction::RotrI32,
+            PlainNumericInstruction::CopysignF32,
+            PlainNumericInstruction::WrapI32I64,
+            PlainNumericInstruction::TruncUI32F64,
+            PlainNumericInstruction::PromoteF64F32,
+            PlainNumericInstruction::ReinterpretF64I64,
+            PlainNumericInstruction::Extend32SI64,
+        ];
+        assert_eq!(exp.instrs.len(), expected.len());
+        for (instr, expected) in exp.instrs.iter().zip(expected.iter()) {
+            match instr {
+                Instruction::Numeric(NumericInstruction::Plain(p)) => {
+                    assert_eq!(std::mem::discriminant(p), std::mem::discriminant(expected));
+                }
+                _ => panic!("expected plain numeric instruction"),
+            }
+        }
+
+        let input: &[u8] = &[0xc5, 0x0b];
+        let result = instruction::Expression::parse(&mut &input[..]);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedByteValue { got: 0xc5, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_memory_instruction() {
+        let input: &[u8] = &[
+            0x28, 0x02, 0x00, // i32.load align=4
+            0x31, 0x00, 0x80, 0x01, // i64.load8_u offset=128
+            0x36, 0x02, 0x04, // i32.store offset=4 align=4
+            0x3e, 0x02, 0x00, // i64.store32
+            0x3f, 0x00, // memory.size
+            0x40, 0x00, // memory.grow
+            0x0b, // end
+        ];
+        let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+        assert_eq!(exp.instrs.len(), 6);
+        assert!(matches!(
+            exp.instrs.first(),
+            Some(Instruction::Memory(MemoryInstruction::LoadI32(MemArg {
+                align: 2,
+                offset: 0
+            })))
+        ));
+        assert!(matches!(
+            exp.instrs.get(1),
+            Some(Instruction::Memory(MemoryInstruction::Load8UI64(MemArg {
+                align: 0,
+                offset: 128
+            })))
+        ));
+        assert!(matches!(
+            exp.instrs.get(2),
+            Some(Instruction::Memory(MemoryInstruction::StoreI32(MemArg {
+                align: 2,
+                offset: 4
+            })))
+        ));
+        assert!(matches!(
+            exp.instrs.get(3),
+            Some(Instruction::Memory(MemoryInstruction::Store32I64(_)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(4),
+            Some(Instruction::Memory(MemoryInstruction::Size))
+        ));
+        assert!(matches!(
+            exp.instrs.get(5),
+            Some(Instruction::Memory(MemoryInstruction::Grow))
+        ));
+
+        let input: &[u8] = &[0x40, 0x01, 0x0b];
+        let result = instruction::Expression::parse(&mut &input[..]);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedByteValue { got: 0x01, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_parametric_and_reference_instruction() {
+        let input: &[u8] = &[
+            0x1a, // drop
+            0x1b, // select
+            0x1c, 0x01, 0x7e, // select (result i64)
+            0xd0, 0x70, // ref.null func
+            0xd0, 0x6f, // ref.null extern
+            0xd1, // ref.is_null
+            0xd2, 0x07, // ref.func 7
+            0x0b, // end
+        ];
+        let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+        assert_eq!(exp.instrs.len(), 7);
+        assert!(matches!(
+            exp.instrs.first(),
+            Some(Instruction::Parametric(ParametricInstruction::Drop))
+        ));
+        assert!(matches!(
+            exp.instrs.get(1),
+            Some(Instruction::Parametric(ParametricInstruction::Select))
+        ));
+        assert!(matches!(
+            exp.instrs.get(2),
+            Some(Instruction::Parametric(ParametricInstruction::SelectTyped(ts)))
+                if matches!(ts[..], [ValueType::Number(NumberType::I64)])
+        ));
+        assert!(matches!(
+            exp.instrs.get(3),
+            Some(Instruction::Reference(ReferenceInstruction::RefNull(
+                ReferenceType::FunctionRef
+            )))
+        ));
+        assert!(matches!(
+            exp.instrs.get(4),
+            Some(Instruction::Reference(ReferenceInstruction::RefNull(
+                ReferenceType::ExternRef
+            )))
+        ));
+        assert!(matches!(
+            exp.instrs.get(5),
+            Some(Instruction::Reference(ReferenceInstruction::RefIsNull))
+        ));
+        assert!(matches!(
+            exp.instrs.get(6),
+            Some(Instruction::Reference(ReferenceInstruction::RefFunc(7)))
+        ));
+
+        let input: &[u8] = &[0xd0, 0x7f, 0x0b];
+        let result = instruction::Expression::parse(&mut &input[..]);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedByteValue { got: 0x7f, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_prefixed_instruction() {
+        let input: &[u8] = &[
+            0xfc, 0x00, // i32.trunc_sat_f32_s
+            0xfc, 0x07, // i64.trunc_sat_f64_u
+            0xfc, 0x08, 0x01, 0x00, // memory.init 1
+            0xfc, 0x09, 0x01, // data.drop 1
+            0xfc, 0x0a, 0x00, 0x00, // memory.copy
+            0xfc, 0x0b, 0x00, // memory.fill
+            0xfc, 0x0c, 0x02, 0x01, // table.init 1 2
+            0xfc, 0x0d, 0x02, // elem.drop 2
+            0xfc, 0x0e, 0x01, 0x00, // table.copy 1 0
+            0xfc, 0x0f, 0x00, // table.grow 0
+            0xfc, 0x10, 0x00, // table.size 0
+            0xfc, 0x11, 0x00, // table.fill 0
+            0x25, 0x00, // table.get 0
+            0x26, 0x01, // table.set 1
+            0x0b, // end
+        ];
+        let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+        assert_eq!(exp.instrs.len(), 14);
+        assert!(matches!(
+            exp.instrs.first(),
+            Some(Instruction::Numeric(
+                NumericInstruction::SaturatingTruncation(
+                    SaturatingTruncationInstruction::TruncSatSI32F32
+                )
+            ))
+        ));
+        assert!(matches!(
+            exp.instrs.get(1),
+            Some(Instruction::Numeric(
+                NumericInstruction::SaturatingTruncation(
+                    SaturatingTruncationInstruction::TruncSatUI64F64
+                )
+            ))
+        ));
+        assert!(matches!(
+            exp.instrs.get(2),
+            Some(Instruction::Memory(MemoryInstruction::Init(1)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(3),
+            Some(Instruction::Memory(MemoryInstruction::DataDrop(1)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(4),
+            Some(Instruction::Memory(MemoryInstruction::Copy))
+        ));
+        assert!(matches!(
+            exp.instrs.get(5),
+            Some(Instruction::Memory(MemoryInstruction::Fill))
+        ));
+        assert!(matches!(
+            exp.instrs.get(6),
+            Some(Instruction::Table(TableInstruction::Init {
+                elem_index: 2,
+                table_index: 1
+            }))
+        ));
+        assert!(matches!(
+            exp.instrs.get(7),
+            Some(Instruction::Table(TableInstruction::ElemDrop(2)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(8),
+            Some(Instruction::Table(TableInstruction::Copy {
+                dst: 1,
+                src: 0
+            }))
+        ));
+        assert!(matches!(
+            exp.instrs.get(9),
+            Some(Instruction::Table(TableInstruction::Grow(0)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(10),
+            Some(Instruction::Table(TableInstruction::Size(0)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(11),
+            Some(Instruction::Table(TableInstruction::Fill(0)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(12),
+            Some(Instruction::Table(TableInstruction::Get(0)))
+        ));
+        assert!(matches!(
+            exp.instrs.get(13),
+            Some(Instruction::Table(TableInstruction::Set(1)))
+        ));
+
+        // sub opcode は u32 の LEB128
+        let input: &[u8] = &[0xfc, 0x80, 0x00, 0x0b];
+        let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+        assert!(matches!(
+            exp.instrs.first(),
+            Some(Instruction::Numeric(
+                NumericInstruction::SaturatingTruncation(
+                    SaturatingTruncationInstruction::TruncSatSI32F32
+                )
+            ))
+        ));
+        let input: &[u8] = &[0xfc, 0x12, 0x0b];
+        let result = instruction::Expression::parse(&mut &input[..]);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_vector_instruction() {
+        let input: &[u8] = &[
+            0xfd, 0x00, 0x04, 0x10, // v128.load offset=16 align=16
+            0xfd, 0x0c, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+            0x0c, 0x0d, 0x0e, 0x0f, // v128.const i8x16 0 1 .. 15
+            0xfd, 0x0d, 0x1f, 0x1e, 0x1d, 0x1c, 0x1b, 0x1a, 0x19, 0x18, 0x17, 0x16, 0x15, 0x14,
+            0x13, 0x12, 0x11, 0x10, // i8x16.shuffle 31 30 .. 16
+            0xfd, 0x15, 0x0f, // i8x16.extract_lane_s 15
+            0xfd, 0x54, 0x00, 0x00, 0x03, // v128.load8_lane 3
+            0xfd, 0xba, 0x01, // i32x4.dot_i16x8_s
+            0xfd, 0xff, 0x01, // f64x2.convert_low_i32x4_u
+            0x0b, // end
+        ];
+        let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+        assert_eq!(exp.instrs.len(), 7);
+        assert!(matches!(
+            exp.instrs.first(),
+            Some(Instruction::Vector(VectorInstruction::Load(MemArg {
+                align: 4,
+                offset: 16
+            })))
+        ));
+        assert!(matches!(
+            exp.instrs.get(1),
+            Some(Instruction::Vector(VectorInstruction::Const(v)))
+                if v == &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+        ));
+        assert!(matches!(
+            exp.instrs.get(2),
+            Some(Instruction::Vector(VectorInstruction::ShuffleI8x16(v)))
+                if v[0] == 31 && v[15] == 16
+        ));
+        assert!(matches!(
+            exp.instrs.get(3),
+            Some(Instruction::Vector(VectorInstruction::ExtractLaneSI8x16(
+                15
+            )))
+        ));
+        assert!(matches!(
+            exp.instrs.get(4),
+            Some(Instruction::Vector(VectorInstruction::Load8Lane(
+                MemArg {
+                    align: 0,
+                    offset: 0
+                },
+                3
+            )))
+        ));
+        assert!(matches!(
+            exp.instrs.get(5),
+            Some(Instruction::Vector(VectorInstruction::DotSI32x4I16x8))
+        ));
+        assert!(matches!(
+            exp.instrs.get(6),
+            Some(Instruction::Vector(
+                VectorInstruction::ConvertLowUF64x2I32x4
+            ))
+        ));
+
+        // 154 は欠番
+        let input: &[u8] = &[0xfd, 0x9a, 0x01, 0x0b];
+        let result = instruction::Expression::parse(&mut &input[..]);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedValue(_))
+        ));
+
+        // (func (param v128) (result v128))
+        let input: &[u8] = &[0x60, 0x01, 0x7b, 0x01, 0x7b];
+        let ft = wasm_type::FunctionType::parse(&mut &input[..]).unwrap();
+        assert!(matches!(
+            ft.params_types.valu_types[..],
+            [ValueType::Vector(VectorType::V128)]
+        ));
+    }
+
+    // 1 回の read で 1 byte しか返さない reader
+    struct OneByteReader<'a>(&'a [u8]);
+    impl std::io::Read for OneByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_parse_from_reader() {
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code section
+        ];
+
+        let mut reader = module::ModuleReader::new(OneByteReader(input)).unwrap();
+        assert_eq!(reader.magic_number(), 0x6d736100);
+        assert_eq!(reader.version(), 1);
+        assert!(matches!(reader.next(), Some(Ok(s)) if s.id == 1));
+        assert!(matches!(reader.next(), Some(Ok(s)) if s.id == 3));
+        assert!(matches!(reader.next(), Some(Ok(s)) if s.id == 10));
+        assert!(reader.next().is_none());
+
+        let result = from_reader(OneByteReader(input)).unwrap();
+        assert_eq!(result.sections.len(), 3);
+
+        // section の境界で終わっていれば正常終了
+        let result = from_reader(OneByteReader(&input[..19])).unwrap();
+        assert_eq!(result.sections.len(), 2);
+
+        // section の途中で終わっていれば途中で切れている
+        for end in [20, 21, 25] {
+            let mut reader = module::ModuleReader::new(OneByteReader(&input[..end])).unwrap();
+            assert!(matches!(reader.next(), Some(Ok(_))));
+            assert!(matches!(reader.next(), Some(Ok(_))));
+            assert!(matches!(
+                reader
+                    .next()
+                    .unwrap()
+                    .err()
+                    .as_ref()
+                    .map(parse::ParseError::kind),
+                Some(parse::ParseError::Decode(DecodeError::UnexpectedEof))
+            ));
+            assert!(reader.next().is_none());
+        }
+
+        // header の途中で終わっている
+        let result = from_reader(OneByteReader(&input[..6]));
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::Decode(DecodeError::UnexpectedEof))
+        ));
+    }
+
+    #[test]
+    fn test_parse_module_borrowed() {
+        // (module
+        // (func $add (param $lhs i32) (param $rhs i32) (result i32)
+        //     get_local $lhs
+        //     get_local $rhs
+        //     i32.add)
+        // (export "add" (func $add))
+        // (data (i32.const 0) "hi")
+        // )
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, // magic number
+            0x01, 0x00, 0x00, 0x00, // version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export section
+            // code section
+            0x0a, 0x0b, // id, length
+            0x01, // number of element
+            0x09, 0x01, 0x01, 0x7e, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // a
+            0x0b, 0x08, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x02, 0x68, 0x69, // data section
+            // custom section
+            0x00, 0x0c, // id, length
+            0x04, 0x6e, 0x61, 0x6d, 0x65, 0x01, 0x06, 0x01, 0x00, 0x03, 0x61, 0x64, // a
+        ];
+        let input_range = input.as_ptr_range();
+        let result = parse_module_borrowed(input).unwrap();
+        assert_eq!(result.magic_number(), 0x6d736100);
+        assert_eq!(result.version(), 1);
+        assert_eq!(result.sections().len(), 6);
+
+        match result.sections()[2].payload_data() {
+            borrowed::SectionData::Export(ex) => {
+                let name = ex.exports()[0].name();
+                assert_eq!(name, b"add");
+                assert!(input_range.contains(&name.as_ptr()));
+                assert!(matches!(
+                    ex.exports()[0].desc(),
+                    section::ExportDesc::FuncIndex(0)
+                ));
+            }
+            _ => panic!("expected export section"),
+        }
+
+        match result.sections()[3].payload_data() {
+            borrowed::SectionData::Code(cs) => {
+                let c = &cs.codes()[0];
+                assert!(input_range.contains(&c.body().as_ptr()));
+                let locals = c.locals().unwrap();
+                assert!(matches!(locals[..], [ValueType::Number(NumberType::I64)]));
+                let instrs = c
+                    .instructions()
+                    .unwrap()
+                    .collect::<parse::Result<Vec<_>>>()
+                    .unwrap();
+                assert_eq!(instrs.len(), 3);
+                assert!(matches!(
+                    instrs[0],
+                    Instruction::Variable(VariableInstruction::LocalGet(0))
+                ));
+                assert!(matches!(
+                    instrs[2],
+                    Instruction::Numeric(NumericInstruction::Plain(
+                        PlainNumericInstruction::AddI32
+                    ))
+                ));
+            }
+            _ => panic!("expected code section"),
+        }
+
+        match result.sections()[4].payload_data() {
+            borrowed::SectionData::Data(ds) => {
+                let init = ds.data()[0].init();
+                assert_eq!(init, b"hi");
+                assert!(input_range.contains(&init.as_ptr()));
+            }
+            _ => panic!("expected data section"),
+        }
+
+        match result.sections()[5].payload_data() {
+            borrowed::SectionData::Custom(c) => {
+                assert_eq!(c.name(), b"name");
+                assert_eq!(c.payload(), &[0x01, 0x06, 0x01, 0x00, 0x03, 0x61, 0x64]);
+            }
+            _ => panic!("expected custom section"),
+        }
+    }
+
+    #[test]
+    fn test_instruction_reader() {
+        // 関数本体のエラーは命令を読んだ時点で分かる
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x01, 0x02, 0x40, 0x0b, 0xff, // code section
+        ];
+        let result = parse_module_borrowed(input).unwrap();
+        let c = match result.sections()[0].payload_data() {
+            borrowed::SectionData::Code(cs) => &cs.codes()[0],
+            _ => panic!("expected code section"),
+        };
+        let mut reader = c.instructions().unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Ok(Instruction::Control(ControlInstruction::Nop)))
+        ));
+        assert!(matches!(
+            reader.next(),
+            Some(Ok(Instruction::Control(ControlInstruction::Block { .. })))
+        ));
+        assert!(matches!(
+            reader
+                .next()
+                .unwrap()
+                .err()
+                .as_ref()
+                .map(parse::ParseError::kind),
+            Some(parse::ParseError::UnexpectedByteValue { got: 0xff, .. })
+        ));
+        assert!(reader.next().is_none());
+
+        // end の後にバイトが残っている
+        let input: &[u8] = &[0x00, 0x01, 0x0b, 0x01];
+        let code = borrowed::Code {
+            body: input,
+            offset: 0,
+            index: 0,
+        };
+        let mut reader = code.instructions().unwrap();
+        assert!(matches!(reader.next(), Some(Ok(_))));
+        assert!(matches!(
+            reader
+                .next()
+                .unwrap()
+                .err()
+                .as_ref()
+                .map(parse::ParseError::kind),
+            Some(parse::ParseError::UnexpectedValue(_))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_decode_code_lazily() {
+        // 3 つの関数のうち 2 番目の本体が不正
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x0a, 0x0a, 0x03, // code section
+            0x02, 0x00, 0x0b, // func 0
+            0x02, 0x00, 0xff, // func 1
+            0x02, 0x00, 0x0b, // func 2
+        ];
+        let result = parse_module(input).unwrap();
+        let cs = match result.sections()[0].payload_data() {
+            SectionData::Code(cs) => cs,
+            _ => panic!("expected code section"),
+        };
+        assert!(cs.codes().iter().all(|c| c.decoded.get().is_none()));
+        assert_eq!(cs.codes()[1].body(), &[0x00, 0xff]);
+
+        assert!(cs.codes()[0].expression().is_ok());
+        assert!(cs.codes()[0].decoded.get().is_some());
+        assert!(cs.codes()[2].decoded.get().is_none());
+
+        assert!(matches!(
+            cs.decode_all_parallel().as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedByteValue { got: 0xff, .. })
+        ));
+        assert!(matches!(
+            cs.decode_all().as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedByteValue { got: 0xff, .. })
+        ));
+        assert!(cs.codes()[2].expression().is_ok());
+
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x0a, 0x0a, 0x03, // code section
+            0x02, 0x00, 0x0b, // func 0
+            0x02, 0x00, 0x0b, // func 1
+            0x02, 0x00, 0x0b, // func 2
+        ];
+        let result = parse_module(input).unwrap();
+        if let SectionData::Code(cs) = result.sections()[0].payload_data() {
+            assert!(cs.decode_all_parallel().is_ok());
+            assert!(cs.codes().iter().all(|c| c.decoded.get().is_some()));
+        }
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let cases: &[(&[u8], u32, u32)] = &[
+            // "\0ASM"
+            (
+                &[0x00, 0x41, 0x53, 0x4d, 0x01, 0x00, 0x00, 0x00],
+                0x4d534100,
+                1,
+            ),
+            // version 2
+            (
+                &[0x00, 0x61, 0x73, 0x6d, 0x02, 0x00, 0x00, 0x00],
+                0x6d736100,
+                2,
+            ),
+            // component model の layer 1
+            (
+                &[0x00, 0x61, 0x73, 0x6d, 0x0d, 0x00, 0x01, 0x00],
+                0x6d736100,
+                0x1000d,
+            ),
+        ];
+        for (input, magic, ver) in cases.iter().copied() {
+            let errors = vec![
+                parse_module(input).err().unwrap(),
+                from_reader(input).err().unwrap(),
+                parse_module_borrowed(input).err().unwrap(),
+            ];
+            for e in errors {
+                assert!(
+                    matches!(
+                        e.kind(),
+                        Error::InvalidHeader { magic_number, version }
+                            if *magic_number == magic && *version == ver
+                    ),
+                    "{}",
+                    e
+                );
+                assert_eq!(e.context().unwrap().offset(), Some(0));
+            }
+        }
+        assert_eq!(
+            parse_module(cases[1].0).err().unwrap().to_string(),
+            "offset 0x0: invalid module header. magic_number=0x6d736100, version=2"
+        );
+    }
+
+    #[test]
+    fn test_section_order() {
+        let header: &[u8] = &[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let custom: &[u8] = &[0x00, 0x02, 0x01, 0x61];
+        let function: &[u8] = &[0x03, 0x01, 0x00];
+        let data_count: &[u8] = &[0x0c, 0x01, 0x00];
+        let code: &[u8] = &[0x0a, 0x01, 0x00];
+        let module = |sections: &[&[u8]]| {
+            let mut v = header.to_vec();
+            sections.iter().for_each(|s| v.extend_from_slice(s));
+            v
+        };
+
+        // custom section はどこに置いてもよく、data count section は code section の前
+        let input = module(&[custom, function, custom, data_count, custom, code, custom]);
+        assert_eq!(parse_module(&input).unwrap().sections().len(), 7);
+        assert_eq!(parse_module_borrowed(&input).unwrap().sections().len(), 7);
+
+        let input = module(&[code, data_count]);
+        assert!(matches!(
+            parse_module(&input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedSectionOrder { id: 12, after: 10 })
+        ));
+        assert!(matches!(
+            parse_module_borrowed(&input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::UnexpectedSectionOrder { id: 12, after: 10 })
+        ));
+
+        let input = module(&[function, custom, function]);
+        assert!(matches!(
+            parse_module(&input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::DuplicateSection(3))
+        ));
+        assert!(matches!(
+            parse_module_borrowed(&input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::DuplicateSection(3))
+        ));
+
+        // 不正な順番のところで読み出しが止まる
+        let input = module(&[function, code, data_count, custom]);
+        let mut reader = module::ModuleReader::new(input.as_slice()).unwrap();
+        assert!(matches!(reader.next(), Some(Ok(_))));
+        assert!(matches!(reader.next(), Some(Ok(_))));
+        assert!(matches!(
+            reader
+                .next()
+                .unwrap()
+                .err()
+                .as_ref()
+                .map(parse::ParseError::kind),
+            Some(parse::ParseError::UnexpectedSectionOrder { .. })
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_section_size_mismatch() {
+        // payload が余る
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x03, 0x03, 0x01, 0x00, 0x00, // function section
+        ];
+        assert!(matches!(
+            parse_module(input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::SectionSizeMismatch {
+                id: 3,
+                payload_len: 3
+            })
+        ));
+        assert!(matches!(
+            parse_module_borrowed(input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::SectionSizeMismatch {
+                id: 3,
+                payload_len: 3
+            })
+        ));
+
+        // payload を越えて読もうとする
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x03, 0x02, 0x02, 0x00, // function section
+            0x0a, 0x04, 0x02, 0x02, 0x00, 0x0b, // code section
+        ];
+        assert!(matches!(
+            parse_module(input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::SectionSizeMismatch {
+                id: 3,
+                payload_len: 2
+            })
+        ));
+        assert!(matches!(
+            parse_module_borrowed(input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::SectionSizeMismatch {
+                id: 3,
+                payload_len: 2
+            })
+        ));
+
+        // 入力自体が payload_len より短い場合は size mismatch ではない
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x03, 0x03, 0x01, 0x00, // function section
+        ];
+        assert!(matches!(
+            parse_module(input).as_ref().map_err(|e| e.kind()),
+            Err(parse::ParseError::Decode(DecodeError::UnexpectedEof))
+        ));
+    }
+
+    #[test]
+    fn test_error_context() {
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x0a, 0x09, 0x02, // code section
+            0x02, 0x00, 0x0b, // func 0
+            0x04, 0x00, 0x01, 0x01, 0xff, // func 1
+        ];
+        let check = |e: &parse::ParseError| {
+            let c = e.context().unwrap();
+            assert_eq!(c.offset(), Some(0x12));
+            assert_eq!(c.section_id(), Some(10));
+            assert_eq!(c.func_index(), Some(1));
+            assert_eq!(c.instr_index(), Some(2));
+            assert!(matches!(
+                e.kind(),
+                parse::ParseError::UnexpectedByteValue { got: 0xff, .. }
+            ));
+            assert_eq!(
+                e.to_string(),
+                "offset 0x12 in code section, func 1, instr 2: unexpected value in Instruction. got=0xff"
+            );
+        };
+
+        let result = parse_module(input).unwrap();
+        if let SectionData::Code(cs) = result.sections()[0].payload_data() {
+            check(&cs.decode_all().err().unwrap());
+        }
+
+        let result = parse_module_borrowed(input).unwrap();
+        if let borrowed::SectionData::Code(cs) = result.sections()[0].payload_data() {
+            let mut reader = cs.codes()[1].instructions().unwrap();
+            assert!(matches!(reader.next(), Some(Ok(_))));
+            assert!(matches!(reader.next(), Some(Ok(_))));
+            check(&reader.next().unwrap().err().unwrap());
+        }
+
+        // 関数の番号は import した関数も数える
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x02, 0x15, 0x03, // import section
+            0x01, 0x6d, 0x01, 0x66, 0x00, 0x00, // func
+            0x01, 0x6d, 0x01, 0x74, 0x01, 0x70, 0x00, 0x00, // table
+            0x01, 0x6d, 0x01, 0x67, 0x00, 0x00, // func
+            0x0a, 0x09, 0x02, // code section
+            0x02, 0x00, 0x0b, // func 2
+            0x04, 0x00, 0x01, 0x01, 0xff, // func 3
+        ];
+        let check = |e: &parse::ParseError| {
+            assert_eq!(e.context().unwrap().func_index(), Some(3));
+            assert_eq!(
+                e.to_string(),
+                "offset 0x29 in code section, func 3, instr 2: unexpected value in Instruction. got=0xff"
+            );
+        };
+        for result in [parse_module(input), from_reader(input)] {
+            if let SectionData::Code(cs) = result.unwrap().sections()[1].payload_data() {
+                check(&cs.decode_all().err().unwrap());
+            }
+        }
+        let result = parse_module_borrowed(input).unwrap();
+        if let borrowed::SectionData::Code(cs) = result.sections()[1].payload_data() {
+            let code = &cs.codes()[1];
+            check(&code.instructions().unwrap().nth(2).unwrap().err().unwrap());
+        }
+
+        // 不正な型の先頭バイト
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x04, 0x01, 0x61, 0x00, 0x00, // type section
+        ];
+        for e in [
+            parse_module(input).err().unwrap(),
+            parse_module_borrowed(input).err().unwrap(),
+        ] {
+            let c = e.context().unwrap();
+            assert_eq!(c.offset(), Some(0x0b));
+            assert_eq!(c.section_id(), Some(1));
+            assert_eq!(c.func_index(), None);
+            assert_eq!(c.instr_index(), None);
+        }
+
+        // global の初期化式の中の命令
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0xff, // global section
+        ];
+        let e = parse_module(input).err().unwrap();
+        assert_eq!(
+            e.to_string(),
+            "offset 0xf in global section, instr 1: unexpected value in Instruction. got=0xff"
+        );
+
+        // section の順番
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x03, 0x01, 0x00, // function section
+            0x01, 0x01, 0x00, // type section
+        ];
+        let e = parse_module(input).err().unwrap();
+        assert_eq!(e.context().unwrap().offset(), Some(0x0b));
+        assert_eq!(e.context().unwrap().section_id(), Some(1));
+
+        // 知らない section id は id のバイトの位置
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x0d, 0x00, // section id 13
+        ];
+        for e in [
+            parse_module(input).err().unwrap(),
+            parse_module_borrowed(input).err().unwrap(),
+            from_reader(input).err().unwrap(),
+        ] {
+            assert!(matches!(
+                e.kind(),
+                parse::ParseError::UnexpectedSectionId(13)
+            ));
+            assert_eq!(e.context().unwrap().offset(), Some(8));
+        }
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let with_len = |id: Option<u8>, bytes: &[u8]| {
+            let mut v = id.into_iter().collect::<Vec<_>>();
+            crate::encode::encode_len(&mut v, bytes);
+            v
+        };
+        let section = |id: u8, payload: &[u8]| with_len(Some(id), payload);
+
+        let mut body = vec![
+            0x02, 0x02, 0x7f, 0x01, 0x7b, // locals
+            0x02, 0x40, // block
+            0x03, 0x7f, 0x41, 0x2a, 0x0d, 0x00, 0x41, 0x01, 0x0b, // loop
+            0x04, 0x00, 0x01, 0x05, 0x00, 0x0b, // if else
+            0x04, 0x40, 0x01, 0x0b, // if
+            0x0e, 0x02, 0x00, 0x01, 0x00, // br_table
+            0x0b, // end of block
+            0x20, 0x00, 0x21, 0x01, 0x1a, 0x1b, 0x1c, 0x01, 0x7f, // variable, parametric
+            0x28, 0x02, 0x10, 0x3e, 0x03, 0x80, 0x01, 0x3f, 0x00, 0x40, 0x00, // memory
+            0xfc, 0x08, 0x00, 0x00, 0xfc, 0x09, 0x00, 0xfc, 0x0a, 0x00, 0x00, 0xfc, 0x0b, 0x00,
+            0xfc, 0x0c, 0x00, 0x01, 0xfc, 0x0d, 0x00, 0xfc, 0x0e, 0x00, 0x01, // table
+            0xfc, 0x0f, 0x00, 0xfc, 0x10, 0x00, 0xfc, 0x11, 0x00, 0x25, 0x00, 0x26, 0x00, 0xfc,
+            0x00, 0xfc, 0x07, // saturating truncation
+            0x43, 0x01, 0x00, 0xc0, 0x7f, // f32.const NaN
+            0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0xbf, // f64.const -1.0
+            0x41, 0x80, 0x7f, 0x42, 0xff, 0x00, 0x6a, 0xa7, 0xc4, // numeric
+            0xd0, 0x70, 0xd1, 0xd2, 0x00, // reference
+            0x10, 0x00, 0x11, 0x00, 0x00, 0x0c, 0x00, 0x0f, 0x00, 0x01, // control
+            0xfd, 0x00, 0x04, 0x00, 0xfd, 0x15, 0x03, 0xfd, 0x54, 0x00, 0x00, 0x01, // vector
+            0xfd, 0x5c, 0x02, 0x08, 0xfd, 0xff, 0x01, 0xfd, 0x0c,
+        ];
+        body.extend(0..16);
+        body.extend_from_slice(&[0xfd, 0x0d]);
+        body.extend(16..32);
+        body.push(0x0b);
+        let mut code = vec![0x02];
+        code.extend(with_len(None, &body));
+        code.extend(with_len(None, &[0x00, 0x0b]));
+
+        let sections = [
+            section(0, &[0x04, b'n', b'o', b't', b'e', 0xde, 0xad]),
+            section(
+                1,
+                &[0x02, 0x60, 0x02, 0x7f, 0x7e, 0x01, 0x7d, 0x60, 0x00, 0x00],
+            ),
+            section(
+                2,
+                &[
+                    0x04, // imports
+                    0x01, b'm', 0x01, b'f', 0x00, 0x01, // func
+                    0x01, b'm', 0x01, b't', 0x01, 0x70, 0x00, 0x01, // table
+                    0x01, b'm', 0x01, b'm', 0x02, 0x01, 0x01, 0x02, // memory
+                    0x01, b'm', 0x01, b'g', 0x03, 0x7f, 0x01, // global
+                ],
+            ),
+            section(3, &[0x02, 0x00, 0x01]),
+            section(4, &[0x01, 0x6f, 0x01, 0x00, 0x80, 0x01]),
+            section(5, &[0x01, 0x00, 0x01]),
+            section(6, &[0x01, 0x7e, 0x00, 0x42, 0x80, 0x7f, 0x0b]),
+            section(7, &[0x02, 0x01, b'f', 0x00, 0x01, 0x01, b'g', 0x03, 0x00]),
+            section(8, &[0x01]),
+            section(
+                9,
+                &[
+                    0x08, // elements
+                    0x00, 0x41, 0x00, 0x0b, 0x01, 0x00, // flag 0
+                    0x01, 0x00, 0x01, 0x01, // flag 1
+                    0x02, 0x01, 0x41, 0x00, 0x0b, 0x00, 0x01, 0x00, // flag 2
+                    0x03, 0x00, 0x01, 0x02, // flag 3
+                    0x04, 0x41, 0x00, 0x0b, 0x01, 0xd2, 0x00, 0x0b, // flag 4
+                    0x05, 0x6f, 0x01, 0xd0, 0x6f, 0x0b, // flag 5
+                    0x06, 0x01, 0x41, 0x00, 0x0b, 0x70, 0x01, 0xd2, 0x01, 0x0b, // flag 6
+                    0x07, 0x70, 0x00, // flag 7
+                ],
+            ),
+            section(12, &[0x03]),
+            section(10, &code),
+            section(
+                11,
+                &[
+                    0x03, // data
+                    0x00, 0x41, 0x00, 0x0b, 0x02, b'h', b'i', // flag 0
+                    0x01, 0x01, b'x', // flag 1
+                    0x02, 0x01, 0x41, 0x08, 0x0b, 0x00, // flag 2
+                ],
+            ),
+            section(0, &[0x00]),
+        ];
+        let mut input = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        sections.iter().for_each(|s| input.extend_from_slice(s));
+
+        let module = parse_module(&input).unwrap();
+        assert_eq!(module.encode(), input);
+
+        for (section, expected) in module.sections().iter().zip(&sections) {
+            let mut buf = Vec::new();
+            section.encode(&mut buf);
+            assert_eq!(&buf, expected);
+        }
+
+        // 関数本体を命令列から書き直しても同じになる
+        let cs = match module.sections()[11].payload_data() {
+            SectionData::Code(cs) => cs,
+            _ => panic!("expected code section"),
+        };
+        let mut buf = Vec::new();
+        cs.codes()[0].expression().unwrap().encode(&mut buf);
+        assert_eq!(buf, &body[5..]);
+    }
+
+    #[test]
+    fn test_encode_keeps_original_encoding() {
+        let header = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let module = |sections: &[&[u8]]| {
+            let mut v = header.to_vec();
+            sections.iter().for_each(|s| v.extend_from_slice(s));
+            v
+        };
+        let type_section: &[u8] = &[0x01, 0x04, 0x01, 0x60, 0x00, 0x00];
+        let function_section: &[u8] = &[0x03, 0x02, 0x01, 0x00];
+        let table_section: &[u8] = &[0x04, 0x04, 0x01, 0x70, 0x00, 0x01];
+        let memory_section: &[u8] = &[0x05, 0x03, 0x01, 0x00, 0x01];
+        let code_section: &[u8] = &[0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b];
+
+        // table 0 の funcref の segment を flag 2 と 6 で table index を明示して書いたもの
+        let input = module(&[
+            type_section,
+            function_section,
+            table_section,
+            &[
+                0x09, 0x13, 0x02, // element section
+                0x02, 0x00, 0x41, 0x00, 0x0b, 0x00, 0x01, 0x00, // flag 2
+                0x06, 0x00, 0x41, 0x00, 0x0b, 0x70, 0x01, 0xd2, 0x00, 0x0b, // flag 6
+            ],
+            code_section,
+        ]);
+        let parsed = parse_module(&input).unwrap();
+        match parsed.sections()[3].payload_data() {
+            SectionData::Element(section) => {
+                let flags: Vec<_> = section.elements().iter().map(|e| e.flag).collect();
+                assert_eq!(flags, [2, 6]);
+            }
+            _ => panic!("expected element section"),
+        }
+        assert!(parsed.sections()[3].original.is_none());
+        assert_eq!(parsed.encode(), input);
+
+        // memory 0 の segment を flag 2 で memory index を明示して書いたもの
+        let input = module(&[
+            memory_section,
+            &[
+                0x0b, 0x08, 0x01, // data section
+                0x02, 0x00, 0x41, 0x00, 0x0b, 0x01, b'a', // flag 2
+            ],
+        ]);
+        let parsed = parse_module(&input).unwrap();
+        assert!(parsed.sections()[1].original.is_none());
+        assert_eq!(parsed.encode(), input);
+
+        // 最短でない LEB128。section の長さ、要素数、type index、関数本体の長さ
+        let input = module(&[
+            &[0x01, 0x84, 0x80, 0x80, 0x80, 0x00, 0x01, 0x60, 0x00, 0x00],
+            &[0x03, 0x04, 0x81, 0x00, 0x80, 0x00],
+            &[0x0a, 0x05, 0x01, 0x82, 0x00, 0x00, 0x0b],
+        ]);
+        for parsed in [
+            parse_module(&input).unwrap(),
+            from_reader(input.as_slice()).unwrap(),
+        ] {
+            match parsed.sections()[1].payload_data() {
+                SectionData::Function(section) => assert_eq!(section.indexies(), [0]),
+                _ => panic!("expected function section"),
+            }
+            assert_eq!(parsed.encode(), input);
+        }
+    }
+
+    #[test]
+    fn test_encode_instruction_round_trip() {
+        let round_trip = |input: &[u8]| {
+            let exp = instruction::Expression::parse(&mut &input[..]).unwrap();
+            let mut buf = Vec::new();
+            exp.encode(&mut buf);
+            assert_eq!(buf, input);
+        };
+        for by in 0x45..=0xc4 {
+            round_trip(&[by, 0x0b]);
+        }
+        // 即値の後ろの 0x00 は unreachable として読まれる
+        for sub in 0..=255 {
+            let mut input = vec![0xfd];
+            crate::encode::encode_varint_u32(&mut input, sub);
+            input.extend_from_slice(&[0x00; 16]);
+            input.push(0x0b);
+            if instruction::Expression::parse(&mut &input[..]).is_ok() {
+                round_trip(&input);
+            }
+        }
+    }
 }
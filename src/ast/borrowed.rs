@@ -0,0 +1,382 @@
+// 入力のバイト列を借用する AST
+// 名前や custom section の中身、関数本体はコピーせず入力のスライスのまま持つ
+use super::{
+    instruction::InstructionReader,
+    name::NameSection,
+    parse::{
+        check_header, parse_bytes_ref, parse_name_ref, parse_payload, parse_vec, ParseError, Result,
+    },
+    section::{
+        self, DataCountSection, DataMode, ElementSection, ExportDesc, FunctionSection,
+        GlobalSection, ImportDesc, MemorySection, SectionOrder, StartSection, TableSection,
+        TypeSection,
+    },
+    wasm_type,
+};
+use crate::decode;
+
+#[derive(Debug)]
+pub struct Module<'a> {
+    pub(crate) magic_number: u32,
+    pub(crate) version: u32,
+    pub(crate) sections: Vec<Section<'a>>,
+}
+
+impl<'a> Module<'a> {
+    pub fn magic_number(&self) -> u32 {
+        self.magic_number
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn sections(&self) -> &[Section<'a>] {
+        &self.sections
+    }
+
+    /// 最初の "name" section をデコードする。なければ None
+    pub fn name_section(&self) -> Option<Result<NameSection>> {
+        self.sections
+            .iter()
+            .find_map(|section| match section.payload_data() {
+                SectionData::Custom(section) => section.name_section(),
+                _ => None,
+            })
+    }
+
+    pub(crate) fn parse(mut data: &'a [u8]) -> Result<Self> {
+        let len = data.len();
+        let data = &mut data;
+        let header = |data: &mut &'a [u8]| -> Result<_> {
+            let magic_number = u32::from_le_bytes(decode::decode_32bit(data)?);
+            let version = u32::from_le_bytes(decode::decode_32bit(data)?);
+            Ok((magic_number, version))
+        };
+        let (magic_number, version) = header(data).map_err(|e| e.located(len, data.len()))?;
+        check_header(magic_number, version)?;
+        let mut sections = Vec::new();
+        let mut order = SectionOrder::default();
+        let mut imported_funcs = 0;
+        while !data.is_empty() {
+            let start = len - data.len();
+            let section = Section::parse(data, len, imported_funcs)?;
+            order.check(section.id, start)?;
+            if let SectionData::Import(section) = &section.payload_data {
+                imported_funcs = section.func_count();
+            }
+            sections.push(section);
+        }
+        Ok(Self {
+            magic_number,
+            version,
+            sections,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Section<'a> {
+    pub(crate) id: u8,
+    pub(crate) payload: &'a [u8],
+    pub(crate) payload_data: SectionData<'a>,
+}
+
+impl<'a> Section<'a> {
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    pub fn payload_data(&self) -> &SectionData<'a> {
+        &self.payload_data
+    }
+
+    // len は入力全体の長さで、エラーの位置を出すのに使う
+    // imported_funcs はそれまでの import section で import した関数の数
+    fn parse(data: &mut &'a [u8], len: usize, imported_funcs: u32) -> Result<Self> {
+        let start = len - data.len();
+        let header = |data: &mut &'a [u8]| -> Result<_> {
+            let [id] = decode::decode_8bit(data)?;
+            section::check_id(id, start)?;
+            let payload_len = decode::decode_varint_u32(data)?;
+            Ok((id, payload_len))
+        };
+        let (id, payload_len) = header(data).map_err(|e| e.located(len, data.len()))?;
+        let offset = len - data.len();
+        let payload = decode::decode_slice(data, payload_len as usize)
+            .map_err(|e| ParseError::from(e).in_section(id).located(len, data.len()))?;
+        let payload_data = parse_payload(payload, id, offset, |data| {
+            SectionData::parse(data, id, offset, imported_funcs)
+        })?;
+        Ok(Self {
+            id,
+            payload,
+            payload_data,
+        })
+    }
+}
+
+// 借用する必要のない section は section モジュールのものをそのまま使う
+#[derive(Debug)]
+pub enum SectionData<'a> {
+    Custom(CustomSection<'a>),
+    Type(TypeSection),
+    Import(ImportSection<'a>),
+    Function(FunctionSection),
+    Table(TableSection),
+    Memory(MemorySection),
+    Global(GlobalSection),
+    Export(ExportSection<'a>),
+    Start(StartSection),
+    Element(ElementSection),
+    Code(CodeSection<'a>),
+    Data(DataSection<'a>),
+    DataCount(DataCountSection),
+}
+
+impl<'a> SectionData<'a> {
+    fn parse(data: &mut &'a [u8], id: u8, offset: usize, imported_funcs: u32) -> Result<Self> {
+        match id {
+            0 => Ok(Self::Custom(CustomSection::parse(data)?)),
+            1 => Ok(Self::Type(TypeSection::parse(data)?)),
+            2 => Ok(Self::Import(ImportSection::parse(data)?)),
+            3 => Ok(Self::Function(FunctionSection::parse(data)?)),
+            4 => Ok(Self::Table(TableSection::parse(data)?)),
+            5 => Ok(Self::Memory(MemorySection::parse(data)?)),
+            6 => Ok(Self::Global(GlobalSection::parse(data)?)),
+            7 => Ok(Self::Export(ExportSection::parse(data)?)),
+            8 => Ok(Self::Start(StartSection::parse(data)?)),
+            9 => Ok(Self::Element(ElementSection::parse(data)?)),
+            10 => Ok(Self::Code(CodeSection::parse(
+                data,
+                offset,
+                imported_funcs,
+            )?)),
+            11 => Ok(Self::Data(DataSection::parse(data)?)),
+            12 => Ok(Self::DataCount(DataCountSection::parse(data)?)),
+            _ => Err(ParseError::UnexpectedSectionId(id)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CustomSection<'a> {
+    pub(crate) name: &'a [u8],
+    pub(crate) payload: &'a [u8],
+}
+
+impl<'a> CustomSection<'a> {
+    pub fn name(&self) -> &'a [u8] {
+        self.name
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// "name" section であれば中身をデコードする
+    pub fn name_section(&self) -> Option<Result<NameSection>> {
+        (self.name == b"name").then(|| NameSection::parse(self.payload))
+    }
+
+    fn parse(data: &mut &'a [u8]) -> Result<Self> {
+        let name = parse_name_ref(data)?;
+        let payload = std::mem::take(data);
+        Ok(Self { name, payload })
+    }
+}
+
+#[derive(Debug)]
+pub struct ImportSection<'a> {
+    pub(crate) imports: Vec<Import<'a>>,
+}
+
+impl<'a> ImportSection<'a> {
+    pub fn imports(&self) -> &[Import<'a>] {
+        &self.imports
+    }
+
+    // import した関数の数。code section の関数の番号はこの後から数える
+    pub(crate) fn func_count(&self) -> u32 {
+        section::count_funcs(self.imports.iter().map(|i| &i.desc))
+    }
+
+    fn parse(data: &mut &'a [u8]) -> Result<Self> {
+        let v = parse_vec(data, Import::parse)?;
+        Ok(Self { imports: v })
+    }
+}
+
+#[derive(Debug)]
+pub struct Import<'a> {
+    pub(crate) module: &'a [u8],
+    pub(crate) name: &'a [u8],
+    pub(crate) desc: ImportDesc,
+}
+
+impl<'a> Import<'a> {
+    pub fn module(&self) -> &'a [u8] {
+        self.module
+    }
+
+    pub fn name(&self) -> &'a [u8] {
+        self.name
+    }
+
+    pub fn desc(&self) -> &ImportDesc {
+        &self.desc
+    }
+
+    fn parse(data: &mut &'a [u8]) -> Result<Self> {
+        let module = parse_name_ref(data)?;
+        let name = parse_name_ref(data)?;
+        let desc = ImportDesc::parse(data)?;
+        Ok(Self { module, name, desc })
+    }
+}
+
+#[derive(Debug)]
+pub struct ExportSection<'a> {
+    pub(crate) exports: Vec<Export<'a>>,
+}
+
+impl<'a> ExportSection<'a> {
+    pub fn exports(&self) -> &[Export<'a>] {
+        &self.exports
+    }
+
+    fn parse(data: &mut &'a [u8]) -> Result<Self> {
+        let v = parse_vec(data, Export::parse)?;
+        Ok(Self { exports: v })
+    }
+}
+
+#[derive(Debug)]
+pub struct Export<'a> {
+    pub(crate) name: &'a [u8],
+    pub(crate) desc: ExportDesc,
+}
+
+impl<'a> Export<'a> {
+    pub fn name(&self) -> &'a [u8] {
+        self.name
+    }
+
+    pub fn desc(&self) -> &ExportDesc {
+        &self.desc
+    }
+
+    fn parse(data: &mut &'a [u8]) -> Result<Self> {
+        let name = parse_name_ref(data)?;
+        let desc = ExportDesc::parse(data)?;
+        Ok(Self { name, desc })
+    }
+}
+
+#[derive(Debug)]
+pub struct CodeSection<'a> {
+    pub(crate) codes: Vec<Code<'a>>,
+}
+
+impl<'a> CodeSection<'a> {
+    pub fn codes(&self) -> &[Code<'a>] {
+        &self.codes
+    }
+
+    // offset は payload の先頭の絶対位置
+    fn parse(data: &mut &'a [u8], offset: usize, imported_funcs: u32) -> Result<Self> {
+        let payload_len = data.len();
+        let mut v = parse_vec(data, |data| {
+            let len = decode::decode_varint_u32(data)?;
+            let body_offset = offset + payload_len - data.len();
+            let body = decode::decode_slice(data, len as usize)?;
+            Ok(Code {
+                body,
+                offset: body_offset,
+                index: 0,
+            })
+        })?;
+        for (i, c) in v.iter_mut().enumerate() {
+            c.index = imported_funcs + i as u32;
+        }
+        Ok(Self { codes: v })
+    }
+}
+
+// 関数本体は必要になるまでデコードしない
+#[derive(Debug)]
+pub struct Code<'a> {
+    pub(crate) body: &'a [u8],
+    // エラーの位置を出すための、関数本体の先頭の絶対位置と import した関数も数えた関数の番号
+    pub(crate) offset: usize,
+    pub(crate) index: u32,
+}
+
+impl<'a> Code<'a> {
+    /// locals の宣言を含む関数本体のバイト列
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+
+    pub fn locals(&self) -> Result<Vec<wasm_type::ValueType>> {
+        self.parse_locals(&mut { self.body })
+    }
+
+    /// locals の宣言を読み飛ばし、命令を先頭から 1 つずつデコードする
+    pub fn instructions(&self) -> Result<InstructionReader<'a>> {
+        let mut data = self.body;
+        self.parse_locals(&mut data)?;
+        let end = self.offset + self.body.len();
+        Ok(InstructionReader::new(data, end, self.index))
+    }
+
+    fn parse_locals(&self, data: &mut &'a [u8]) -> Result<Vec<wasm_type::ValueType>> {
+        section::parse_locals(data).map_err(|e| {
+            e.in_function(self.index)
+                .in_section(10)
+                .located(self.offset + self.body.len(), data.len())
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DataSection<'a> {
+    pub(crate) data: Vec<Data<'a>>,
+}
+
+impl<'a> DataSection<'a> {
+    pub fn data(&self) -> &[Data<'a>] {
+        &self.data
+    }
+
+    fn parse(data: &mut &'a [u8]) -> Result<Self> {
+        let v = parse_vec(data, Data::parse)?;
+        Ok(Self { data: v })
+    }
+}
+
+#[derive(Debug)]
+pub struct Data<'a> {
+    pub(crate) init: &'a [u8],
+    pub(crate) mode: DataMode,
+}
+
+impl<'a> Data<'a> {
+    pub fn init(&self) -> &'a [u8] {
+        self.init
+    }
+
+    pub fn mode(&self) -> &DataMode {
+        &self.mode
+    }
+
+    fn parse(data: &mut &'a [u8]) -> Result<Self> {
+        let mode = DataMode::parse(data)?;
+        let init = parse_bytes_ref(data)?;
+        Ok(Self { init, mode })
+    }
+}
@@ -1,83 +1,557 @@
-use crate::decode;
+use crate::{decode, encode};
 
 use super::{
     parse::{parse_vec, ParseError, Result},
-    wasm_type::{self, FunctionType},
+    wasm_type,
 };
-use std::convert::TryFrom;
+#[derive(Debug)]
 pub struct Expression {
-    pub(super) instrs: Vec<Instruction>,
+    pub(crate) instrs: Vec<Instruction>,
 }
 
 impl Expression {
+    pub fn instrs(&self) -> &[Instruction] {
+        &self.instrs
+    }
+
     pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
-        let mut v = Vec::new();
-        loop {
-            let by = u8::try_from(decode::decode_varint(data)?)?;
-            match by {
-                0x0b => break Ok(Self { instrs: v }),
-                by => match Instruction::parse(data, by) {
-                    Ok(instr) => v.push(instr),
-                    Err(e) => break Err(e),
-                },
+        let instrs = parse_instrs(data)?;
+        Ok(Self { instrs })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode_instrs(buf, &self.instrs);
+        buf.push(0x0b);
+    }
+}
+
+// block/loop/if の入れ子を読んでいる途中の状態
+struct BlockFrame {
+    opcode: u8,
+    block_type: BlockType,
+    outer: Vec<Instruction>,
+    then_instrs: Option<Vec<Instruction>>,
+}
+
+// 読んでいる命令の通し番号と、その命令の先頭で残っていたバイト数
+// エラーに命令の位置を付けるために使う
+#[derive(Default, Debug)]
+struct InstrPosition {
+    index: u32,
+    count: u32,
+    remaining: usize,
+}
+
+impl InstrPosition {
+    fn read_opcode(&mut self, data: &mut &[u8]) -> Result<u8> {
+        self.index = self.count;
+        self.count += 1;
+        self.remaining = data.len();
+        Ok(decode::decode_8bit(data)?[0])
+    }
+}
+
+// 対応する end (0x0b) までの命令列を読む
+fn parse_instrs(data: &mut &[u8]) -> Result<Vec<Instruction>> {
+    let mut pos = InstrPosition::default();
+    parse_instrs_at(data, &mut pos).map_err(|e| e.in_instruction(pos.index, pos.remaining))
+}
+
+fn parse_instrs_at(data: &mut &[u8], pos: &mut InstrPosition) -> Result<Vec<Instruction>> {
+    let mut v = Vec::new();
+    loop {
+        match pos.read_opcode(data)? {
+            0x0b => break Ok(v),
+            by => v.push(parse_instr(data, by, pos)?),
+        }
+    }
+}
+
+// 命令を 1 つ読む。block/loop/if の場合は対応する end までを読む
+// 入れ子が深いモジュールでもスタックを使い果たさないよう、再帰せずに自前のスタックで読む
+fn parse_instr(data: &mut &[u8], by: u8, pos: &mut InstrPosition) -> Result<Instruction> {
+    if !(0x02..=0x05).contains(&by) {
+        return Instruction::parse(data, by);
+    }
+    let mut stack: Vec<BlockFrame> = Vec::new();
+    let mut current = Vec::new();
+    let mut by = by;
+    loop {
+        match by {
+            0x02..=0x04 => {
+                let block_type = BlockType::parse(data)?;
+                stack.push(BlockFrame {
+                    opcode: by,
+                    block_type,
+                    outer: std::mem::take(&mut current),
+                    then_instrs: None,
+                });
             }
+            0x05 => match stack.last_mut() {
+                Some(frame) if frame.opcode == 0x04 && frame.then_instrs.is_none() => {
+                    frame.then_instrs = Some(std::mem::take(&mut current));
+                }
+                _ => {
+                    return Err(ParseError::UnexpectedValue(
+                        "else must be placed in if block".to_string(),
+                    ))
+                }
+            },
+            0x0b => {
+                // stack が空の状態で end は来ない (最初の命令は block/loop/if)
+                let frame = stack.pop().unwrap();
+                let instrs = std::mem::replace(&mut current, frame.outer);
+                let block_type = frame.block_type;
+                let instr = match (frame.opcode, frame.then_instrs) {
+                    (0x02, _) => ControlInstruction::Block { block_type, instrs },
+                    (0x03, _) => ControlInstruction::Loop { block_type, instrs },
+                    (_, None) => ControlInstruction::IfElse {
+                        block_type,
+                        then_instrs: instrs,
+                        else_instrs: None,
+                    },
+                    (_, Some(then_instrs)) => ControlInstruction::IfElse {
+                        block_type,
+                        then_instrs,
+                        else_instrs: Some(instrs),
+                    },
+                };
+                if stack.is_empty() {
+                    break Ok(Instruction::Control(instr));
+                }
+                current.push(Instruction::Control(instr));
+            }
+            by => current.push(Instruction::parse(data, by)?),
         }
+        by = pos.read_opcode(data)?;
     }
 }
 
+// 命令列を書き込む。block/loop/if の場合は対応する end までを書く
+// parse_instr と同じく、入れ子が深くてもスタックを使い果たさないよう自前のスタックで書く
+fn encode_instrs(buf: &mut Vec<u8>, instrs: &[Instruction]) {
+    enum Pending<'a> {
+        Instrs(&'a [Instruction]),
+        Byte(u8),
+    }
+    let mut stack = vec![Pending::Instrs(instrs)];
+    while let Some(pending) = stack.pop() {
+        let instrs = match pending {
+            Pending::Instrs(instrs) => instrs,
+            Pending::Byte(by) => {
+                buf.push(by);
+                continue;
+            }
+        };
+        let (instr, rest) = match instrs.split_first() {
+            Some(x) => x,
+            None => continue,
+        };
+        stack.push(Pending::Instrs(rest));
+        match instr {
+            Instruction::Control(ControlInstruction::Block { block_type, instrs }) => {
+                buf.push(0x02);
+                block_type.encode(buf);
+                stack.push(Pending::Byte(0x0b));
+                stack.push(Pending::Instrs(instrs));
+            }
+            Instruction::Control(ControlInstruction::Loop { block_type, instrs }) => {
+                buf.push(0x03);
+                block_type.encode(buf);
+                stack.push(Pending::Byte(0x0b));
+                stack.push(Pending::Instrs(instrs));
+            }
+            Instruction::Control(ControlInstruction::IfElse {
+                block_type,
+                then_instrs,
+                else_instrs,
+            }) => {
+                buf.push(0x04);
+                block_type.encode(buf);
+                stack.push(Pending::Byte(0x0b));
+                if let Some(else_instrs) = else_instrs {
+                    stack.push(Pending::Instrs(else_instrs));
+                    stack.push(Pending::Byte(0x05));
+                }
+                stack.push(Pending::Instrs(then_instrs));
+            }
+            instr => instr.encode_plain(buf),
+        }
+    }
+}
+
+/// 関数本体の命令を先頭から 1 つずつ読み出す
+#[derive(Debug)]
+pub struct InstructionReader<'a> {
+    data: &'a [u8],
+    pos: InstrPosition,
+    // エラーの位置を出すための、data の終わりの絶対位置と関数の番号
+    end: usize,
+    func_index: u32,
+    finished: bool,
+}
+
+impl<'a> InstructionReader<'a> {
+    pub(super) fn new(data: &'a [u8], end: usize, func_index: u32) -> Self {
+        Self {
+            data,
+            pos: InstrPosition::default(),
+            end,
+            func_index,
+            finished: false,
+        }
+    }
+}
+
+impl<'a> Iterator for InstructionReader<'a> {
+    type Item = Result<Instruction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let result = match self.pos.read_opcode(&mut self.data) {
+            Ok(0x0b) => {
+                self.finished = true;
+                if self.data.is_empty() {
+                    return None;
+                }
+                Err(ParseError::UnexpectedValue(format!(
+                    "{} bytes remain after end of expression",
+                    self.data.len()
+                )))
+            }
+            Ok(by) => parse_instr(&mut self.data, by, &mut self.pos),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            self.finished = true;
+        }
+        Some(result.map_err(|e| {
+            e.in_instruction(self.pos.index, self.pos.remaining)
+                .in_function(self.func_index)
+                .in_section(10)
+                .located(self.end, self.data.len())
+        }))
+    }
+}
+
+#[derive(Debug)]
 pub enum Instruction {
     Control(ControlInstruction),
     Numeric(NumericInstruction),
+    Reference(ReferenceInstruction),
+    Parametric(ParametricInstruction),
     Variable(VariableInstruction),
+    Table(TableInstruction),
+    Memory(MemoryInstruction),
+    Vector(VectorInstruction),
 }
 
 impl Instruction {
     fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
         match by {
-            0x00..=0x11 => Ok(Self::Control(ControlInstruction::Nop)),
+            0x00..=0x11 => Ok(Self::Control(ControlInstruction::parse(data, by)?)),
+            0x1A..=0x1C => Ok(Self::Parametric(ParametricInstruction::parse(data, by)?)),
             0x20..=0x24 => Ok(Self::Variable(VariableInstruction::parse(data, by)?)),
+            0x25 | 0x26 => Ok(Self::Table(TableInstruction::parse(data, by)?)),
+            0x28..=0x40 => Ok(Self::Memory(MemoryInstruction::parse(data, by)?)),
             0x41..=0xC4 => Ok(Self::Numeric(NumericInstruction::parse(data, by)?)),
+            0xD0..=0xD2 => Ok(Self::Reference(ReferenceInstruction::parse(data, by)?)),
+            0xFC => {
+                let sub = decode::decode_varint_u32(data)?;
+                match sub {
+                    0..=7 => Ok(Self::Numeric(NumericInstruction::SaturatingTruncation(
+                        SaturatingTruncationInstruction::parse(sub)?,
+                    ))),
+                    8..=11 => Ok(Self::Memory(MemoryInstruction::parse_prefixed(data, sub)?)),
+                    12..=17 => Ok(Self::Table(TableInstruction::parse_prefixed(data, sub)?)),
+                    _ => Err(ParseError::UnexpectedValue(format!(
+                        "unexpected sub opcode after 0xFC. got={}",
+                        sub
+                    ))),
+                }
+            }
+            0xFD => {
+                let sub = decode::decode_varint_u32(data)?;
+                Ok(Self::Vector(VectorInstruction::parse(data, sub)?))
+            }
             _ => Err(ParseError::UnexpectedByteValue {
                 title: "Instruction".to_string(),
                 got: by,
             }),
         }
     }
+
+    /// 命令を書き込む。block/loop/if の場合は対応する end までを書く
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode_instrs(buf, std::slice::from_ref(self));
+    }
+
+    // block/loop/if 以外の命令を書く
+    fn encode_plain(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Control(instr) => instr.encode_plain(buf),
+            Self::Numeric(instr) => instr.encode(buf),
+            Self::Reference(instr) => instr.encode(buf),
+            Self::Parametric(instr) => instr.encode(buf),
+            Self::Variable(instr) => instr.encode(buf),
+            Self::Table(instr) => instr.encode(buf),
+            Self::Memory(instr) => instr.encode(buf),
+            Self::Vector(instr) => instr.encode(buf),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BlockType {
+    Empty,
+    Value(wasm_type::ValueType),
+    TypeIndex(u32),
+}
+
+impl BlockType {
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let by = *data.first().ok_or(decode::DecodeError::UnexpectedEof)?;
+        if by == 0x40 {
+            *data = &data[1..];
+            return Ok(Self::Empty);
+        }
+        if let Some(value_type) = wasm_type::ValueType::new(by) {
+            *data = &data[1..];
+            return Ok(Self::Value(value_type));
+        }
+        // 型インデックスは s33 でエンコードされ、負の値は許されない
+        let index = decode::decode_varint_s33(data)?;
+        if index < 0 {
+            return Err(ParseError::UnexpectedValue(format!(
+                "block type index must not be negative. got={}",
+                index
+            )));
+        }
+        Ok(Self::TypeIndex(index as u32))
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Empty => buf.push(0x40),
+            Self::Value(value_type) => value_type.encode(buf),
+            Self::TypeIndex(index) => encode::encode_varint_s33(buf, *index as i64),
+        }
+    }
 }
 
+#[derive(Debug)]
 pub enum ControlInstruction {
     Unreachable,
     Nop,
-    Block,
-    Loop,
-    IfElse,
-    Br,
-    BrIf,
-    BrTable,
+    Block {
+        block_type: BlockType,
+        instrs: Vec<Instruction>,
+    },
+    Loop {
+        block_type: BlockType,
+        instrs: Vec<Instruction>,
+    },
+    IfElse {
+        block_type: BlockType,
+        then_instrs: Vec<Instruction>,
+        else_instrs: Option<Vec<Instruction>>,
+    },
+    Br(u32),
+    BrIf(u32),
+    BrTable {
+        labels: Vec<u32>,
+        default: u32,
+    },
     Return,
-    Call,
-    CallIndirect,
+    Call(u32),
+    CallIndirect {
+        type_index: u32,
+        table_index: u32,
+    },
+}
+
+impl ControlInstruction {
+    // block/loop/if は入れ子の命令列を持つので parse_instrs で読む
+    fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
+        match by {
+            0x00 => Ok(Self::Unreachable),
+            0x01 => Ok(Self::Nop),
+            0x0C => Ok(Self::Br(decode::decode_varint_u32(data)?)),
+            0x0D => Ok(Self::BrIf(decode::decode_varint_u32(data)?)),
+            0x0E => {
+                let labels = parse_vec(data, |data| Ok(decode::decode_varint_u32(data)?))?;
+                let default = decode::decode_varint_u32(data)?;
+                Ok(Self::BrTable { labels, default })
+            }
+            0x0F => Ok(Self::Return),
+            0x10 => Ok(Self::Call(decode::decode_varint_u32(data)?)),
+            0x11 => {
+                let type_index = decode::decode_varint_u32(data)?;
+                let table_index = decode::decode_varint_u32(data)?;
+                Ok(Self::CallIndirect {
+                    type_index,
+                    table_index,
+                })
+            }
+            _ => Err(ParseError::UnexpectedByteValue {
+                title: "ControlInstruction".to_string(),
+                got: by,
+            }),
+        }
+    }
+
+    // block/loop/if は encode_instrs で書く
+    fn encode_plain(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Unreachable => buf.push(0x00),
+            Self::Nop => buf.push(0x01),
+            Self::Block { .. } | Self::Loop { .. } | Self::IfElse { .. } => {
+                unreachable!("block instructions are encoded by encode_instrs")
+            }
+            Self::Br(label) => {
+                buf.push(0x0C);
+                encode::encode_varint_u32(buf, *label);
+            }
+            Self::BrIf(label) => {
+                buf.push(0x0D);
+                encode::encode_varint_u32(buf, *label);
+            }
+            Self::BrTable { labels, default } => {
+                buf.push(0x0E);
+                encode::encode_vec(buf, labels, |buf, l| encode::encode_varint_u32(buf, *l));
+                encode::encode_varint_u32(buf, *default);
+            }
+            Self::Return => buf.push(0x0F),
+            Self::Call(index) => {
+                buf.push(0x10);
+                encode::encode_varint_u32(buf, *index);
+            }
+            Self::CallIndirect {
+                type_index,
+                table_index,
+            } => {
+                buf.push(0x11);
+                encode::encode_varint_u32(buf, *type_index);
+                encode::encode_varint_u32(buf, *table_index);
+            }
+        }
+    }
+}
+
+// 自動で生成される drop は入れ子の深さだけ再帰するので、
+// 入れ子の命令列を取り出して自前のスタックで解放する
+impl Drop for ControlInstruction {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_bodies(&mut pending);
+        while let Some(mut instrs) = pending.pop() {
+            for instr in &mut instrs {
+                if let Instruction::Control(control) = instr {
+                    control.take_bodies(&mut pending);
+                }
+            }
+        }
+    }
 }
 
 impl ControlInstruction {
-    fn new(by: u8) -> Option<Self> {
+    fn take_bodies(&mut self, pending: &mut Vec<Vec<Instruction>>) {
+        match self {
+            Self::Block { instrs, .. } | Self::Loop { instrs, .. } => {
+                pending.push(std::mem::take(instrs));
+            }
+            Self::IfElse {
+                then_instrs,
+                else_instrs,
+                ..
+            } => {
+                pending.push(std::mem::take(then_instrs));
+                pending.extend(else_instrs.take());
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReferenceInstruction {
+    RefNull(wasm_type::ReferenceType),
+    RefIsNull,
+    RefFunc(u32),
+}
+impl ReferenceInstruction {
+    fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
+        match by {
+            0xD0 => {
+                let [t] = decode::decode_8bit(data)?;
+                let ref_type = wasm_type::ReferenceType::new(t).ok_or_else(|| {
+                    ParseError::UnexpectedByteValue {
+                        title: "reference type".to_string(),
+                        got: t,
+                    }
+                })?;
+                Ok(Self::RefNull(ref_type))
+            }
+            0xD1 => Ok(Self::RefIsNull),
+            0xD2 => Ok(Self::RefFunc(decode::decode_varint_u32(data)?)),
+            _ => Err(ParseError::UnexpectedByteValue {
+                title: "ReferenceInstruction".to_string(),
+                got: by,
+            }),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::RefNull(ref_type) => {
+                buf.push(0xD0);
+                ref_type.encode(buf);
+            }
+            Self::RefIsNull => buf.push(0xD1),
+            Self::RefFunc(index) => {
+                buf.push(0xD2);
+                encode::encode_varint_u32(buf, *index);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParametricInstruction {
+    Drop,
+    Select,
+    SelectTyped(Vec<wasm_type::ValueType>),
+}
+impl ParametricInstruction {
+    fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
         match by {
-            0x00 => Some(ControlInstruction::Unreachable),
-            0x01 => Some(ControlInstruction::Nop),
-            0x02 => Some(ControlInstruction::Block),
-            0x03 => Some(ControlInstruction::Loop),
-            0x04 => Some(ControlInstruction::IfElse),
-            0x0C => Some(ControlInstruction::Br),
-            0x0D => Some(ControlInstruction::BrIf),
-            0x0E => Some(ControlInstruction::BrTable),
-            0x0F => Some(ControlInstruction::Return),
-            0x10 => Some(ControlInstruction::Call),
-            0x11 => Some(ControlInstruction::CallIndirect),
-            _ => None,
+            0x1A => Ok(Self::Drop),
+            0x1B => Ok(Self::Select),
+            0x1C => Ok(Self::SelectTyped(parse_vec(data, |data| {
+                wasm_type::ValueType::parse(data)
+            })?)),
+            _ => Err(ParseError::UnexpectedByteValue {
+                title: "ParametricInstruction".to_string(),
+                got: by,
+            }),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Drop => buf.push(0x1A),
+            Self::Select => buf.push(0x1B),
+            Self::SelectTyped(types) => {
+                buf.push(0x1C);
+                encode::encode_vec(buf, types, |buf, t| t.encode(buf));
+            }
         }
     }
 }
 
+#[derive(Debug)]
 pub enum VariableInstruction {
     LocalGet(u32),
     LocalSet(u32),
@@ -87,7 +561,7 @@ pub enum VariableInstruction {
 }
 impl VariableInstruction {
     fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
-        let index = u32::try_from(decode::decode_varint(data)?)?;
+        let index = decode::decode_varint_u32(data)?;
         match by {
             0x20 => Ok(Self::LocalGet(index)),
             0x21 => Ok(Self::LocalSet(index)),
@@ -100,12 +574,1104 @@ impl VariableInstruction {
             }),
         }
     }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let (by, index) = match self {
+            Self::LocalGet(index) => (0x20, index),
+            Self::LocalSet(index) => (0x21, index),
+            Self::LocalTee(index) => (0x22, index),
+            Self::GlobalGet(index) => (0x23, index),
+            Self::GlobalSet(index) => (0x24, index),
+        };
+        buf.push(by);
+        encode::encode_varint_u32(buf, *index);
+    }
+}
+
+#[derive(Debug)]
+pub struct MemArg {
+    pub(crate) align: u32,
+    pub(crate) offset: u32,
+}
+
+impl MemArg {
+    pub fn align(&self) -> u32 {
+        self.align
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let align = decode::decode_varint_u32(data)?;
+        let offset = decode::decode_varint_u32(data)?;
+        Ok(Self { align, offset })
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_varint_u32(buf, self.align);
+        encode::encode_varint_u32(buf, self.offset);
+    }
+}
+
+#[derive(Debug)]
+pub enum MemoryInstruction {
+    LoadI32(MemArg),
+    LoadI64(MemArg),
+    LoadF32(MemArg),
+    LoadF64(MemArg),
+    Load8SI32(MemArg),
+    Load8UI32(MemArg),
+    Load16SI32(MemArg),
+    Load16UI32(MemArg),
+    Load8SI64(MemArg),
+    Load8UI64(MemArg),
+    Load16SI64(MemArg),
+    Load16UI64(MemArg),
+    Load32SI64(MemArg),
+    Load32UI64(MemArg),
+    StoreI32(MemArg),
+    StoreI64(MemArg),
+    StoreF32(MemArg),
+    StoreF64(MemArg),
+    Store8I32(MemArg),
+    Store16I32(MemArg),
+    Store8I64(MemArg),
+    Store16I64(MemArg),
+    Store32I64(MemArg),
+    Size,
+    Grow,
+    Init(u32),
+    DataDrop(u32),
+    Copy,
+    Fill,
 }
 
+impl MemoryInstruction {
+    fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
+        match by {
+            0x28 => Ok(Self::LoadI32(MemArg::parse(data)?)),
+            0x29 => Ok(Self::LoadI64(MemArg::parse(data)?)),
+            0x2A => Ok(Self::LoadF32(MemArg::parse(data)?)),
+            0x2B => Ok(Self::LoadF64(MemArg::parse(data)?)),
+            0x2C => Ok(Self::Load8SI32(MemArg::parse(data)?)),
+            0x2D => Ok(Self::Load8UI32(MemArg::parse(data)?)),
+            0x2E => Ok(Self::Load16SI32(MemArg::parse(data)?)),
+            0x2F => Ok(Self::Load16UI32(MemArg::parse(data)?)),
+            0x30 => Ok(Self::Load8SI64(MemArg::parse(data)?)),
+            0x31 => Ok(Self::Load8UI64(MemArg::parse(data)?)),
+            0x32 => Ok(Self::Load16SI64(MemArg::parse(data)?)),
+            0x33 => Ok(Self::Load16UI64(MemArg::parse(data)?)),
+            0x34 => Ok(Self::Load32SI64(MemArg::parse(data)?)),
+            0x35 => Ok(Self::Load32UI64(MemArg::parse(data)?)),
+            0x36 => Ok(Self::StoreI32(MemArg::parse(data)?)),
+            0x37 => Ok(Self::StoreI64(MemArg::parse(data)?)),
+            0x38 => Ok(Self::StoreF32(MemArg::parse(data)?)),
+            0x39 => Ok(Self::StoreF64(MemArg::parse(data)?)),
+            0x3A => Ok(Self::Store8I32(MemArg::parse(data)?)),
+            0x3B => Ok(Self::Store16I32(MemArg::parse(data)?)),
+            0x3C => Ok(Self::Store8I64(MemArg::parse(data)?)),
+            0x3D => Ok(Self::Store16I64(MemArg::parse(data)?)),
+            0x3E => Ok(Self::Store32I64(MemArg::parse(data)?)),
+            0x3F => {
+                parse_memory_index(data)?;
+                Ok(Self::Size)
+            }
+            0x40 => {
+                parse_memory_index(data)?;
+                Ok(Self::Grow)
+            }
+            _ => Err(ParseError::UnexpectedByteValue {
+                title: "MemoryInstruction".to_string(),
+                got: by,
+            }),
+        }
+    }
+
+    // 0xFC prefix の命令
+    fn parse_prefixed(data: &mut &[u8], sub: u32) -> Result<Self> {
+        match sub {
+            8 => {
+                let data_index = decode::decode_varint_u32(data)?;
+                parse_memory_index(data)?;
+                Ok(Self::Init(data_index))
+            }
+            9 => Ok(Self::DataDrop(decode::decode_varint_u32(data)?)),
+            10 => {
+                parse_memory_index(data)?;
+                parse_memory_index(data)?;
+                Ok(Self::Copy)
+            }
+            11 => {
+                parse_memory_index(data)?;
+                Ok(Self::Fill)
+            }
+            _ => Err(ParseError::UnexpectedValue(format!(
+                "unexpected sub opcode in MemoryInstruction. got={}",
+                sub
+            ))),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let (by, memarg) = match self {
+            Self::LoadI32(memarg) => (0x28, memarg),
+            Self::LoadI64(memarg) => (0x29, memarg),
+            Self::LoadF32(memarg) => (0x2A, memarg),
+            Self::LoadF64(memarg) => (0x2B, memarg),
+            Self::Load8SI32(memarg) => (0x2C, memarg),
+            Self::Load8UI32(memarg) => (0x2D, memarg),
+            Self::Load16SI32(memarg) => (0x2E, memarg),
+            Self::Load16UI32(memarg) => (0x2F, memarg),
+            Self::Load8SI64(memarg) => (0x30, memarg),
+            Self::Load8UI64(memarg) => (0x31, memarg),
+            Self::Load16SI64(memarg) => (0x32, memarg),
+            Self::Load16UI64(memarg) => (0x33, memarg),
+            Self::Load32SI64(memarg) => (0x34, memarg),
+            Self::Load32UI64(memarg) => (0x35, memarg),
+            Self::StoreI32(memarg) => (0x36, memarg),
+            Self::StoreI64(memarg) => (0x37, memarg),
+            Self::StoreF32(memarg) => (0x38, memarg),
+            Self::StoreF64(memarg) => (0x39, memarg),
+            Self::Store8I32(memarg) => (0x3A, memarg),
+            Self::Store16I32(memarg) => (0x3B, memarg),
+            Self::Store8I64(memarg) => (0x3C, memarg),
+            Self::Store16I64(memarg) => (0x3D, memarg),
+            Self::Store32I64(memarg) => (0x3E, memarg),
+            // memory index は予約バイトの 0x00 を書く
+            Self::Size => return buf.extend_from_slice(&[0x3F, 0x00]),
+            Self::Grow => return buf.extend_from_slice(&[0x40, 0x00]),
+            Self::Init(data_index) => {
+                buf.extend_from_slice(&[0xFC, 8]);
+                encode::encode_varint_u32(buf, *data_index);
+                return buf.push(0x00);
+            }
+            Self::DataDrop(data_index) => {
+                buf.extend_from_slice(&[0xFC, 9]);
+                return encode::encode_varint_u32(buf, *data_index);
+            }
+            Self::Copy => return buf.extend_from_slice(&[0xFC, 10, 0x00, 0x00]),
+            Self::Fill => return buf.extend_from_slice(&[0xFC, 11, 0x00]),
+        };
+        buf.push(by);
+        memarg.encode(buf);
+    }
+}
+
+// 現状 memory index は 0 のみなので予約バイトとして扱う
+fn parse_memory_index(data: &mut &[u8]) -> Result<()> {
+    let [reserved] = decode::decode_8bit(data)?;
+    if reserved != 0x00 {
+        return Err(ParseError::UnexpectedByteValue {
+            title: "memory index".to_string(),
+            got: reserved,
+        });
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum TableInstruction {
+    Get(u32),
+    Set(u32),
+    Init { elem_index: u32, table_index: u32 },
+    ElemDrop(u32),
+    Copy { dst: u32, src: u32 },
+    Grow(u32),
+    Size(u32),
+    Fill(u32),
+}
+
+impl TableInstruction {
+    fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
+        match by {
+            0x25 => Ok(Self::Get(decode::decode_varint_u32(data)?)),
+            0x26 => Ok(Self::Set(decode::decode_varint_u32(data)?)),
+            _ => Err(ParseError::UnexpectedByteValue {
+                title: "TableInstruction".to_string(),
+                got: by,
+            }),
+        }
+    }
+
+    // 0xFC prefix の命令
+    fn parse_prefixed(data: &mut &[u8], sub: u32) -> Result<Self> {
+        match sub {
+            12 => {
+                let elem_index = decode::decode_varint_u32(data)?;
+                let table_index = decode::decode_varint_u32(data)?;
+                Ok(Self::Init {
+                    elem_index,
+                    table_index,
+                })
+            }
+            13 => Ok(Self::ElemDrop(decode::decode_varint_u32(data)?)),
+            14 => {
+                let dst = decode::decode_varint_u32(data)?;
+                let src = decode::decode_varint_u32(data)?;
+                Ok(Self::Copy { dst, src })
+            }
+            15 => Ok(Self::Grow(decode::decode_varint_u32(data)?)),
+            16 => Ok(Self::Size(decode::decode_varint_u32(data)?)),
+            17 => Ok(Self::Fill(decode::decode_varint_u32(data)?)),
+            _ => Err(ParseError::UnexpectedValue(format!(
+                "unexpected sub opcode in TableInstruction. got={}",
+                sub
+            ))),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let index = match self {
+            Self::Get(index) => {
+                buf.push(0x25);
+                index
+            }
+            Self::Set(index) => {
+                buf.push(0x26);
+                index
+            }
+            Self::Init {
+                elem_index,
+                table_index,
+            } => {
+                buf.extend_from_slice(&[0xFC, 12]);
+                encode::encode_varint_u32(buf, *elem_index);
+                table_index
+            }
+            Self::ElemDrop(elem_index) => {
+                buf.extend_from_slice(&[0xFC, 13]);
+                elem_index
+            }
+            Self::Copy { dst, src } => {
+                buf.extend_from_slice(&[0xFC, 14]);
+                encode::encode_varint_u32(buf, *dst);
+                src
+            }
+            Self::Grow(index) => {
+                buf.extend_from_slice(&[0xFC, 15]);
+                index
+            }
+            Self::Size(index) => {
+                buf.extend_from_slice(&[0xFC, 16]);
+                index
+            }
+            Self::Fill(index) => {
+                buf.extend_from_slice(&[0xFC, 17]);
+                index
+            }
+        };
+        encode::encode_varint_u32(buf, *index);
+    }
+}
+
+// 0xFD prefix の SIMD 命令
+// 命名は PlainNumericInstruction と同じく 演算 + 結果の shape + 入力の shape の順
+// lane index を持つ命令は u8 で lane index を保持する
+// 検証は validator、実行は evaluator::vector で行う
+#[derive(Debug)]
+pub enum VectorInstruction {
+    Load(MemArg),
+    Load8x8S(MemArg),
+    Load8x8U(MemArg),
+    Load16x4S(MemArg),
+    Load16x4U(MemArg),
+    Load32x2S(MemArg),
+    Load32x2U(MemArg),
+    Load8Splat(MemArg),
+    Load16Splat(MemArg),
+    Load32Splat(MemArg),
+    Load64Splat(MemArg),
+    Store(MemArg),
+    Const([u8; 16]),
+    ShuffleI8x16([u8; 16]),
+    SwizzleI8x16,
+    SplatI8x16,
+    SplatI16x8,
+    SplatI32x4,
+    SplatI64x2,
+    SplatF32x4,
+    SplatF64x2,
+    ExtractLaneSI8x16(u8),
+    ExtractLaneUI8x16(u8),
+    ReplaceLaneI8x16(u8),
+    ExtractLaneSI16x8(u8),
+    ExtractLaneUI16x8(u8),
+    ReplaceLaneI16x8(u8),
+    ExtractLaneI32x4(u8),
+    ReplaceLaneI32x4(u8),
+    ExtractLaneI64x2(u8),
+    ReplaceLaneI64x2(u8),
+    ExtractLaneF32x4(u8),
+    ReplaceLaneF32x4(u8),
+    ExtractLaneF64x2(u8),
+    ReplaceLaneF64x2(u8),
+    EqI8x16,
+    NeI8x16,
+    LtSI8x16,
+    LtUI8x16,
+    GtSI8x16,
+    GtUI8x16,
+    LeSI8x16,
+    LeUI8x16,
+    GeSI8x16,
+    GeUI8x16,
+    EqI16x8,
+    NeI16x8,
+    LtSI16x8,
+    LtUI16x8,
+    GtSI16x8,
+    GtUI16x8,
+    LeSI16x8,
+    LeUI16x8,
+    GeSI16x8,
+    GeUI16x8,
+    EqI32x4,
+    NeI32x4,
+    LtSI32x4,
+    LtUI32x4,
+    GtSI32x4,
+    GtUI32x4,
+    LeSI32x4,
+    LeUI32x4,
+    GeSI32x4,
+    GeUI32x4,
+    EqF32x4,
+    NeF32x4,
+    LtF32x4,
+    GtF32x4,
+    LeF32x4,
+    GeF32x4,
+    EqF64x2,
+    NeF64x2,
+    LtF64x2,
+    GtF64x2,
+    LeF64x2,
+    GeF64x2,
+    NotV128,
+    AndV128,
+    AndNotV128,
+    OrV128,
+    XorV128,
+    BitselectV128,
+    AnyTrueV128,
+    Load8Lane(MemArg, u8),
+    Load16Lane(MemArg, u8),
+    Load32Lane(MemArg, u8),
+    Load64Lane(MemArg, u8),
+    Store8Lane(MemArg, u8),
+    Store16Lane(MemArg, u8),
+    Store32Lane(MemArg, u8),
+    Store64Lane(MemArg, u8),
+    Load32Zero(MemArg),
+    Load64Zero(MemArg),
+    DemoteZeroF32x4F64x2,
+    PromoteLowF64x2F32x4,
+    AbsI8x16,
+    NegI8x16,
+    PopcntI8x16,
+    AllTrueI8x16,
+    BitmaskI8x16,
+    NarrowSI8x16I16x8,
+    NarrowUI8x16I16x8,
+    CeilF32x4,
+    FloorF32x4,
+    TruncF32x4,
+    NearestF32x4,
+    ShlI8x16,
+    ShrSI8x16,
+    ShrUI8x16,
+    AddI8x16,
+    AddSatSI8x16,
+    AddSatUI8x16,
+    SubI8x16,
+    SubSatSI8x16,
+    SubSatUI8x16,
+    CeilF64x2,
+    FloorF64x2,
+    MinSI8x16,
+    MinUI8x16,
+    MaxSI8x16,
+    MaxUI8x16,
+    TruncF64x2,
+    AvgrUI8x16,
+    ExtaddPairwiseSI16x8I8x16,
+    ExtaddPairwiseUI16x8I8x16,
+    ExtaddPairwiseSI32x4I16x8,
+    ExtaddPairwiseUI32x4I16x8,
+    AbsI16x8,
+    NegI16x8,
+    Q15mulrSatSI16x8,
+    AllTrueI16x8,
+    BitmaskI16x8,
+    NarrowSI16x8I32x4,
+    NarrowUI16x8I32x4,
+    ExtendLowSI16x8I8x16,
+    ExtendHighSI16x8I8x16,
+    ExtendLowUI16x8I8x16,
+    ExtendHighUI16x8I8x16,
+    ShlI16x8,
+    ShrSI16x8,
+    ShrUI16x8,
+    AddI16x8,
+    AddSatSI16x8,
+    AddSatUI16x8,
+    SubI16x8,
+    SubSatSI16x8,
+    SubSatUI16x8,
+    NearestF64x2,
+    MulI16x8,
+    MinSI16x8,
+    MinUI16x8,
+    MaxSI16x8,
+    MaxUI16x8,
+    AvgrUI16x8,
+    ExtmulLowSI16x8I8x16,
+    ExtmulHighSI16x8I8x16,
+    ExtmulLowUI16x8I8x16,
+    ExtmulHighUI16x8I8x16,
+    AbsI32x4,
+    NegI32x4,
+    AllTrueI32x4,
+    BitmaskI32x4,
+    ExtendLowSI32x4I16x8,
+    ExtendHighSI32x4I16x8,
+    ExtendLowUI32x4I16x8,
+    ExtendHighUI32x4I16x8,
+    ShlI32x4,
+    ShrSI32x4,
+    ShrUI32x4,
+    AddI32x4,
+    SubI32x4,
+    MulI32x4,
+    MinSI32x4,
+    MinUI32x4,
+    MaxSI32x4,
+    MaxUI32x4,
+    DotSI32x4I16x8,
+    ExtmulLowSI32x4I16x8,
+    ExtmulHighSI32x4I16x8,
+    ExtmulLowUI32x4I16x8,
+    ExtmulHighUI32x4I16x8,
+    AbsI64x2,
+    NegI64x2,
+    AllTrueI64x2,
+    BitmaskI64x2,
+    ExtendLowSI64x2I32x4,
+    ExtendHighSI64x2I32x4,
+    ExtendLowUI64x2I32x4,
+    ExtendHighUI64x2I32x4,
+    ShlI64x2,
+    ShrSI64x2,
+    ShrUI64x2,
+    AddI64x2,
+    SubI64x2,
+    MulI64x2,
+    EqI64x2,
+    NeI64x2,
+    LtSI64x2,
+    GtSI64x2,
+    LeSI64x2,
+    GeSI64x2,
+    ExtmulLowSI64x2I32x4,
+    ExtmulHighSI64x2I32x4,
+    ExtmulLowUI64x2I32x4,
+    ExtmulHighUI64x2I32x4,
+    AbsF32x4,
+    NegF32x4,
+    SqrtF32x4,
+    AddF32x4,
+    SubF32x4,
+    MulF32x4,
+    DivF32x4,
+    MinF32x4,
+    MaxF32x4,
+    PminF32x4,
+    PmaxF32x4,
+    AbsF64x2,
+    NegF64x2,
+    SqrtF64x2,
+    AddF64x2,
+    SubF64x2,
+    MulF64x2,
+    DivF64x2,
+    MinF64x2,
+    MaxF64x2,
+    PminF64x2,
+    PmaxF64x2,
+    TruncSatSI32x4F32x4,
+    TruncSatUI32x4F32x4,
+    ConvertSF32x4I32x4,
+    ConvertUF32x4I32x4,
+    TruncSatZeroSI32x4F64x2,
+    TruncSatZeroUI32x4F64x2,
+    ConvertLowSF64x2I32x4,
+    ConvertLowUF64x2I32x4,
+}
+
+impl VectorInstruction {
+    fn parse(data: &mut &[u8], sub: u32) -> Result<Self> {
+        match sub {
+            0 => Ok(Self::Load(MemArg::parse(data)?)),
+            1 => Ok(Self::Load8x8S(MemArg::parse(data)?)),
+            2 => Ok(Self::Load8x8U(MemArg::parse(data)?)),
+            3 => Ok(Self::Load16x4S(MemArg::parse(data)?)),
+            4 => Ok(Self::Load16x4U(MemArg::parse(data)?)),
+            5 => Ok(Self::Load32x2S(MemArg::parse(data)?)),
+            6 => Ok(Self::Load32x2U(MemArg::parse(data)?)),
+            7 => Ok(Self::Load8Splat(MemArg::parse(data)?)),
+            8 => Ok(Self::Load16Splat(MemArg::parse(data)?)),
+            9 => Ok(Self::Load32Splat(MemArg::parse(data)?)),
+            10 => Ok(Self::Load64Splat(MemArg::parse(data)?)),
+            11 => Ok(Self::Store(MemArg::parse(data)?)),
+            12 => Ok(Self::Const(decode::decode_128bit(data)?)),
+            13 => Ok(Self::ShuffleI8x16(decode::decode_128bit(data)?)),
+            14 => Ok(Self::SwizzleI8x16),
+            15 => Ok(Self::SplatI8x16),
+            16 => Ok(Self::SplatI16x8),
+            17 => Ok(Self::SplatI32x4),
+            18 => Ok(Self::SplatI64x2),
+            19 => Ok(Self::SplatF32x4),
+            20 => Ok(Self::SplatF64x2),
+            21 => Ok(Self::ExtractLaneSI8x16(decode::decode_8bit(data)?[0])),
+            22 => Ok(Self::ExtractLaneUI8x16(decode::decode_8bit(data)?[0])),
+            23 => Ok(Self::ReplaceLaneI8x16(decode::decode_8bit(data)?[0])),
+            24 => Ok(Self::ExtractLaneSI16x8(decode::decode_8bit(data)?[0])),
+            25 => Ok(Self::ExtractLaneUI16x8(decode::decode_8bit(data)?[0])),
+            26 => Ok(Self::ReplaceLaneI16x8(decode::decode_8bit(data)?[0])),
+            27 => Ok(Self::ExtractLaneI32x4(decode::decode_8bit(data)?[0])),
+            28 => Ok(Self::ReplaceLaneI32x4(decode::decode_8bit(data)?[0])),
+            29 => Ok(Self::ExtractLaneI64x2(decode::decode_8bit(data)?[0])),
+            30 => Ok(Self::ReplaceLaneI64x2(decode::decode_8bit(data)?[0])),
+            31 => Ok(Self::ExtractLaneF32x4(decode::decode_8bit(data)?[0])),
+            32 => Ok(Self::ReplaceLaneF32x4(decode::decode_8bit(data)?[0])),
+            33 => Ok(Self::ExtractLaneF64x2(decode::decode_8bit(data)?[0])),
+            34 => Ok(Self::ReplaceLaneF64x2(decode::decode_8bit(data)?[0])),
+            35 => Ok(Self::EqI8x16),
+            36 => Ok(Self::NeI8x16),
+            37 => Ok(Self::LtSI8x16),
+            38 => Ok(Self::LtUI8x16),
+            39 => Ok(Self::GtSI8x16),
+            40 => Ok(Self::GtUI8x16),
+            41 => Ok(Self::LeSI8x16),
+            42 => Ok(Self::LeUI8x16),
+            43 => Ok(Self::GeSI8x16),
+            44 => Ok(Self::GeUI8x16),
+            45 => Ok(Self::EqI16x8),
+            46 => Ok(Self::NeI16x8),
+            47 => Ok(Self::LtSI16x8),
+            48 => Ok(Self::LtUI16x8),
+            49 => Ok(Self::GtSI16x8),
+            50 => Ok(Self::GtUI16x8),
+            51 => Ok(Self::LeSI16x8),
+            52 => Ok(Self::LeUI16x8),
+            53 => Ok(Self::GeSI16x8),
+            54 => Ok(Self::GeUI16x8),
+            55 => Ok(Self::EqI32x4),
+            56 => Ok(Self::NeI32x4),
+            57 => Ok(Self::LtSI32x4),
+            58 => Ok(Self::LtUI32x4),
+            59 => Ok(Self::GtSI32x4),
+            60 => Ok(Self::GtUI32x4),
+            61 => Ok(Self::LeSI32x4),
+            62 => Ok(Self::LeUI32x4),
+            63 => Ok(Self::GeSI32x4),
+            64 => Ok(Self::GeUI32x4),
+            65 => Ok(Self::EqF32x4),
+            66 => Ok(Self::NeF32x4),
+            67 => Ok(Self::LtF32x4),
+            68 => Ok(Self::GtF32x4),
+            69 => Ok(Self::LeF32x4),
+            70 => Ok(Self::GeF32x4),
+            71 => Ok(Self::EqF64x2),
+            72 => Ok(Self::NeF64x2),
+            73 => Ok(Self::LtF64x2),
+            74 => Ok(Self::GtF64x2),
+            75 => Ok(Self::LeF64x2),
+            76 => Ok(Self::GeF64x2),
+            77 => Ok(Self::NotV128),
+            78 => Ok(Self::AndV128),
+            79 => Ok(Self::AndNotV128),
+            80 => Ok(Self::OrV128),
+            81 => Ok(Self::XorV128),
+            82 => Ok(Self::BitselectV128),
+            83 => Ok(Self::AnyTrueV128),
+            84 => Ok(Self::Load8Lane(
+                MemArg::parse(data)?,
+                decode::decode_8bit(data)?[0],
+            )),
+            85 => Ok(Self::Load16Lane(
+                MemArg::parse(data)?,
+                decode::decode_8bit(data)?[0],
+            )),
+            86 => Ok(Self::Load32Lane(
+                MemArg::parse(data)?,
+                decode::decode_8bit(data)?[0],
+            )),
+            87 => Ok(Self::Load64Lane(
+                MemArg::parse(data)?,
+                decode::decode_8bit(data)?[0],
+            )),
+            88 => Ok(Self::Store8Lane(
+                MemArg::parse(data)?,
+                decode::decode_8bit(data)?[0],
+            )),
+            89 => Ok(Self::Store16Lane(
+                MemArg::parse(data)?,
+                decode::decode_8bit(data)?[0],
+            )),
+            90 => Ok(Self::Store32Lane(
+                MemArg::parse(data)?,
+                decode::decode_8bit(data)?[0],
+            )),
+            91 => Ok(Self::Store64Lane(
+                MemArg::parse(data)?,
+                decode::decode_8bit(data)?[0],
+            )),
+            92 => Ok(Self::Load32Zero(MemArg::parse(data)?)),
+            93 => Ok(Self::Load64Zero(MemArg::parse(data)?)),
+            94 => Ok(Self::DemoteZeroF32x4F64x2),
+            95 => Ok(Self::PromoteLowF64x2F32x4),
+            96 => Ok(Self::AbsI8x16),
+            97 => Ok(Self::NegI8x16),
+            98 => Ok(Self::PopcntI8x16),
+            99 => Ok(Self::AllTrueI8x16),
+            100 => Ok(Self::BitmaskI8x16),
+            101 => Ok(Self::NarrowSI8x16I16x8),
+            102 => Ok(Self::NarrowUI8x16I16x8),
+            103 => Ok(Self::CeilF32x4),
+            104 => Ok(Self::FloorF32x4),
+            105 => Ok(Self::TruncF32x4),
+            106 => Ok(Self::NearestF32x4),
+            107 => Ok(Self::ShlI8x16),
+            108 => Ok(Self::ShrSI8x16),
+            109 => Ok(Self::ShrUI8x16),
+            110 => Ok(Self::AddI8x16),
+            111 => Ok(Self::AddSatSI8x16),
+            112 => Ok(Self::AddSatUI8x16),
+            113 => Ok(Self::SubI8x16),
+            114 => Ok(Self::SubSatSI8x16),
+            115 => Ok(Self::SubSatUI8x16),
+            116 => Ok(Self::CeilF64x2),
+            117 => Ok(Self::FloorF64x2),
+            118 => Ok(Self::MinSI8x16),
+            119 => Ok(Self::MinUI8x16),
+            120 => Ok(Self::MaxSI8x16),
+            121 => Ok(Self::MaxUI8x16),
+            122 => Ok(Self::TruncF64x2),
+            123 => Ok(Self::AvgrUI8x16),
+            124 => Ok(Self::ExtaddPairwiseSI16x8I8x16),
+            125 => Ok(Self::ExtaddPairwiseUI16x8I8x16),
+            126 => Ok(Self::ExtaddPairwiseSI32x4I16x8),
+            127 => Ok(Self::ExtaddPairwiseUI32x4I16x8),
+            128 => Ok(Self::AbsI16x8),
+            129 => Ok(Self::NegI16x8),
+            130 => Ok(Self::Q15mulrSatSI16x8),
+            131 => Ok(Self::AllTrueI16x8),
+            132 => Ok(Self::BitmaskI16x8),
+            133 => Ok(Self::NarrowSI16x8I32x4),
+            134 => Ok(Self::NarrowUI16x8I32x4),
+            135 => Ok(Self::ExtendLowSI16x8I8x16),
+            136 => Ok(Self::ExtendHighSI16x8I8x16),
+            137 => Ok(Self::ExtendLowUI16x8I8x16),
+            138 => Ok(Self::ExtendHighUI16x8I8x16),
+            139 => Ok(Self::ShlI16x8),
+            140 => Ok(Self::ShrSI16x8),
+            141 => Ok(Self::ShrUI16x8),
+            142 => Ok(Self::AddI16x8),
+            143 => Ok(Self::AddSatSI16x8),
+            144 => Ok(Self::AddSatUI16x8),
+            145 => Ok(Self::SubI16x8),
+            146 => Ok(Self::SubSatSI16x8),
+            147 => Ok(Self::SubSatUI16x8),
+            148 => Ok(Self::NearestF64x2),
+            149 => Ok(Self::MulI16x8),
+            150 => Ok(Self::MinSI16x8),
+            151 => Ok(Self::MinUI16x8),
+            152 => Ok(Self::MaxSI16x8),
+            153 => Ok(Self::MaxUI16x8),
+            155 => Ok(Self::AvgrUI16x8),
+            156 => Ok(Self::ExtmulLowSI16x8I8x16),
+            157 => Ok(Self::ExtmulHighSI16x8I8x16),
+            158 => Ok(Self::ExtmulLowUI16x8I8x16),
+            159 => Ok(Self::ExtmulHighUI16x8I8x16),
+            160 => Ok(Self::AbsI32x4),
+            161 => Ok(Self::NegI32x4),
+            163 => Ok(Self::AllTrueI32x4),
+            164 => Ok(Self::BitmaskI32x4),
+            167 => Ok(Self::ExtendLowSI32x4I16x8),
+            168 => Ok(Self::ExtendHighSI32x4I16x8),
+            169 => Ok(Self::ExtendLowUI32x4I16x8),
+            170 => Ok(Self::ExtendHighUI32x4I16x8),
+            171 => Ok(Self::ShlI32x4),
+            172 => Ok(Self::ShrSI32x4),
+            173 => Ok(Self::ShrUI32x4),
+            174 => Ok(Self::AddI32x4),
+            177 => Ok(Self::SubI32x4),
+            181 => Ok(Self::MulI32x4),
+            182 => Ok(Self::MinSI32x4),
+            183 => Ok(Self::MinUI32x4),
+            184 => Ok(Self::MaxSI32x4),
+            185 => Ok(Self::MaxUI32x4),
+            186 => Ok(Self::DotSI32x4I16x8),
+            188 => Ok(Self::ExtmulLowSI32x4I16x8),
+            189 => Ok(Self::ExtmulHighSI32x4I16x8),
+            190 => Ok(Self::ExtmulLowUI32x4I16x8),
+            191 => Ok(Self::ExtmulHighUI32x4I16x8),
+            192 => Ok(Self::AbsI64x2),
+            193 => Ok(Self::NegI64x2),
+            195 => Ok(Self::AllTrueI64x2),
+            196 => Ok(Self::BitmaskI64x2),
+            199 => Ok(Self::ExtendLowSI64x2I32x4),
+            200 => Ok(Self::ExtendHighSI64x2I32x4),
+            201 => Ok(Self::ExtendLowUI64x2I32x4),
+            202 => Ok(Self::ExtendHighUI64x2I32x4),
+            203 => Ok(Self::ShlI64x2),
+            204 => Ok(Self::ShrSI64x2),
+            205 => Ok(Self::ShrUI64x2),
+            206 => Ok(Self::AddI64x2),
+            209 => Ok(Self::SubI64x2),
+            213 => Ok(Self::MulI64x2),
+            214 => Ok(Self::EqI64x2),
+            215 => Ok(Self::NeI64x2),
+            216 => Ok(Self::LtSI64x2),
+            217 => Ok(Self::GtSI64x2),
+            218 => Ok(Self::LeSI64x2),
+            219 => Ok(Self::GeSI64x2),
+            220 => Ok(Self::ExtmulLowSI64x2I32x4),
+            221 => Ok(Self::ExtmulHighSI64x2I32x4),
+            222 => Ok(Self::ExtmulLowUI64x2I32x4),
+            223 => Ok(Self::ExtmulHighUI64x2I32x4),
+            224 => Ok(Self::AbsF32x4),
+            225 => Ok(Self::NegF32x4),
+            227 => Ok(Self::SqrtF32x4),
+            228 => Ok(Self::AddF32x4),
+            229 => Ok(Self::SubF32x4),
+            230 => Ok(Self::MulF32x4),
+            231 => Ok(Self::DivF32x4),
+            232 => Ok(Self::MinF32x4),
+            233 => Ok(Self::MaxF32x4),
+            234 => Ok(Self::PminF32x4),
+            235 => Ok(Self::PmaxF32x4),
+            236 => Ok(Self::AbsF64x2),
+            237 => Ok(Self::NegF64x2),
+            239 => Ok(Self::SqrtF64x2),
+            240 => Ok(Self::AddF64x2),
+            241 => Ok(Self::SubF64x2),
+            242 => Ok(Self::MulF64x2),
+            243 => Ok(Self::DivF64x2),
+            244 => Ok(Self::MinF64x2),
+            245 => Ok(Self::MaxF64x2),
+            246 => Ok(Self::PminF64x2),
+            247 => Ok(Self::PmaxF64x2),
+            248 => Ok(Self::TruncSatSI32x4F32x4),
+            249 => Ok(Self::TruncSatUI32x4F32x4),
+            250 => Ok(Self::ConvertSF32x4I32x4),
+            251 => Ok(Self::ConvertUF32x4I32x4),
+            252 => Ok(Self::TruncSatZeroSI32x4F64x2),
+            253 => Ok(Self::TruncSatZeroUI32x4F64x2),
+            254 => Ok(Self::ConvertLowSF64x2I32x4),
+            255 => Ok(Self::ConvertLowUF64x2I32x4),
+            _ => Err(ParseError::UnexpectedValue(format!(
+                "unexpected sub opcode in VectorInstruction. got={}",
+                sub
+            ))),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let sub = match self {
+            Self::Load(..) => 0,
+            Self::Load8x8S(..) => 1,
+            Self::Load8x8U(..) => 2,
+            Self::Load16x4S(..) => 3,
+            Self::Load16x4U(..) => 4,
+            Self::Load32x2S(..) => 5,
+            Self::Load32x2U(..) => 6,
+            Self::Load8Splat(..) => 7,
+            Self::Load16Splat(..) => 8,
+            Self::Load32Splat(..) => 9,
+            Self::Load64Splat(..) => 10,
+            Self::Store(..) => 11,
+            Self::Const(..) => 12,
+            Self::ShuffleI8x16(..) => 13,
+            Self::SwizzleI8x16 => 14,
+            Self::SplatI8x16 => 15,
+            Self::SplatI16x8 => 16,
+            Self::SplatI32x4 => 17,
+            Self::SplatI64x2 => 18,
+            Self::SplatF32x4 => 19,
+            Self::SplatF64x2 => 20,
+            Self::ExtractLaneSI8x16(..) => 21,
+            Self::ExtractLaneUI8x16(..) => 22,
+            Self::ReplaceLaneI8x16(..) => 23,
+            Self::ExtractLaneSI16x8(..) => 24,
+            Self::ExtractLaneUI16x8(..) => 25,
+            Self::ReplaceLaneI16x8(..) => 26,
+            Self::ExtractLaneI32x4(..) => 27,
+            Self::ReplaceLaneI32x4(..) => 28,
+            Self::ExtractLaneI64x2(..) => 29,
+            Self::ReplaceLaneI64x2(..) => 30,
+            Self::ExtractLaneF32x4(..) => 31,
+            Self::ReplaceLaneF32x4(..) => 32,
+            Self::ExtractLaneF64x2(..) => 33,
+            Self::ReplaceLaneF64x2(..) => 34,
+            Self::EqI8x16 => 35,
+            Self::NeI8x16 => 36,
+            Self::LtSI8x16 => 37,
+            Self::LtUI8x16 => 38,
+            Self::GtSI8x16 => 39,
+            Self::GtUI8x16 => 40,
+            Self::LeSI8x16 => 41,
+            Self::LeUI8x16 => 42,
+            Self::GeSI8x16 => 43,
+            Self::GeUI8x16 => 44,
+            Self::EqI16x8 => 45,
+            Self::NeI16x8 => 46,
+            Self::LtSI16x8 => 47,
+            Self::LtUI16x8 => 48,
+            Self::GtSI16x8 => 49,
+            Self::GtUI16x8 => 50,
+            Self::LeSI16x8 => 51,
+            Self::LeUI16x8 => 52,
+            Self::GeSI16x8 => 53,
+            Self::GeUI16x8 => 54,
+            Self::EqI32x4 => 55,
+            Self::NeI32x4 => 56,
+            Self::LtSI32x4 => 57,
+            Self::LtUI32x4 => 58,
+            Self::GtSI32x4 => 59,
+            Self::GtUI32x4 => 60,
+            Self::LeSI32x4 => 61,
+            Self::LeUI32x4 => 62,
+            Self::GeSI32x4 => 63,
+            Self::GeUI32x4 => 64,
+            Self::EqF32x4 => 65,
+            Self::NeF32x4 => 66,
+            Self::LtF32x4 => 67,
+            Self::GtF32x4 => 68,
+            Self::LeF32x4 => 69,
+            Self::GeF32x4 => 70,
+            Self::EqF64x2 => 71,
+            Self::NeF64x2 => 72,
+            Self::LtF64x2 => 73,
+            Self::GtF64x2 => 74,
+            Self::LeF64x2 => 75,
+            Self::GeF64x2 => 76,
+            Self::NotV128 => 77,
+            Self::AndV128 => 78,
+            Self::AndNotV128 => 79,
+            Self::OrV128 => 80,
+            Self::XorV128 => 81,
+            Self::BitselectV128 => 82,
+            Self::AnyTrueV128 => 83,
+            Self::Load8Lane(..) => 84,
+            Self::Load16Lane(..) => 85,
+            Self::Load32Lane(..) => 86,
+            Self::Load64Lane(..) => 87,
+            Self::Store8Lane(..) => 88,
+            Self::Store16Lane(..) => 89,
+            Self::Store32Lane(..) => 90,
+            Self::Store64Lane(..) => 91,
+            Self::Load32Zero(..) => 92,
+            Self::Load64Zero(..) => 93,
+            Self::DemoteZeroF32x4F64x2 => 94,
+            Self::PromoteLowF64x2F32x4 => 95,
+            Self::AbsI8x16 => 96,
+            Self::NegI8x16 => 97,
+            Self::PopcntI8x16 => 98,
+            Self::AllTrueI8x16 => 99,
+            Self::BitmaskI8x16 => 100,
+            Self::NarrowSI8x16I16x8 => 101,
+            Self::NarrowUI8x16I16x8 => 102,
+            Self::CeilF32x4 => 103,
+            Self::FloorF32x4 => 104,
+            Self::TruncF32x4 => 105,
+            Self::NearestF32x4 => 106,
+            Self::ShlI8x16 => 107,
+            Self::ShrSI8x16 => 108,
+            Self::ShrUI8x16 => 109,
+            Self::AddI8x16 => 110,
+            Self::AddSatSI8x16 => 111,
+            Self::AddSatUI8x16 => 112,
+            Self::SubI8x16 => 113,
+            Self::SubSatSI8x16 => 114,
+            Self::SubSatUI8x16 => 115,
+            Self::CeilF64x2 => 116,
+            Self::FloorF64x2 => 117,
+            Self::MinSI8x16 => 118,
+            Self::MinUI8x16 => 119,
+            Self::MaxSI8x16 => 120,
+            Self::MaxUI8x16 => 121,
+            Self::TruncF64x2 => 122,
+            Self::AvgrUI8x16 => 123,
+            Self::ExtaddPairwiseSI16x8I8x16 => 124,
+            Self::ExtaddPairwiseUI16x8I8x16 => 125,
+            Self::ExtaddPairwiseSI32x4I16x8 => 126,
+            Self::ExtaddPairwiseUI32x4I16x8 => 127,
+            Self::AbsI16x8 => 128,
+            Self::NegI16x8 => 129,
+            Self::Q15mulrSatSI16x8 => 130,
+            Self::AllTrueI16x8 => 131,
+            Self::BitmaskI16x8 => 132,
+            Self::NarrowSI16x8I32x4 => 133,
+            Self::NarrowUI16x8I32x4 => 134,
+            Self::ExtendLowSI16x8I8x16 => 135,
+            Self::ExtendHighSI16x8I8x16 => 136,
+            Self::ExtendLowUI16x8I8x16 => 137,
+            Self::ExtendHighUI16x8I8x16 => 138,
+            Self::ShlI16x8 => 139,
+            Self::ShrSI16x8 => 140,
+            Self::ShrUI16x8 => 141,
+            Self::AddI16x8 => 142,
+            Self::AddSatSI16x8 => 143,
+            Self::AddSatUI16x8 => 144,
+            Self::SubI16x8 => 145,
+            Self::SubSatSI16x8 => 146,
+            Self::SubSatUI16x8 => 147,
+            Self::NearestF64x2 => 148,
+            Self::MulI16x8 => 149,
+            Self::MinSI16x8 => 150,
+            Self::MinUI16x8 => 151,
+            Self::MaxSI16x8 => 152,
+            Self::MaxUI16x8 => 153,
+            Self::AvgrUI16x8 => 155,
+            Self::ExtmulLowSI16x8I8x16 => 156,
+            Self::ExtmulHighSI16x8I8x16 => 157,
+            Self::ExtmulLowUI16x8I8x16 => 158,
+            Self::ExtmulHighUI16x8I8x16 => 159,
+            Self::AbsI32x4 => 160,
+            Self::NegI32x4 => 161,
+            Self::AllTrueI32x4 => 163,
+            Self::BitmaskI32x4 => 164,
+            Self::ExtendLowSI32x4I16x8 => 167,
+            Self::ExtendHighSI32x4I16x8 => 168,
+            Self::ExtendLowUI32x4I16x8 => 169,
+            Self::ExtendHighUI32x4I16x8 => 170,
+            Self::ShlI32x4 => 171,
+            Self::ShrSI32x4 => 172,
+            Self::ShrUI32x4 => 173,
+            Self::AddI32x4 => 174,
+            Self::SubI32x4 => 177,
+            Self::MulI32x4 => 181,
+            Self::MinSI32x4 => 182,
+            Self::MinUI32x4 => 183,
+            Self::MaxSI32x4 => 184,
+            Self::MaxUI32x4 => 185,
+            Self::DotSI32x4I16x8 => 186,
+            Self::ExtmulLowSI32x4I16x8 => 188,
+            Self::ExtmulHighSI32x4I16x8 => 189,
+            Self::ExtmulLowUI32x4I16x8 => 190,
+            Self::ExtmulHighUI32x4I16x8 => 191,
+            Self::AbsI64x2 => 192,
+            Self::NegI64x2 => 193,
+            Self::AllTrueI64x2 => 195,
+            Self::BitmaskI64x2 => 196,
+            Self::ExtendLowSI64x2I32x4 => 199,
+            Self::ExtendHighSI64x2I32x4 => 200,
+            Self::ExtendLowUI64x2I32x4 => 201,
+            Self::ExtendHighUI64x2I32x4 => 202,
+            Self::ShlI64x2 => 203,
+            Self::ShrSI64x2 => 204,
+            Self::ShrUI64x2 => 205,
+            Self::AddI64x2 => 206,
+            Self::SubI64x2 => 209,
+            Self::MulI64x2 => 213,
+            Self::EqI64x2 => 214,
+            Self::NeI64x2 => 215,
+            Self::LtSI64x2 => 216,
+            Self::GtSI64x2 => 217,
+            Self::LeSI64x2 => 218,
+            Self::GeSI64x2 => 219,
+            Self::ExtmulLowSI64x2I32x4 => 220,
+            Self::ExtmulHighSI64x2I32x4 => 221,
+            Self::ExtmulLowUI64x2I32x4 => 222,
+            Self::ExtmulHighUI64x2I32x4 => 223,
+            Self::AbsF32x4 => 224,
+            Self::NegF32x4 => 225,
+            Self::SqrtF32x4 => 227,
+            Self::AddF32x4 => 228,
+            Self::SubF32x4 => 229,
+            Self::MulF32x4 => 230,
+            Self::DivF32x4 => 231,
+            Self::MinF32x4 => 232,
+            Self::MaxF32x4 => 233,
+            Self::PminF32x4 => 234,
+            Self::PmaxF32x4 => 235,
+            Self::AbsF64x2 => 236,
+            Self::NegF64x2 => 237,
+            Self::SqrtF64x2 => 239,
+            Self::AddF64x2 => 240,
+            Self::SubF64x2 => 241,
+            Self::MulF64x2 => 242,
+            Self::DivF64x2 => 243,
+            Self::MinF64x2 => 244,
+            Self::MaxF64x2 => 245,
+            Self::PminF64x2 => 246,
+            Self::PmaxF64x2 => 247,
+            Self::TruncSatSI32x4F32x4 => 248,
+            Self::TruncSatUI32x4F32x4 => 249,
+            Self::ConvertSF32x4I32x4 => 250,
+            Self::ConvertUF32x4I32x4 => 251,
+            Self::TruncSatZeroSI32x4F64x2 => 252,
+            Self::TruncSatZeroUI32x4F64x2 => 253,
+            Self::ConvertLowSF64x2I32x4 => 254,
+            Self::ConvertLowUF64x2I32x4 => 255,
+        };
+        buf.push(0xFD);
+        encode::encode_varint_u32(buf, sub);
+        match self {
+            Self::Load(memarg)
+            | Self::Load8x8S(memarg)
+            | Self::Load8x8U(memarg)
+            | Self::Load16x4S(memarg)
+            | Self::Load16x4U(memarg)
+            | Self::Load32x2S(memarg)
+            | Self::Load32x2U(memarg)
+            | Self::Load8Splat(memarg)
+            | Self::Load16Splat(memarg)
+            | Self::Load32Splat(memarg)
+            | Self::Load64Splat(memarg)
+            | Self::Store(memarg)
+            | Self::Load32Zero(memarg)
+            | Self::Load64Zero(memarg) => memarg.encode(buf),
+            Self::Const(bytes) | Self::ShuffleI8x16(bytes) => buf.extend_from_slice(bytes),
+            Self::ExtractLaneSI8x16(lane)
+            | Self::ExtractLaneUI8x16(lane)
+            | Self::ReplaceLaneI8x16(lane)
+            | Self::ExtractLaneSI16x8(lane)
+            | Self::ExtractLaneUI16x8(lane)
+            | Self::ReplaceLaneI16x8(lane)
+            | Self::ExtractLaneI32x4(lane)
+            | Self::ReplaceLaneI32x4(lane)
+            | Self::ExtractLaneI64x2(lane)
+            | Self::ReplaceLaneI64x2(lane)
+            | Self::ExtractLaneF32x4(lane)
+            | Self::ReplaceLaneF32x4(lane)
+            | Self::ExtractLaneF64x2(lane)
+            | Self::ReplaceLaneF64x2(lane) => buf.push(*lane),
+            Self::Load8Lane(memarg, lane)
+            | Self::Load16Lane(memarg, lane)
+            | Self::Load32Lane(memarg, lane)
+            | Self::Load64Lane(memarg, lane)
+            | Self::Store8Lane(memarg, lane)
+            | Self::Store16Lane(memarg, lane)
+            | Self::Store32Lane(memarg, lane)
+            | Self::Store64Lane(memarg, lane) => {
+                memarg.encode(buf);
+                buf.push(*lane);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug)]
 pub enum NumericInstruction {
     Const(ConstNumericInstruction),
     Plain(PlainNumericInstruction),
-    SaturatingTruncation,
+    SaturatingTruncation(SaturatingTruncationInstruction),
 }
 
 impl NumericInstruction {
@@ -113,15 +1679,69 @@ impl NumericInstruction {
         match by {
             0x41..=0x44 => Ok(Self::Const(ConstNumericInstruction::parse(data, by)?)),
             0x45..=0xC4 => Ok(Self::Plain(PlainNumericInstruction::parse(data, by)?)),
-            0xFC => Ok(Self::SaturatingTruncation),
             _ => Err(ParseError::UnexpectedByteValue {
                 title: "NumericInstruction".to_string(),
                 got: by,
             }),
         }
     }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Const(instr) => instr.encode(buf),
+            Self::Plain(instr) => instr.encode(buf),
+            Self::SaturatingTruncation(instr) => instr.encode(buf),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SaturatingTruncationInstruction {
+    TruncSatSI32F32,
+    TruncSatUI32F32,
+    TruncSatSI32F64,
+    TruncSatUI32F64,
+    TruncSatSI64F32,
+    TruncSatUI64F32,
+    TruncSatSI64F64,
+    TruncSatUI64F64,
+}
+
+impl SaturatingTruncationInstruction {
+    // 0xFC prefix の命令
+    fn parse(sub: u32) -> Result<Self> {
+        match sub {
+            0 => Ok(Self::TruncSatSI32F32),
+            1 => Ok(Self::TruncSatUI32F32),
+            2 => Ok(Self::TruncSatSI32F64),
+            3 => Ok(Self::TruncSatUI32F64),
+            4 => Ok(Self::TruncSatSI64F32),
+            5 => Ok(Self::TruncSatUI64F32),
+            6 => Ok(Self::TruncSatSI64F64),
+            7 => Ok(Self::TruncSatUI64F64),
+            _ => Err(ParseError::UnexpectedValue(format!(
+                "unexpected sub opcode in SaturatingTruncationInstruction. got={}",
+                sub
+            ))),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let sub = match self {
+            Self::TruncSatSI32F32 => 0,
+            Self::TruncSatUI32F32 => 1,
+            Self::TruncSatSI32F64 => 2,
+            Self::TruncSatUI32F64 => 3,
+            Self::TruncSatSI64F32 => 4,
+            Self::TruncSatUI64F32 => 5,
+            Self::TruncSatSI64F64 => 6,
+            Self::TruncSatUI64F64 => 7,
+        };
+        buf.extend_from_slice(&[0xFC, sub]);
+    }
 }
 
+#[derive(Debug)]
 pub enum ConstNumericInstruction {
     ConstI32(i32),
     ConstI64(i64),
@@ -131,48 +1751,447 @@ pub enum ConstNumericInstruction {
 
 impl ConstNumericInstruction {
     fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
-        let value = decode::decode_varint(data)?;
         match by {
-            0x41 => {
-                if value > u32::MAX as u64 {
-                    return Err(ParseError::UnexpectedValue(format!(
-                        "unexpected value. this value is greater than {}(u64::MAX)",
-                        u64::MAX
-                    )));
-                }
-                Ok(Self::ConstI32(value as i32))
-            }
-            0x42 => Ok(Self::ConstI64(value as i64)),
-            0x43 => {
-                if value > u32::MAX as u64 {
-                    return Err(ParseError::UnexpectedValue(format!(
-                        "unexpected value. this value is greater than {}(u64::MAX)",
-                        u64::MAX
-                    )));
-                }
-                Ok(Self::ConstF32(value as f32))
-            }
-            0x44 => Ok(Self::ConstF64(value as f64)),
+            0x41 => Ok(Self::ConstI32(decode::decode_varint_s32(data)?)),
+            0x42 => Ok(Self::ConstI64(decode::decode_varint_s64(data)?)),
+            // 浮動小数点数は IEEE 754 のビット列がそのまま little endian で格納されている
+            0x43 => Ok(Self::ConstF32(f32::from_bits(u32::from_le_bytes(
+                decode::decode_32bit(data)?,
+            )))),
+            0x44 => Ok(Self::ConstF64(f64::from_bits(u64::from_le_bytes(
+                decode::decode_64bit(data)?,
+            )))),
             _ => Err(ParseError::UnexpectedByteValue {
                 title: "ConstNumericInstruction".to_string(),
                 got: by,
             }),
         }
     }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::ConstI32(value) => {
+                buf.push(0x41);
+                encode::encode_varint_s32(buf, *value);
+            }
+            Self::ConstI64(value) => {
+                buf.push(0x42);
+                encode::encode_varint_s64(buf, *value);
+            }
+            // NaN の payload も保つよう、ビット列をそのまま書く
+            Self::ConstF32(value) => {
+                buf.push(0x43);
+                buf.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+            Self::ConstF64(value) => {
+                buf.push(0x44);
+                buf.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+        }
+    }
 }
 
+// 変換命令は 演算 + 結果の型 + 入力の型 の順で命名する (例: i32.wrap_i64 => WrapI32I64)
+#[derive(Debug)]
 pub enum PlainNumericInstruction {
+    EqzI32,
+    EqI32,
+    NeI32,
+    LtSI32,
+    LtUI32,
+    GtSI32,
+    GtUI32,
+    LeSI32,
+    LeUI32,
+    GeSI32,
+    GeUI32,
+    EqzI64,
+    EqI64,
+    NeI64,
+    LtSI64,
+    LtUI64,
+    GtSI64,
+    GtUI64,
+    LeSI64,
+    LeUI64,
+    GeSI64,
+    GeUI64,
+    EqF32,
+    NeF32,
+    LtF32,
+    GtF32,
+    LeF32,
+    GeF32,
+    EqF64,
+    NeF64,
+    LtF64,
+    GtF64,
+    LeF64,
+    GeF64,
+    ClzI32,
+    CtzI32,
+    PopcntI32,
     AddI32,
+    SubI32,
+    MulI32,
+    DivSI32,
+    DivUI32,
+    RemSI32,
+    RemUI32,
+    AndI32,
+    OrI32,
+    XorI32,
+    ShlI32,
+    ShrSI32,
+    ShrUI32,
+    RotlI32,
+    RotrI32,
+    ClzI64,
+    CtzI64,
+    PopcntI64,
+    AddI64,
+    SubI64,
+    MulI64,
+    DivSI64,
+    DivUI64,
+    RemSI64,
+    RemUI64,
+    AndI64,
+    OrI64,
+    XorI64,
+    ShlI64,
+    ShrSI64,
+    ShrUI64,
+    RotlI64,
+    RotrI64,
+    AbsF32,
+    NegF32,
+    CeilF32,
+    FloorF32,
+    TruncF32,
+    NearestF32,
+    SqrtF32,
+    AddF32,
+    SubF32,
+    MulF32,
+    DivF32,
+    MinF32,
+    MaxF32,
+    CopysignF32,
+    AbsF64,
+    NegF64,
+    CeilF64,
+    FloorF64,
+    TruncF64,
+    NearestF64,
+    SqrtF64,
+    AddF64,
+    SubF64,
+    MulF64,
+    DivF64,
+    MinF64,
+    MaxF64,
+    CopysignF64,
+    WrapI32I64,
+    TruncSI32F32,
+    TruncUI32F32,
+    TruncSI32F64,
+    TruncUI32F64,
+    ExtendSI64I32,
+    ExtendUI64I32,
+    TruncSI64F32,
+    TruncUI64F32,
+    TruncSI64F64,
+    TruncUI64F64,
+    ConvertSF32I32,
+    ConvertUF32I32,
+    ConvertSF32I64,
+    ConvertUF32I64,
+    DemoteF32F64,
+    ConvertSF64I32,
+    ConvertUF64I32,
+    ConvertSF64I64,
+    ConvertUF64I64,
+    PromoteF64F32,
+    ReinterpretI32F32,
+    ReinterpretI64F64,
+    ReinterpretF32I32,
+    ReinterpretF64I64,
+    Extend8SI32,
+    Extend16SI32,
+    Extend8SI64,
+    Extend16SI64,
+    Extend32SI64,
 }
 
 impl PlainNumericInstruction {
-    fn parse(data: &mut &[u8], by: u8) -> Result<Self> {
+    fn parse(_data: &mut &[u8], by: u8) -> Result<Self> {
         match by {
-            0x6a => Ok(Self::AddI32),
+            0x45 => Ok(Self::EqzI32),
+            0x46 => Ok(Self::EqI32),
+            0x47 => Ok(Self::NeI32),
+            0x48 => Ok(Self::LtSI32),
+            0x49 => Ok(Self::LtUI32),
+            0x4A => Ok(Self::GtSI32),
+            0x4B => Ok(Self::GtUI32),
+            0x4C => Ok(Self::LeSI32),
+            0x4D => Ok(Self::LeUI32),
+            0x4E => Ok(Self::GeSI32),
+            0x4F => Ok(Self::GeUI32),
+            0x50 => Ok(Self::EqzI64),
+            0x51 => Ok(Self::EqI64),
+            0x52 => Ok(Self::NeI64),
+            0x53 => Ok(Self::LtSI64),
+            0x54 => Ok(Self::LtUI64),
+            0x55 => Ok(Self::GtSI64),
+            0x56 => Ok(Self::GtUI64),
+            0x57 => Ok(Self::LeSI64),
+            0x58 => Ok(Self::LeUI64),
+            0x59 => Ok(Self::GeSI64),
+            0x5A => Ok(Self::GeUI64),
+            0x5B => Ok(Self::EqF32),
+            0x5C => Ok(Self::NeF32),
+            0x5D => Ok(Self::LtF32),
+            0x5E => Ok(Self::GtF32),
+            0x5F => Ok(Self::LeF32),
+            0x60 => Ok(Self::GeF32),
+            0x61 => Ok(Self::EqF64),
+            0x62 => Ok(Self::NeF64),
+            0x63 => Ok(Self::LtF64),
+            0x64 => Ok(Self::GtF64),
+            0x65 => Ok(Self::LeF64),
+            0x66 => Ok(Self::GeF64),
+            0x67 => Ok(Self::ClzI32),
+            0x68 => Ok(Self::CtzI32),
+            0x69 => Ok(Self::PopcntI32),
+            0x6A => Ok(Self::AddI32),
+            0x6B => Ok(Self::SubI32),
+            0x6C => Ok(Self::MulI32),
+            0x6D => Ok(Self::DivSI32),
+            0x6E => Ok(Self::DivUI32),
+            0x6F => Ok(Self::RemSI32),
+            0x70 => Ok(Self::RemUI32),
+            0x71 => Ok(Self::AndI32),
+            0x72 => Ok(Self::OrI32),
+            0x73 => Ok(Self::XorI32),
+            0x74 => Ok(Self::ShlI32),
+            0x75 => Ok(Self::ShrSI32),
+            0x76 => Ok(Self::ShrUI32),
+            0x77 => Ok(Self::RotlI32),
+            0x78 => Ok(Self::RotrI32),
+            0x79 => Ok(Self::ClzI64),
+            0x7A => Ok(Self::CtzI64),
+            0x7B => Ok(Self::PopcntI64),
+            0x7C => Ok(Self::AddI64),
+            0x7D => Ok(Self::SubI64),
+            0x7E => Ok(Self::MulI64),
+            0x7F => Ok(Self::DivSI64),
+            0x80 => Ok(Self::DivUI64),
+            0x81 => Ok(Self::RemSI64),
+            0x82 => Ok(Self::RemUI64),
+            0x83 => Ok(Self::AndI64),
+            0x84 => Ok(Self::OrI64),
+            0x85 => Ok(Self::XorI64),
+            0x86 => Ok(Self::ShlI64),
+            0x87 => Ok(Self::ShrSI64),
+            0x88 => Ok(Self::ShrUI64),
+            0x89 => Ok(Self::RotlI64),
+            0x8A => Ok(Self::RotrI64),
+            0x8B => Ok(Self::AbsF32),
+            0x8C => Ok(Self::NegF32),
+            0x8D => Ok(Self::CeilF32),
+            0x8E => Ok(Self::FloorF32),
+            0x8F => Ok(Self::TruncF32),
+            0x90 => Ok(Self::NearestF32),
+            0x91 => Ok(Self::SqrtF32),
+            0x92 => Ok(Self::AddF32),
+            0x93 => Ok(Self::SubF32),
+            0x94 => Ok(Self::MulF32),
+            0x95 => Ok(Self::DivF32),
+            0x96 => Ok(Self::MinF32),
+            0x97 => Ok(Self::MaxF32),
+            0x98 => Ok(Self::CopysignF32),
+            0x99 => Ok(Self::AbsF64),
+            0x9A => Ok(Self::NegF64),
+            0x9B => Ok(Self::CeilF64),
+            0x9C => Ok(Self::FloorF64),
+            0x9D => Ok(Self::TruncF64),
+            0x9E => Ok(Self::NearestF64),
+            0x9F => Ok(Self::SqrtF64),
+            0xA0 => Ok(Self::AddF64),
+            0xA1 => Ok(Self::SubF64),
+            0xA2 => Ok(Self::MulF64),
+            0xA3 => Ok(Self::DivF64),
+            0xA4 => Ok(Self::MinF64),
+            0xA5 => Ok(Self::MaxF64),
+            0xA6 => Ok(Self::CopysignF64),
+            0xA7 => Ok(Self::WrapI32I64),
+            0xA8 => Ok(Self::TruncSI32F32),
+            0xA9 => Ok(Self::TruncUI32F32),
+            0xAA => Ok(Self::TruncSI32F64),
+            0xAB => Ok(Self::TruncUI32F64),
+            0xAC => Ok(Self::ExtendSI64I32),
+            0xAD => Ok(Self::ExtendUI64I32),
+            0xAE => Ok(Self::TruncSI64F32),
+            0xAF => Ok(Self::TruncUI64F32),
+            0xB0 => Ok(Self::TruncSI64F64),
+            0xB1 => Ok(Self::TruncUI64F64),
+            0xB2 => Ok(Self::ConvertSF32I32),
+            0xB3 => Ok(Self::ConvertUF32I32),
+            0xB4 => Ok(Self::ConvertSF32I64),
+            0xB5 => Ok(Self::ConvertUF32I64),
+            0xB6 => Ok(Self::DemoteF32F64),
+            0xB7 => Ok(Self::ConvertSF64I32),
+            0xB8 => Ok(Self::ConvertUF64I32),
+            0xB9 => Ok(Self::ConvertSF64I64),
+            0xBA => Ok(Self::ConvertUF64I64),
+            0xBB => Ok(Self::PromoteF64F32),
+            0xBC => Ok(Self::ReinterpretI32F32),
+            0xBD => Ok(Self::ReinterpretI64F64),
+            0xBE => Ok(Self::ReinterpretF32I32),
+            0xBF => Ok(Self::ReinterpretF64I64),
+            0xC0 => Ok(Self::Extend8SI32),
+            0xC1 => Ok(Self::Extend16SI32),
+            0xC2 => Ok(Self::Extend8SI64),
+            0xC3 => Ok(Self::Extend16SI64),
+            0xC4 => Ok(Self::Extend32SI64),
             _ => Err(ParseError::UnexpectedByteValue {
                 title: "PlainNumericInstruction".to_string(),
                 got: by,
             }),
         }
     }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(match self {
+            Self::EqzI32 => 0x45,
+            Self::EqI32 => 0x46,
+            Self::NeI32 => 0x47,
+            Self::LtSI32 => 0x48,
+            Self::LtUI32 => 0x49,
+            Self::GtSI32 => 0x4A,
+            Self::GtUI32 => 0x4B,
+            Self::LeSI32 => 0x4C,
+            Self::LeUI32 => 0x4D,
+            Self::GeSI32 => 0x4E,
+            Self::GeUI32 => 0x4F,
+            Self::EqzI64 => 0x50,
+            Self::EqI64 => 0x51,
+            Self::NeI64 => 0x52,
+            Self::LtSI64 => 0x53,
+            Self::LtUI64 => 0x54,
+            Self::GtSI64 => 0x55,
+            Self::GtUI64 => 0x56,
+            Self::LeSI64 => 0x57,
+            Self::LeUI64 => 0x58,
+            Self::GeSI64 => 0x59,
+            Self::GeUI64 => 0x5A,
+            Self::EqF32 => 0x5B,
+            Self::NeF32 => 0x5C,
+            Self::LtF32 => 0x5D,
+            Self::GtF32 => 0x5E,
+            Self::LeF32 => 0x5F,
+            Self::GeF32 => 0x60,
+            Self::EqF64 => 0x61,
+            Self::NeF64 => 0x62,
+            Self::LtF64 => 0x63,
+            Self::GtF64 => 0x64,
+            Self::LeF64 => 0x65,
+            Self::GeF64 => 0x66,
+            Self::ClzI32 => 0x67,
+            Self::CtzI32 => 0x68,
+            Self::PopcntI32 => 0x69,
+            Self::AddI32 => 0x6A,
+            Self::SubI32 => 0x6B,
+            Self::MulI32 => 0x6C,
+            Self::DivSI32 => 0x6D,
+            Self::DivUI32 => 0x6E,
+            Self::RemSI32 => 0x6F,
+            Self::RemUI32 => 0x70,
+            Self::AndI32 => 0x71,
+            Self::OrI32 => 0x72,
+            Self::XorI32 => 0x73,
+            Self::ShlI32 => 0x74,
+            Self::ShrSI32 => 0x75,
+            Self::ShrUI32 => 0x76,
+            Self::RotlI32 => 0x77,
+            Self::RotrI32 => 0x78,
+            Self::ClzI64 => 0x79,
+            Self::CtzI64 => 0x7A,
+            Self::PopcntI64 => 0x7B,
+            Self::AddI64 => 0x7C,
+            Self::SubI64 => 0x7D,
+            Self::MulI64 => 0x7E,
+            Self::DivSI64 => 0x7F,
+            Self::DivUI64 => 0x80,
+            Self::RemSI64 => 0x81,
+            Self::RemUI64 => 0x82,
+            Self::AndI64 => 0x83,
+            Self::OrI64 => 0x84,
+            Self::XorI64 => 0x85,
+            Self::ShlI64 => 0x86,
+            Self::ShrSI64 => 0x87,
+            Self::ShrUI64 => 0x88,
+            Self::RotlI64 => 0x89,
+            Self::RotrI64 => 0x8A,
+            Self::AbsF32 => 0x8B,
+            Self::NegF32 => 0x8C,
+            Self::CeilF32 => 0x8D,
+            Self::FloorF32 => 0x8E,
+            Self::TruncF32 => 0x8F,
+            Self::NearestF32 => 0x90,
+            Self::SqrtF32 => 0x91,
+            Self::AddF32 => 0x92,
+            Self::SubF32 => 0x93,
+            Self::MulF32 => 0x94,
+            Self::DivF32 => 0x95,
+            Self::MinF32 => 0x96,
+            Self::MaxF32 => 0x97,
+            Self::CopysignF32 => 0x98,
+            Self::AbsF64 => 0x99,
+            Self::NegF64 => 0x9A,
+            Self::CeilF64 => 0x9B,
+            Self::FloorF64 => 0x9C,
+            Self::TruncF64 => 0x9D,
+            Self::NearestF64 => 0x9E,
+            Self::SqrtF64 => 0x9F,
+            Self::AddF64 => 0xA0,
+            Self::SubF64 => 0xA1,
+            Self::MulF64 => 0xA2,
+            Self::DivF64 => 0xA3,
+            Self::MinF64 => 0xA4,
+            Self::MaxF64 => 0xA5,
+            Self::CopysignF64 => 0xA6,
+            Self::WrapI32I64 => 0xA7,
+            Self::TruncSI32F32 => 0xA8,
+            Self::TruncUI32F32 => 0xA9,
+            Self::TruncSI32F64 => 0xAA,
+            Self::TruncUI32F64 => 0xAB,
+            Self::ExtendSI64I32 => 0xAC,
+            Self::ExtendUI64I32 => 0xAD,
+            Self::TruncSI64F32 => 0xAE,
+            Self::TruncUI64F32 => 0xAF,
+            Self::TruncSI64F64 => 0xB0,
+            Self::TruncUI64F64 => 0xB1,
+            Self::ConvertSF32I32 => 0xB2,
+            Self::ConvertUF32I32 => 0xB3,
+            Self::ConvertSF32I64 => 0xB4,
+            Self::ConvertUF32I64 => 0xB5,
+            Self::DemoteF32F64 => 0xB6,
+            Self::ConvertSF64I32 => 0xB7,
+            Self::ConvertUF64I32 => 0xB8,
+            Self::ConvertSF64I64 => 0xB9,
+            Self::ConvertUF64I64 => 0xBA,
+            Self::PromoteF64F32 => 0xBB,
+            Self::ReinterpretI32F32 => 0xBC,
+            Self::ReinterpretI64F64 => 0xBD,
+            Self::ReinterpretF32I32 => 0xBE,
+            Self::ReinterpretF64I64 => 0xBF,
+            Self::Extend8SI32 => 0xC0,
+            Self::Extend16SI32 => 0xC1,
+            Self::Extend8SI64 => 0xC2,
+            Self::Extend16SI64 => 0xC3,
+            Self::Extend32SI64 => 0xC4,
+        });
+    }
 }
@@ -1,17 +1,51 @@
-use std::io::Cursor;
+use std::io::Read;
 
 use crate::decode;
 
-use super::parse::Result;
-use super::section::Section;
+use super::name::NameSection;
+use super::parse::{check_header, ParseError, Result};
+use super::section::{Section, SectionData, SectionOrder};
 
+#[derive(Debug)]
 pub struct Module {
-    pub magic_number: u32,
-    pub version: u32,
-    pub sections: Vec<Section>,
+    pub(crate) magic_number: u32,
+    pub(crate) version: u32,
+    pub(crate) sections: Vec<Section>,
 }
 
 impl Module {
+    pub fn magic_number(&self) -> u32 {
+        self.magic_number
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// 最初の "name" section をデコードする。なければ None
+    pub fn name_section(&self) -> Option<Result<NameSection>> {
+        self.sections
+            .iter()
+            .find_map(|section| match section.payload_data() {
+                SectionData::Custom(section) => section.name_section(),
+                _ => None,
+            })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&self.magic_number.to_le_bytes());
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        for section in &self.sections {
+            section.encode(&mut buf);
+        }
+        buf
+    }
+
     pub(crate) fn new(magic_number: u32, version: u32, sections: Vec<Section>) -> Self {
         Self {
             magic_number,
@@ -20,10 +54,81 @@ impl Module {
         }
     }
 
-    pub(crate) fn parse(data: &mut Cursor<&[u8]>) -> Result<Self> {
-        let magic_number = u32::from_le_bytes(decode::decode_32bit(data)?);
-        let version = u32::from_le_bytes(decode::decode_32bit(data)?);
-        let sections = Section::parse_multi(data)?;
+    pub(crate) fn parse<R: Read>(data: &mut R) -> Result<Self> {
+        let reader = ModuleReader::new(data)?;
+        let (magic_number, version) = (reader.magic_number, reader.version);
+        let sections = reader.collect::<Result<Vec<_>>>()?;
         Ok(Self::new(magic_number, version, sections))
     }
 }
+
+/// `Read` から section を 1 つずつ読み出す
+#[derive(Debug)]
+pub struct ModuleReader<R> {
+    reader: decode::OffsetReader<R>,
+    magic_number: u32,
+    version: u32,
+    order: SectionOrder,
+    // code section の関数の番号を import した関数の後から数えるため
+    imported_funcs: u32,
+    finished: bool,
+}
+
+impl<R: Read> ModuleReader<R> {
+    /// magic number と version を読み込む
+    pub fn new(reader: R) -> std::result::Result<Self, ParseError> {
+        let mut reader = decode::OffsetReader::new(reader, 0);
+        let mut header = || -> Result<_> {
+            let magic_number = u32::from_le_bytes(decode::decode_32bit(&mut reader)?);
+            let version = u32::from_le_bytes(decode::decode_32bit(&mut reader)?);
+            Ok((magic_number, version))
+        };
+        let (magic_number, version) = header().map_err(|e| e.located(reader.offset(), 0))?;
+        check_header(magic_number, version)?;
+        Ok(Self {
+            reader,
+            magic_number,
+            version,
+            order: SectionOrder::default(),
+            imported_funcs: 0,
+            finished: false,
+        })
+    }
+
+    pub fn magic_number(&self) -> u32 {
+        self.magic_number
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+impl<R: Read> Iterator for ModuleReader<R> {
+    type Item = std::result::Result<Section, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let start = self.reader.offset();
+        let result = Section::parse(&mut self.reader, self.imported_funcs)
+            .and_then(|section| match section {
+                Some(section) => self.order.check(section.id, start).map(|_| Some(section)),
+                None => Ok(None),
+            })
+            .transpose();
+        if let Some(Ok(Section {
+            payload_data: SectionData::Import(section),
+            ..
+        })) = &result
+        {
+            self.imported_funcs = section.func_count();
+        }
+        // 入力の終わりかエラーの後は読み進めない
+        if !matches!(result, Some(Ok(_))) {
+            self.finished = true;
+        }
+        result
+    }
+}
@@ -0,0 +1,222 @@
+// custom section "name" の中身
+//
+// name section が壊れていてもモジュールとしては正しいので、必要になった時にだけデコードする
+use std::collections::BTreeMap;
+
+use super::parse::{parse_name, parse_vec, ParseError, Result};
+use crate::decode;
+
+/// index から名前を引く表
+#[derive(Debug, Default, Clone)]
+pub struct NameMap {
+    pub(crate) names: BTreeMap<u32, Vec<u8>>,
+}
+
+impl NameMap {
+    pub fn get(&self, index: u32) -> Option<&[u8]> {
+        self.names.get(&index).map(Vec::as_slice)
+    }
+
+    /// index の昇順に並ぶ
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        self.names
+            .iter()
+            .map(|(index, name)| (*index, name.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let names = parse_vec(data, |data| {
+            let index = decode::decode_varint_u32(data)?;
+            Ok((index, parse_name(data)?))
+        })?;
+        Ok(Self {
+            names: names.into_iter().collect(),
+        })
+    }
+}
+
+/// 関数の index ごとの NameMap。local と label の名前に使う
+#[derive(Debug, Default, Clone)]
+pub struct IndirectNameMap {
+    pub(crate) maps: BTreeMap<u32, NameMap>,
+}
+
+impl IndirectNameMap {
+    pub fn get(&self, index: u32) -> Option<&NameMap> {
+        self.maps.get(&index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &NameMap)> {
+        self.maps.iter().map(|(index, map)| (*index, map))
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let maps = parse_vec(data, |data| {
+            let index = decode::decode_varint_u32(data)?;
+            Ok((index, NameMap::parse(data)?))
+        })?;
+        Ok(Self {
+            maps: maps.into_iter().collect(),
+        })
+    }
+}
+
+/// デコードした name section
+///
+/// 拡張された subsection (label, type, table, memory, global, elem, data) も読む。
+/// 知らない subsection は読み飛ばす
+#[derive(Debug, Default, Clone)]
+pub struct NameSection {
+    pub(crate) module: Option<Vec<u8>>,
+    pub(crate) funcs: NameMap,
+    pub(crate) locals: IndirectNameMap,
+    pub(crate) labels: IndirectNameMap,
+    pub(crate) types: NameMap,
+    pub(crate) tables: NameMap,
+    pub(crate) memories: NameMap,
+    pub(crate) globals: NameMap,
+    pub(crate) elems: NameMap,
+    pub(crate) data: NameMap,
+}
+
+impl NameSection {
+    pub fn module(&self) -> Option<&[u8]> {
+        self.module.as_deref()
+    }
+
+    pub fn funcs(&self) -> &NameMap {
+        &self.funcs
+    }
+
+    pub fn locals(&self) -> &IndirectNameMap {
+        &self.locals
+    }
+
+    /// label の index は関数の中で block/loop/if が現れた順に数える
+    pub fn labels(&self) -> &IndirectNameMap {
+        &self.labels
+    }
+
+    pub fn types(&self) -> &NameMap {
+        &self.types
+    }
+
+    pub fn tables(&self) -> &NameMap {
+        &self.tables
+    }
+
+    pub fn memories(&self) -> &NameMap {
+        &self.memories
+    }
+
+    pub fn globals(&self) -> &NameMap {
+        &self.globals
+    }
+
+    pub fn elems(&self) -> &NameMap {
+        &self.elems
+    }
+
+    pub fn data(&self) -> &NameMap {
+        &self.data
+    }
+
+    pub fn func(&self, func: u32) -> Option<&[u8]> {
+        self.funcs.get(func)
+    }
+
+    pub fn local(&self, func: u32, local: u32) -> Option<&[u8]> {
+        self.locals.get(func)?.get(local)
+    }
+
+    pub fn label(&self, func: u32, label: u32) -> Option<&[u8]> {
+        self.labels.get(func)?.get(label)
+    }
+
+    /// custom section "name" の payload (名前の後ろ) をデコードする
+    pub fn parse(mut data: &[u8]) -> Result<Self> {
+        let data = &mut data;
+        let mut section = Self::default();
+        while !data.is_empty() {
+            let [id] = decode::decode_8bit(data)?;
+            let len = decode::decode_varint_u32(data)?;
+            let subsection = &mut decode::decode_slice(data, len as usize)?;
+            match id {
+                0 => section.module = Some(parse_name(subsection)?),
+                1 => section.funcs = NameMap::parse(subsection)?,
+                2 => section.locals = IndirectNameMap::parse(subsection)?,
+                3 => section.labels = IndirectNameMap::parse(subsection)?,
+                4 => section.types = NameMap::parse(subsection)?,
+                5 => section.tables = NameMap::parse(subsection)?,
+                6 => section.memories = NameMap::parse(subsection)?,
+                7 => section.globals = NameMap::parse(subsection)?,
+                8 => section.elems = NameMap::parse(subsection)?,
+                9 => section.data = NameMap::parse(subsection)?,
+                _ => continue,
+            }
+            if !subsection.is_empty() {
+                return Err(ParseError::UnexpectedValue(format!(
+                    "name subsection id={} has {} trailing bytes",
+                    id,
+                    subsection.len()
+                )));
+            }
+        }
+        Ok(section)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_name_section() {
+        let payload: &[u8] = &[
+            0x00, 0x02, 0x01, b'm', // module
+            0x01, 0x06, 0x01, 0x00, 0x03, b'a', b'd', b'd', // funcs
+            0x02, 0x0d, 0x01, 0x00, 0x02, 0x00, 0x03, b'l', b'h', b's', 0x01, 0x03, b'r', b'h',
+            b's', // locals
+            0x03, 0x08, 0x01, 0x00, 0x01, 0x02, 0x03, b'o', b'u', b't', // labels
+            0x04, 0x04, 0x01, 0x00, 0x01, b't', // types
+            0x05, 0x04, 0x01, 0x00, 0x01, b'T', // tables
+            0x06, 0x06, 0x01, 0x00, 0x03, b'm', b'e', b'm', // memories
+            0x07, 0x04, 0x01, 0x01, 0x01, b'g', // globals
+            0x08, 0x04, 0x01, 0x00, 0x01, b'e', // elems
+            0x09, 0x04, 0x01, 0x02, 0x01, b'd', // data
+            0x0a, 0x01, 0xff, // 知らない subsection
+        ];
+        let names = NameSection::parse(payload).unwrap();
+        assert_eq!(names.module(), Some(&b"m"[..]));
+        assert_eq!(names.func(0), Some(&b"add"[..]));
+        assert_eq!(names.func(1), None);
+        assert_eq!(names.local(0, 0), Some(&b"lhs"[..]));
+        assert_eq!(names.local(0, 1), Some(&b"rhs"[..]));
+        assert_eq!(names.local(1, 0), None);
+        assert_eq!(names.label(0, 2), Some(&b"out"[..]));
+        assert_eq!(names.types().get(0), Some(&b"t"[..]));
+        assert_eq!(names.tables().get(0), Some(&b"T"[..]));
+        assert_eq!(names.memories().get(0), Some(&b"mem"[..]));
+        assert_eq!(names.globals().get(1), Some(&b"g"[..]));
+        assert_eq!(names.elems().get(0), Some(&b"e"[..]));
+        assert_eq!(names.data().get(2), Some(&b"d"[..]));
+        let locals: Vec<(u32, &[u8])> = names.locals().get(0).unwrap().iter().collect();
+        assert_eq!(locals, [(0, &b"lhs"[..]), (1, &b"rhs"[..])]);
+    }
+
+    #[test]
+    fn test_parse_name_section_error() {
+        // subsection の長さより名前が長い
+        assert!(NameSection::parse(&[0x01, 0x03, 0x01, 0x00, 0x05]).is_err());
+        // subsection の後ろにバイトが残っている
+        assert!(NameSection::parse(&[0x00, 0x03, 0x01, b'm', 0x00]).is_err());
+    }
+}
@@ -1,34 +1,267 @@
 use thiserror::Error;
 
 use crate::decode;
-use std::convert::TryFrom;
 #[derive(Error, Debug)]
-pub(crate) enum ParseError {
+#[non_exhaustive]
+pub enum ParseError {
     #[error("faild to decode: {0}")]
     Decode(#[from] decode::DecodeError),
     #[error("faild to convert: {0}")]
     Convert(#[from] std::num::TryFromIntError),
     #[error("read failed: {0}")]
     Read(#[from] std::io::Error),
+    #[error("invalid module header. magic_number=0x{magic_number:0>8x}, version={version}")]
+    InvalidHeader { magic_number: u32, version: u32 },
     #[error("id={0} is unexpected section id.")]
     UnexpectedSectionId(u8),
     #[error("unexpected value in {title}. got=0x{got:0>2x}")]
     UnexpectedByteValue { title: String, got: u8 },
+    #[error("too many locals. got={0}")]
+    TooManyLocals(u64),
     #[error("unexpected value. {0}")]
     UnexpectedValue(String),
+    #[error("section size mismatch. id={id}, payload_len={payload_len}")]
+    SectionSizeMismatch { id: u8, payload_len: usize },
+    #[error("duplicate section. id={0}")]
+    DuplicateSection(u8),
+    #[error("section id={id} must be placed before section id={after}")]
+    UnexpectedSectionOrder { id: u8, after: u8 },
+    #[error("{context}: {source}")]
+    Context {
+        context: ErrorContext,
+        source: Box<ParseError>,
+    },
+}
+
+impl ParseError {
+    /// エラーが起きた場所。分からない場合は None
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// 場所の情報を除いたエラー
+    pub fn kind(&self) -> &ParseError {
+        match self {
+            Self::Context { source, .. } => source,
+            _ => self,
+        }
+    }
+
+    fn update_context<F: FnOnce(&mut ErrorContext)>(self, f: F) -> Self {
+        let (mut context, source) = match self {
+            Self::Context { context, source } => (context, source),
+            e => (ErrorContext::default(), Box::new(e)),
+        };
+        f(&mut context);
+        Self::Context { context, source }
+    }
+
+    // 内側で付けた情報を優先するため、まだ付いていない情報だけを付ける
+    pub(crate) fn in_section(self, id: u8) -> Self {
+        self.update_context(|c| {
+            c.section_id.get_or_insert(id);
+        })
+    }
+
+    pub(crate) fn in_function(self, index: u32) -> Self {
+        self.update_context(|c| {
+            c.func_index.get_or_insert(index);
+        })
+    }
+
+    // remaining は命令の先頭で残っていたバイト数
+    pub(crate) fn in_instruction(self, index: u32, remaining: usize) -> Self {
+        self.update_context(|c| {
+            c.instr_index.get_or_insert(index);
+            c.offset.get_or_insert(Offset::Remaining(remaining));
+        })
+    }
+
+    /// 読んでいたバッファの終わりの絶対位置 end を使って、エラーの位置を絶対位置に直す
+    ///
+    /// 位置がまだ分かっていない場合は、エラーの時点で読み終えていた位置とする
+    /// 1 バイトの値が不正な場合は、読んだばかりのそのバイトの位置とする
+    pub(crate) fn located(self, end: usize, remaining: usize) -> Self {
+        let read_byte = matches!(self.kind(), Self::UnexpectedByteValue { .. });
+        self.update_context(|c| {
+            let offset = match c.offset {
+                Some(Offset::Absolute(offset)) => offset,
+                Some(Offset::Remaining(r)) => end - r,
+                None if read_byte => end - remaining - 1,
+                None => end - remaining,
+            };
+            c.offset = Some(Offset::Absolute(offset));
+        })
+    }
+}
+
+/// エラーが起きた場所
+///
+/// `Display` は "offset 0x1a3f in code section, func 12, instr 3" の形になる
+#[derive(Debug, Default)]
+pub struct ErrorContext {
+    offset: Option<Offset>,
+    section_id: Option<u8>,
+    func_index: Option<u32>,
+    instr_index: Option<u32>,
+}
+
+// 内側のパーサーは入力全体での位置を知らないので、
+// 絶対位置が分かる層までバッファの終わりからの距離で持っておく
+#[derive(Debug, Clone, Copy)]
+enum Offset {
+    Remaining(usize),
+    Absolute(usize),
+}
+
+impl ErrorContext {
+    /// 入力の先頭からのバイト数
+    pub fn offset(&self) -> Option<usize> {
+        match self.offset {
+            Some(Offset::Absolute(offset)) => Some(offset),
+            _ => None,
+        }
+    }
+
+    pub fn section_id(&self) -> Option<u8> {
+        self.section_id
+    }
+
+    /// モジュールの関数の番号。import した関数も数える
+    pub fn func_index(&self) -> Option<u32> {
+        self.func_index
+    }
+
+    /// 式の先頭から数えた命令の番号。block の中の命令や else, end も数える
+    pub fn instr_index(&self) -> Option<u32> {
+        self.instr_index
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut place = Vec::new();
+        if let Some(offset) = self.offset() {
+            place.push(format!("offset 0x{:x}", offset));
+        }
+        if let Some(id) = self.section_id {
+            place.push(format!("in {} section", section_name(id)));
+        }
+        let mut parts = vec![place.join(" ")];
+        if let Some(index) = self.func_index {
+            parts.push(format!("func {}", index));
+        }
+        if let Some(index) = self.instr_index {
+            parts.push(format!("instr {}", index));
+        }
+        parts.retain(|s| !s.is_empty());
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+fn section_name(id: u8) -> String {
+    match id {
+        0 => "custom",
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data count",
+        _ => return format!("unknown (id={})", id),
+    }
+    .to_string()
 }
 
 pub(crate) type Result<T> = std::result::Result<T, ParseError>;
 
-pub(super) fn parse_vec<F, T>(data: &mut &[u8], func: F) -> Result<Vec<T>>
+// "\0asm" を little endian で読んだ値
+const MAGIC_NUMBER: u32 = 0x6d736100;
+const VERSION: u32 = 1;
+
+// magic number と version を確かめる。エラーの位置は header の先頭にする
+pub(super) fn check_header(magic_number: u32, version: u32) -> Result<()> {
+    if magic_number != MAGIC_NUMBER || version != VERSION {
+        let e = ParseError::InvalidHeader {
+            magic_number,
+            version,
+        };
+        return Err(e.located(0, 0));
+    }
+    Ok(())
+}
+
+pub(super) fn parse_vec<'a, F, T>(data: &mut &'a [u8], func: F) -> Result<Vec<T>>
 where
-    F: Fn(&mut &[u8]) -> Result<T>,
+    F: Fn(&mut &'a [u8]) -> Result<T>,
     T: Sized,
 {
-    let num = u32::try_from(decode::decode_varint(data)?)?;
+    let num = decode::decode_varint_u32(data)?;
     let mut v = Vec::new();
     for _ in 0..num {
         v.push(func(data)?);
     }
     Ok(v)
 }
+
+// 長さを前に付けたバイト列。data segment の初期値など
+pub(super) fn parse_bytes(data: &mut &[u8]) -> Result<Vec<u8>> {
+    let len = decode::decode_varint_u32(data)?;
+    Ok(decode::decode_len(data, len as usize)?)
+}
+
+pub(super) fn parse_bytes_ref<'a>(data: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let len = decode::decode_varint_u32(data)?;
+    Ok(decode::decode_slice(data, len as usize)?)
+}
+
+// name はバイト列と同じ形で書かれる。UTF-8 かどうかは確かめない
+pub(super) fn parse_name(data: &mut &[u8]) -> Result<Vec<u8>> {
+    parse_bytes(data)
+}
+
+pub(super) fn parse_name_ref<'a>(data: &mut &'a [u8]) -> Result<&'a [u8]> {
+    parse_bytes_ref(data)
+}
+
+// section の payload をちょうど読み切ったかを検査する
+// payload が余った場合も、payload を越えて読もうとした場合も size mismatch とする
+// offset は payload の先頭の絶対位置で、エラーには section と位置を付ける
+pub(super) fn parse_payload<'a, F, T>(
+    payload: &'a [u8],
+    id: u8,
+    offset: usize,
+    func: F,
+) -> Result<T>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T>,
+{
+    let data = &mut { payload };
+    let mismatch = ParseError::SectionSizeMismatch {
+        id,
+        payload_len: payload.len(),
+    };
+    let result = match func(data) {
+        Ok(_) if !data.is_empty() => Err(mismatch),
+        Err(e)
+            if matches!(
+                e.kind(),
+                ParseError::Decode(decode::DecodeError::UnexpectedEof)
+            ) =>
+        {
+            *data = &[];
+            Err(mismatch)
+        }
+        result => result,
+    };
+    result.map_err(|e| e.in_section(id).located(offset + payload.len(), data.len()))
+}
@@ -1,166 +1,1030 @@
 use super::{
     instruction,
-    parse::{parse_vec, ParseError, Result},
-    wasm_type::{self, FunctionType},
+    name::NameSection,
+    parse::{parse_bytes, parse_name, parse_payload, parse_vec, ParseError, Result},
+    wasm_type::{self, FunctionType, GlobalType, MemoryType, ReferenceType, TableType},
 };
-use crate::decode;
-use std::convert::TryFrom;
-use std::io::{Cursor, Seek, SeekFrom};
+use crate::{decode, encode};
+use std::io::Read;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
+#[derive(Debug)]
 pub struct Section {
-    pub id: u8,
-    pub payload_len: u32,
-    pub payload_data: SectionData,
+    pub(crate) id: u8,
+    pub(crate) payload_len: u32,
+    pub(crate) payload_data: SectionData,
+    // payload_data を encode しても読んだバイト列に戻らない場合だけ持つ
+    pub(crate) original: Option<Original>,
+}
+
+// 最短でない LEB128 を含む section の、読んだ時の payload と長さの LEB128 のバイト数
+#[derive(Debug)]
+pub(crate) struct Original {
+    len_width: usize,
+    payload: Arc<Vec<u8>>,
 }
 
 impl Section {
-    pub(crate) fn parse(data: &mut Cursor<&[u8]>) -> Result<Self> {
-        let id = u8::try_from(decode::decode_varint(data)?)?;
+    pub fn id(&self) -> u8 {
+        self.id
+    }
 
-        let payload_len = u32::try_from(decode::decode_varint(data)?)?;
+    pub fn payload_len(&self) -> u32 {
+        self.payload_len
+    }
 
-        let payload_data = SectionData::parse(data, id, payload_len as usize)?;
+    pub fn payload_data(&self) -> &SectionData {
+        &self.payload_data
+    }
 
-        Ok(Self {
+    // section の境界で入力が終わっている場合は None を返す
+    // imported_funcs はそれまでの import section で import した関数の数
+    pub(crate) fn parse<R: Read>(
+        data: &mut decode::OffsetReader<R>,
+        imported_funcs: u32,
+    ) -> Result<Option<Self>> {
+        let start = data.offset();
+        let header = |data: &mut decode::OffsetReader<R>| -> Result<_> {
+            let id = match decode::decode_8bit_or_eof(data)? {
+                Some(id) => id,
+                None => return Ok(None),
+            };
+            check_id(id, start)?;
+            Ok(Some((id, decode::decode_varint_u32(data)?)))
+        };
+        let (id, payload_len) = match header(data) {
+            Ok(Some(header)) => header,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(e.located(data.offset(), 0)),
+        };
+
+        let len_width = data.offset() - start - 1;
+        let (payload, payload_data) =
+            SectionData::parse(data, id, payload_len as usize, imported_funcs)?;
+
+        let mut section = Self {
             id,
             payload_len,
             payload_data,
-        })
+            original: None,
+        };
+        section.keep_original(payload, len_width);
+        Ok(Some(section))
+    }
+
+    // 最短の LEB128 で書き直すと入力と変わる場合は、入力のバイト列を encode で使う
+    fn keep_original(&mut self, payload: Arc<Vec<u8>>, len_width: usize) {
+        let mut encoded = vec![];
+        self.payload_data.encode(&mut encoded);
+        let mut len = vec![];
+        encode::encode_varint_u32(&mut len, payload.len() as u32);
+        if encoded != *payload || len.len() != len_width {
+            self.original = Some(Original { len_width, payload });
+        }
+    }
+
+    /// 読み込んだ section は、LEB128 の幅も含めて読んだ時のバイト列のまま書く
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.id);
+        match &self.original {
+            Some(original) => {
+                let len = original.payload.len() as u32;
+                encode::encode_varint_u32_padded(buf, len, original.len_width);
+                buf.extend_from_slice(&original.payload);
+            }
+            None => {
+                let mut payload = Vec::new();
+                self.payload_data.encode(&mut payload);
+                encode::encode_len(buf, &payload);
+            }
+        }
+    }
+}
+
+// 知らない id は payload の長さを読む前に、id のバイトの位置で報告する
+pub(super) fn check_id(id: u8, offset: usize) -> Result<()> {
+    if id != 0 && !SECTION_ORDER.contains(&id) {
+        return Err(ParseError::UnexpectedSectionId(id).located(offset, 0));
     }
+    Ok(())
+}
+
+// custom section 以外の section が置かれる順番
+// data count section は code section より前に置く
+const SECTION_ORDER: [u8; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 12, 10, 11];
+
+/// section の順番と重複を検査する
+///
+/// custom section はどこに何度置いてもよい
+#[derive(Default, Debug)]
+pub(crate) struct SectionOrder {
+    last: Option<u8>,
+}
 
-    pub(crate) fn parse_multi(data: &mut Cursor<&[u8]>) -> Result<Vec<Self>> {
-        let current_position = data.position();
-        let end = data.seek(SeekFrom::End(0))?;
-        data.seek(SeekFrom::Start(current_position))?;
-        let mut v = Vec::new();
-        while end > data.position() {
-            let value = Self::parse(data)?;
-            v.push(value);
+impl SectionOrder {
+    // offset は section の先頭の絶対位置で、エラーの位置に使う
+    pub(crate) fn check(&mut self, id: u8, offset: usize) -> Result<()> {
+        self.check_order(id)
+            .map_err(|e| e.in_section(id).located(offset, 0))
+    }
+
+    fn check_order(&mut self, id: u8) -> Result<()> {
+        if id == 0 {
+            return Ok(());
         }
-        Ok(v)
+        let position = |id| SECTION_ORDER.iter().position(|&i| i == id);
+        let current = position(id).ok_or(ParseError::UnexpectedSectionId(id))?;
+        if let Some(last) = self.last {
+            if last == id {
+                return Err(ParseError::DuplicateSection(id));
+            }
+            if position(last) > Some(current) {
+                return Err(ParseError::UnexpectedSectionOrder { id, after: last });
+            }
+        }
+        self.last = Some(id);
+        Ok(())
     }
 }
 
+#[derive(Debug)]
 pub enum SectionData {
     Custom(CustomSection),
     Type(TypeSection),
-    Import,
+    Import(ImportSection),
     Function(FunctionSection),
-    Table,
-    Memory,
-    Global,
+    Table(TableSection),
+    Memory(MemorySection),
+    Global(GlobalSection),
     Export(ExportSection),
-    Start,
-    Element,
+    Start(StartSection),
+    Element(ElementSection),
     Code(CodeSection),
-    Data,
-    DataCount,
+    Data(DataSection),
+    DataCount(DataCountSection),
 }
 
 impl SectionData {
-    fn parse(data: &mut Cursor<&[u8]>, id: u8, payload_len: usize) -> Result<Self> {
-        let payload_data = decode::decode_len(data, payload_len)?;
-        match id {
-            0 => Ok(Self::Custom(CustomSection {})), // custom section は何もしない
-            1 => Ok(Self::Type(TypeSection::parse(
-                &mut payload_data.as_slice(),
-            )?)),
-            3 => Ok(Self::Function(FunctionSection::parse(
-                &mut payload_data.as_slice(),
-            )?)),
-            7 => Ok(Self::Export(ExportSection::parse(
-                &mut payload_data.as_slice(),
-            )?)),
+    fn parse<R: Read>(
+        data: &mut decode::OffsetReader<R>,
+        id: u8,
+        payload_len: usize,
+        imported_funcs: u32,
+    ) -> Result<(Arc<Vec<u8>>, Self)> {
+        let offset = data.offset();
+        let payload_data = decode::decode_len(data, payload_len)
+            .map_err(|e| ParseError::from(e).in_section(id).located(data.offset(), 0))?;
+        let payload_data = Arc::new(payload_data);
+        let section = parse_payload(&payload_data, id, offset, |data| match id {
+            0 => Ok(Self::Custom(CustomSection::parse(data)?)),
+            1 => Ok(Self::Type(TypeSection::parse(data)?)),
+            2 => Ok(Self::Import(ImportSection::parse(data)?)),
+            3 => Ok(Self::Function(FunctionSection::parse(data)?)),
+            4 => Ok(Self::Table(TableSection::parse(data)?)),
+            5 => Ok(Self::Memory(MemorySection::parse(data)?)),
+            6 => Ok(Self::Global(GlobalSection::parse(data)?)),
+            7 => Ok(Self::Export(ExportSection::parse(data)?)),
+            8 => Ok(Self::Start(StartSection::parse(data)?)),
+            9 => Ok(Self::Element(ElementSection::parse(data)?)),
             10 => Ok(Self::Code(CodeSection::parse(
-                &mut payload_data.as_slice(),
+                &payload_data,
+                offset,
+                imported_funcs,
+                data,
             )?)),
+            11 => Ok(Self::Data(DataSection::parse(data)?)),
+            12 => Ok(Self::DataCount(DataCountSection::parse(data)?)),
             _ => Err(ParseError::UnexpectedSectionId(id)),
+        })?;
+        Ok((payload_data, section))
+    }
+
+    /// section id と payload の長さを除いた payload を書き込む
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Custom(section) => section.encode(buf),
+            Self::Type(section) => section.encode(buf),
+            Self::Import(section) => section.encode(buf),
+            Self::Function(section) => section.encode(buf),
+            Self::Table(section) => section.encode(buf),
+            Self::Memory(section) => section.encode(buf),
+            Self::Global(section) => section.encode(buf),
+            Self::Export(section) => section.encode(buf),
+            Self::Start(section) => section.encode(buf),
+            Self::Element(section) => section.encode(buf),
+            Self::Code(section) => section.encode(buf),
+            Self::Data(section) => section.encode(buf),
+            Self::DataCount(section) => section.encode(buf),
         }
     }
 }
-pub struct CustomSection {}
+// 中身は解釈せず、名前と残りのバイト列をそのまま持つ
+#[derive(Debug)]
+pub struct CustomSection {
+    pub(crate) name: Vec<u8>,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl CustomSection {
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// "name" section であれば中身をデコードする
+    pub fn name_section(&self) -> Option<Result<NameSection>> {
+        (self.name == b"name").then(|| NameSection::parse(&self.payload))
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let name = parse_name(data)?;
+        let payload = std::mem::take(data).to_vec();
+        Ok(Self { name, payload })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_len(buf, &self.name);
+        buf.extend_from_slice(&self.payload);
+    }
+}
 
+#[derive(Debug)]
 pub struct TypeSection {
-    pub funcs: Vec<FunctionType>,
+    pub(crate) funcs: Vec<FunctionType>,
 }
 impl TypeSection {
-    fn parse(data: &mut &[u8]) -> Result<Self> {
-        let v = parse_vec(data, |data| FunctionType::parse(data))?;
+    pub fn funcs(&self) -> &[FunctionType] {
+        &self.funcs
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let v = parse_vec(data, FunctionType::parse)?;
         Ok(Self { funcs: v })
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.funcs, |buf, t| t.encode(buf));
+    }
+}
+#[derive(Debug)]
+pub struct ImportSection {
+    pub(crate) imports: Vec<Import>,
+}
+impl ImportSection {
+    pub fn imports(&self) -> &[Import] {
+        &self.imports
+    }
+
+    // import した関数の数。code section の関数の番号はこの後から数える
+    pub(crate) fn func_count(&self) -> u32 {
+        count_funcs(self.imports.iter().map(|i| &i.desc))
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let v = parse_vec(data, Import::parse)?;
+        Ok(Self { imports: v })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.imports, |buf, i| i.encode(buf));
+    }
+}
+#[derive(Debug)]
+pub struct Import {
+    pub(crate) module: Vec<u8>,
+    pub(crate) name: Vec<u8>,
+    pub(crate) desc: ImportDesc,
+}
+
+impl Import {
+    pub fn module(&self) -> &[u8] {
+        &self.module
+    }
+
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    pub fn desc(&self) -> &ImportDesc {
+        &self.desc
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let module = parse_name(data)?;
+        let name = parse_name(data)?;
+        let desc = ImportDesc::parse(data)?;
+        Ok(Self { module, name, desc })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_len(buf, &self.module);
+        encode::encode_len(buf, &self.name);
+        self.desc.encode(buf);
+    }
+}
+
+pub(super) fn count_funcs<'a>(descs: impl Iterator<Item = &'a ImportDesc>) -> u32 {
+    descs
+        .filter(|desc| matches!(desc, ImportDesc::TypeIndex(_)))
+        .count() as u32
+}
+
+#[derive(Debug)]
+pub enum ImportDesc {
+    TypeIndex(u32),
+    Table(TableType),
+    Memory(MemoryType),
+    Global(GlobalType),
+}
+
+impl ImportDesc {
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        match decode::decode_8bit(data)?[0] {
+            0x00 => Ok(Self::TypeIndex(decode::decode_varint_u32(data)?)),
+            0x01 => Ok(Self::Table(TableType::parse(data)?)),
+            0x02 => Ok(Self::Memory(MemoryType::parse(data)?)),
+            0x03 => Ok(Self::Global(GlobalType::parse(data)?)),
+            invalid => Err(ParseError::UnexpectedByteValue {
+                title: "importdesc".to_string(),
+                got: invalid,
+            }),
+        }
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::TypeIndex(index) => {
+                buf.push(0x00);
+                encode::encode_varint_u32(buf, *index);
+            }
+            Self::Table(table_type) => {
+                buf.push(0x01);
+                table_type.encode(buf);
+            }
+            Self::Memory(memory_type) => {
+                buf.push(0x02);
+                memory_type.encode(buf);
+            }
+            Self::Global(global_type) => {
+                buf.push(0x03);
+                global_type.encode(buf);
+            }
+        }
+    }
 }
+
+#[derive(Debug)]
 pub struct FunctionSection {
-    pub indexies: Vec<u32>,
+    pub(crate) indexies: Vec<u32>,
 }
 
 impl FunctionSection {
-    fn parse(data: &mut &[u8]) -> Result<Self> {
-        let v = parse_vec(data, |data| {
-            Ok(u32::try_from(decode::decode_varint(data)?)?)
-        })?;
+    pub fn indexies(&self) -> &[u32] {
+        &self.indexies
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let v = parse_vec(data, |data| Ok(decode::decode_varint_u32(data)?))?;
         Ok(Self { indexies: v })
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.indexies, |buf, i| {
+            encode::encode_varint_u32(buf, *i)
+        });
+    }
+}
+#[derive(Debug)]
+pub struct TableSection {
+    pub(crate) tables: Vec<TableType>,
+}
+
+impl TableSection {
+    pub fn tables(&self) -> &[TableType] {
+        &self.tables
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let v = parse_vec(data, TableType::parse)?;
+        Ok(Self { tables: v })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.tables, |buf, t| t.encode(buf));
+    }
+}
+#[derive(Debug)]
+pub struct MemorySection {
+    pub(crate) memories: Vec<MemoryType>,
+}
+
+impl MemorySection {
+    pub fn memories(&self) -> &[MemoryType] {
+        &self.memories
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let v = parse_vec(data, MemoryType::parse)?;
+        Ok(Self { memories: v })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.memories, |buf, m| m.encode(buf));
+    }
+}
+#[derive(Debug)]
+pub struct GlobalSection {
+    pub(crate) globals: Vec<Global>,
+}
+
+impl GlobalSection {
+    pub fn globals(&self) -> &[Global] {
+        &self.globals
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let v = parse_vec(data, Global::parse)?;
+        Ok(Self { globals: v })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.globals, |buf, g| g.encode(buf));
+    }
+}
+#[derive(Debug)]
+pub struct Global {
+    pub(crate) global_type: GlobalType,
+    pub(crate) init: instruction::Expression,
+}
+
+impl Global {
+    pub fn global_type(&self) -> &GlobalType {
+        &self.global_type
+    }
+
+    pub fn init(&self) -> &instruction::Expression {
+        &self.init
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let global_type = GlobalType::parse(data)?;
+        let init = instruction::Expression::parse(data)?;
+        Ok(Self { global_type, init })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        self.global_type.encode(buf);
+        self.init.encode(buf);
+    }
+}
+#[derive(Debug)]
+pub struct StartSection {
+    pub(crate) func_index: u32,
+}
+
+impl StartSection {
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let func_index = decode::decode_varint_u32(data)?;
+        Ok(Self { func_index })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_varint_u32(buf, self.func_index);
+    }
 }
+#[derive(Debug)]
+pub struct ElementSection {
+    pub(crate) elements: Vec<Element>,
+}
+
+impl ElementSection {
+    pub fn elements(&self) -> &[Element] {
+        &self.elements
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let v = parse_vec(data, Element::parse)?;
+        Ok(Self { elements: v })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.elements, |buf, e| e.encode(buf));
+    }
+}
+#[derive(Debug)]
+pub struct Element {
+    pub(crate) element_type: ReferenceType,
+    pub(crate) init: ElementInit,
+    pub(crate) mode: ElementMode,
+    // 読んだ時の flag。table 0 の funcref の active segment は flag 0 と 2 のどちらでも書ける
+    pub(crate) flag: u32,
+}
+
+impl Element {
+    pub fn element_type(&self) -> &ReferenceType {
+        &self.element_type
+    }
+
+    pub fn init(&self) -> &ElementInit {
+        &self.init
+    }
+
+    pub fn mode(&self) -> &ElementMode {
+        &self.mode
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        // bit 0: passive または declarative
+        // bit 1: active なら table index を明示、そうでなければ declarative
+        // bit 2: 初期値が function index ではなく expression
+        let flag = decode::decode_varint_u32(data)?;
+        if flag > 7 {
+            return Err(ParseError::UnexpectedValue(format!(
+                "element segment flag must be 0..=7. got={}",
+                flag
+            )));
+        }
+        let mode = match flag & 0b011 {
+            0b000 => ElementMode::Active {
+                table: 0,
+                offset: instruction::Expression::parse(data)?,
+            },
+            0b001 => ElementMode::Passive,
+            0b010 => ElementMode::Active {
+                table: decode::decode_varint_u32(data)?,
+                offset: instruction::Expression::parse(data)?,
+            },
+            _ => ElementMode::Declarative,
+        };
+        let uses_expression = flag & 0b100 != 0;
+        // flag 0, 4 は elemkind/reftype を持たず funcref 固定
+        let element_type = if flag & 0b011 == 0 {
+            ReferenceType::FunctionRef
+        } else if uses_expression {
+            let [by] = decode::decode_8bit(data)?;
+            ReferenceType::new(by).ok_or_else(|| ParseError::UnexpectedByteValue {
+                title: "reference type".to_string(),
+                got: by,
+            })?
+        } else {
+            match decode::decode_8bit(data)?[0] {
+                0x00 => ReferenceType::FunctionRef,
+                invalid => {
+                    return Err(ParseError::UnexpectedByteValue {
+                        title: "elemkind".to_string(),
+                        got: invalid,
+                    })
+                }
+            }
+        };
+        let init = if uses_expression {
+            ElementInit::Expressions(parse_vec(data, |data| {
+                instruction::Expression::parse(data)
+            })?)
+        } else {
+            ElementInit::FuncIndexies(parse_vec(data, |data| {
+                Ok(decode::decode_varint_u32(data)?)
+            })?)
+        };
+        Ok(Self {
+            element_type,
+            init,
+            mode,
+            flag,
+        })
+    }
+
+    // flag は parse と同じ意味。読んだ時に省略されていた table index と elemkind/reftype は省略する
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let uses_expression = matches!(self.init, ElementInit::Expressions(_));
+        let mut flag = match (&self.mode, &self.element_type) {
+            (ElementMode::Active { table: 0, .. }, ReferenceType::FunctionRef)
+                if self.flag & 0b011 == 0b010 =>
+            {
+                0b010
+            }
+            (ElementMode::Active { table: 0, .. }, ReferenceType::FunctionRef) => 0b000,
+            (ElementMode::Active { .. }, _) => 0b010,
+            (ElementMode::Passive, _) => 0b001,
+            (ElementMode::Declarative, _) => 0b011,
+        };
+        if uses_expression {
+            flag |= 0b100;
+        }
+        encode::encode_varint_u32(buf, flag);
+        if let ElementMode::Active { table, offset } = &self.mode {
+            if flag & 0b010 != 0 {
+                encode::encode_varint_u32(buf, *table);
+            }
+            offset.encode(buf);
+        }
+        if flag & 0b011 != 0 {
+            if uses_expression {
+                self.element_type.encode(buf);
+            } else {
+                // elemkind は funcref を表す 0x00 のみ
+                buf.push(0x00);
+            }
+        }
+        match &self.init {
+            ElementInit::FuncIndexies(indexies) => {
+                encode::encode_vec(buf, indexies, |buf, i| encode::encode_varint_u32(buf, *i))
+            }
+            ElementInit::Expressions(exprs) => {
+                encode::encode_vec(buf, exprs, |buf, e| e.encode(buf))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ElementInit {
+    FuncIndexies(Vec<u32>),
+    Expressions(Vec<instruction::Expression>),
+}
+
+#[derive(Debug)]
+pub enum ElementMode {
+    Passive,
+    Active {
+        table: u32,
+        offset: instruction::Expression,
+    },
+    Declarative,
+}
+
+#[derive(Debug)]
 pub struct CodeSection {
-    pub codes: Vec<Code>,
+    pub(crate) codes: Vec<Code>,
 }
 impl CodeSection {
-    fn parse(data: &mut &[u8]) -> Result<Self> {
-        let v = parse_vec(data, |data| {
-            let len = decode::decode_varint(data)?;
-            let data = decode::decode_len(data, len as usize)?;
-            Code::parse(&mut data.as_slice())
+    pub fn codes(&self) -> &[Code] {
+        &self.codes
+    }
+
+    /// 全ての関数本体をデコードする
+    pub fn decode_all(&self) -> Result<()> {
+        for c in &self.codes {
+            c.decode()?;
+        }
+        Ok(())
+    }
+
+    /// 全ての関数本体を `std::thread::scope` で並列にデコードする
+    ///
+    /// エラーがあった場合は関数インデックスが最も小さいもののエラーを返す
+    /// 型の検証はしない。検証もする場合は `validator::validate_parallel` を使う
+    pub fn decode_all_parallel(&self) -> Result<()> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if threads <= 1 || self.codes.len() <= 1 {
+            return self.decode_all();
+        }
+        let chunk_size = self.codes.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .codes
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().try_for_each(|c| c.decode().map(|_| ())))
+                })
+                .collect();
+            // chunk の順に結果を見るので、最初に見つかったエラーが最も小さいインデックスのもの
+            handles
+                .into_iter()
+                .try_for_each(|h| h.join().expect("decode thread panicked"))
+        })
+    }
+
+    // 関数本体は payload 中の範囲だけ記録しておき、使われるときにデコードする
+    // offset は payload の先頭の絶対位置
+    fn parse(
+        payload: &Arc<Vec<u8>>,
+        offset: usize,
+        imported_funcs: u32,
+        data: &mut &[u8],
+    ) -> Result<Self> {
+        let mut v = parse_vec(data, |data| {
+            let len = decode::decode_varint_u32(data)?;
+            let start = payload.len() - data.len();
+            decode::decode_slice(data, len as usize)?;
+            Ok(Code {
+                bytes: Arc::clone(payload),
+                range: start..start + len as usize,
+                offset: offset + start,
+                index: 0,
+                decoded: OnceLock::new(),
+            })
         })?;
+        for (i, c) in v.iter_mut().enumerate() {
+            c.index = imported_funcs + i as u32;
+        }
         Ok(Self { codes: v })
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.codes, |buf, c| c.encode(buf));
+    }
 }
 pub struct Code {
-    pub(super) locals: Vec<wasm_type::ValueType>,
-    pub(super) expression: instruction::Expression,
+    pub(crate) bytes: Arc<Vec<u8>>,
+    pub(crate) range: Range<usize>,
+    // エラーの位置を出すための、関数本体の先頭の絶対位置と import した関数も数えた関数の番号
+    pub(crate) offset: usize,
+    pub(crate) index: u32,
+    pub(crate) decoded: OnceLock<DecodedCode>,
+}
+
+#[derive(Debug)]
+pub(crate) struct DecodedCode {
+    pub(crate) locals: Vec<wasm_type::ValueType>,
+    pub(crate) expression: instruction::Expression,
 }
 
+// 巨大な local 数を宣言してメモリを使い果たすモジュールを弾くための上限
+const MAX_LOCALS: u64 = 50000;
+
 impl Code {
+    /// locals の宣言を含む、デコード前の関数本体
+    pub fn body(&self) -> &[u8] {
+        &self.bytes[self.range.clone()]
+    }
+
+    pub fn locals(&self) -> Result<&[wasm_type::ValueType]> {
+        Ok(&self.decode()?.locals)
+    }
+
+    pub fn expression(&self) -> Result<&instruction::Expression> {
+        Ok(&self.decode()?.expression)
+    }
+
+    // 初回のみデコードし、結果を保持する
+    fn decode(&self) -> Result<&DecodedCode> {
+        if let Some(decoded) = self.decoded.get() {
+            return Ok(decoded);
+        }
+        let body = self.body();
+        let data = &mut { body };
+        let decoded = DecodedCode::parse(data).map_err(|e| {
+            e.in_function(self.index)
+                .in_section(10)
+                .located(self.offset + body.len(), data.len())
+        })?;
+        // 他のスレッドが先にデコードしていた場合はそちらを使う
+        let _ = self.decoded.set(decoded);
+        Ok(self.decoded.get().unwrap())
+    }
+
+    /// 関数本体はデコード前のバイト列をそのまま書く
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_len(buf, self.body());
+    }
+}
+
+// 関数本体のバイト列はモジュール全体を共有しているので、範囲だけを出す
+impl std::fmt::Debug for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Code")
+            .field("range", &self.range)
+            .field("offset", &self.offset)
+            .field("index", &self.index)
+            .field("decoded", &self.decoded)
+            .finish()
+    }
+}
+
+impl DecodedCode {
     fn parse(data: &mut &[u8]) -> Result<Self> {
-        let locals = parse_vec(data, |data| wasm_type::ValueType::parse(data))?;
+        let locals = parse_locals(data)?;
         let expression = instruction::Expression::parse(data)?;
+        if !data.is_empty() {
+            return Err(ParseError::UnexpectedValue(format!(
+                "{} bytes remain after end of expression",
+                data.len()
+            )));
+        }
         Ok(Self { locals, expression })
     }
 }
 
+pub(super) fn parse_locals(data: &mut &[u8]) -> Result<Vec<wasm_type::ValueType>> {
+    // locals は (個数, 型) の組の vector で圧縮されている
+    let runs = parse_vec(data, |data| {
+        let n = decode::decode_varint_u32(data)?;
+        let t = wasm_type::ValueType::parse(data)?;
+        Ok((n, t))
+    })?;
+    let total = runs.iter().map(|(n, _)| *n as u64).sum();
+    if total > MAX_LOCALS {
+        return Err(ParseError::TooManyLocals(total));
+    }
+    let mut locals = Vec::with_capacity(total as usize);
+    for (n, t) in runs {
+        locals.extend(std::iter::repeat_n(t, n as usize));
+    }
+    Ok(locals)
+}
+
+#[derive(Debug)]
+pub struct DataSection {
+    pub(crate) data: Vec<Data>,
+}
+
+impl DataSection {
+    pub fn data(&self) -> &[Data] {
+        &self.data
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let v = parse_vec(data, Data::parse)?;
+        Ok(Self { data: v })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.data, |buf, d| d.encode(buf));
+    }
+}
+#[derive(Debug)]
+pub struct Data {
+    pub(crate) init: Vec<u8>,
+    pub(crate) mode: DataMode,
+}
+
+impl Data {
+    pub fn init(&self) -> &[u8] {
+        &self.init
+    }
+
+    pub fn mode(&self) -> &DataMode {
+        &self.mode
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let mode = DataMode::parse(data)?;
+        let init = parse_bytes(data)?;
+        Ok(Self { init, mode })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        self.mode.encode(buf);
+        encode::encode_len(buf, &self.init);
+    }
+}
+
+#[derive(Debug)]
+pub enum DataMode {
+    Passive,
+    Active {
+        memory: u32,
+        offset: instruction::Expression,
+        /// 読んだ時の flag。memory 0 は memory index を省略した 0 と明示した 2 のどちらでも書ける
+        flag: u32,
+    },
+}
+
+impl DataMode {
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        match decode::decode_varint_u32(data)? {
+            0 => Ok(Self::Active {
+                memory: 0,
+                offset: instruction::Expression::parse(data)?,
+                flag: 0,
+            }),
+            1 => Ok(Self::Passive),
+            2 => Ok(Self::Active {
+                memory: decode::decode_varint_u32(data)?,
+                offset: instruction::Expression::parse(data)?,
+                flag: 2,
+            }),
+            invalid => Err(ParseError::UnexpectedValue(format!(
+                "data segment flag must be 0..=2. got={}",
+                invalid
+            ))),
+        }
+    }
+
+    // memory 0 への active segment は、読んだ時に省略されていれば memory index を省略した flag 0 で書く
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Active {
+                memory: 0,
+                offset,
+                flag: 0,
+            } => {
+                buf.push(0x00);
+                offset.encode(buf);
+            }
+            Self::Passive => buf.push(0x01),
+            Self::Active { memory, offset, .. } => {
+                buf.push(0x02);
+                encode::encode_varint_u32(buf, *memory);
+                offset.encode(buf);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DataCountSection {
+    pub(crate) count: u32,
+}
+
+impl DataCountSection {
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let count = decode::decode_varint_u32(data)?;
+        Ok(Self { count })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_varint_u32(buf, self.count);
+    }
+}
+
+#[derive(Debug)]
 pub struct ExportSection {
-    pub exports: Vec<Export>,
+    pub(crate) exports: Vec<Export>,
 }
 impl ExportSection {
+    pub fn exports(&self) -> &[Export] {
+        &self.exports
+    }
+
     fn parse(data: &mut &[u8]) -> Result<Self> {
-        let v = parse_vec(data, |data| Export::parse(data))?;
+        let v = parse_vec(data, Export::parse)?;
         Ok(Self { exports: v })
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.exports, |buf, e| e.encode(buf));
+    }
 }
+#[derive(Debug)]
 pub struct Export {
-    pub name: Vec<u8>,
-    pub desc: ExportDesc,
+    pub(crate) name: Vec<u8>,
+    pub(crate) desc: ExportDesc,
 }
 
 impl Export {
-    fn parse(data: &mut &[u8]) -> Result<Self> {
-        let len = decode::decode_varint(data)?;
-        let name = decode::decode_len(data, len as usize)?;
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
 
-        let desc = match u8::try_from(decode::decode_varint(data)?)? {
-            0x00 => Ok(ExportDesc::FuncIndex(decode::decode_varint(data)? as u32)),
-            0x01 => Ok(ExportDesc::FuncIndex(decode::decode_varint(data)? as u32)),
-            0x02 => Ok(ExportDesc::FuncIndex(decode::decode_varint(data)? as u32)),
-            invalid => Err(ParseError::UnexpectedByteValue {
-                title: "exportdesc".to_string(),
-                got: invalid,
-            }),
-        }?;
+    pub fn desc(&self) -> &ExportDesc {
+        &self.desc
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        let name = parse_name(data)?;
+        let desc = ExportDesc::parse(data)?;
         Ok(Self { name, desc })
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_len(buf, &self.name);
+        self.desc.encode(buf);
+    }
 }
 
+#[derive(Debug)]
 pub enum ExportDesc {
     FuncIndex(u32),
     TableIndex(u32),
     MemIndex(u32),
     GlobalIndex(u32),
 }
+
+impl ExportDesc {
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        match decode::decode_8bit(data)?[0] {
+            0x00 => Ok(Self::FuncIndex(decode::decode_varint_u32(data)?)),
+            0x01 => Ok(Self::TableIndex(decode::decode_varint_u32(data)?)),
+            0x02 => Ok(Self::MemIndex(decode::decode_varint_u32(data)?)),
+            0x03 => Ok(Self::GlobalIndex(decode::decode_varint_u32(data)?)),
+            invalid => Err(ParseError::UnexpectedByteValue {
+                title: "exportdesc".to_string(),
+                got: invalid,
+            }),
+        }
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let (by, index) = match self {
+            Self::FuncIndex(index) => (0x00, index),
+            Self::TableIndex(index) => (0x01, index),
+            Self::MemIndex(index) => (0x02, index),
+            Self::GlobalIndex(index) => (0x03, index),
+        };
+        buf.push(by);
+        encode::encode_varint_u32(buf, *index);
+    }
+}
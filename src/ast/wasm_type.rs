@@ -1,22 +1,31 @@
 use super::parse::{parse_vec, ParseError, Result};
-use crate::decode;
-use std::convert::TryFrom;
+use crate::{decode, encode};
+#[derive(Debug)]
 pub enum Type {
     Function(FunctionType),
     Result(ResultType),
     Value(ValueType),
     Number(NumberType),
+    Vector(VectorType),
     Reference(ReferenceType),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct FunctionType {
-    pub params_types: ResultType,
-    pub return_types: ResultType,
+    pub(crate) params_types: ResultType,
+    pub(crate) return_types: ResultType,
 }
 impl FunctionType {
+    pub fn params_types(&self) -> &ResultType {
+        &self.params_types
+    }
+
+    pub fn return_types(&self) -> &ResultType {
+        &self.return_types
+    }
+
     pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
-        let x = u8::try_from(decode::decode_varint(data)?)?;
+        let [x] = decode::decode_8bit(data)?;
         if x != 0x60 {
             return Err(ParseError::UnexpectedByteValue {
                 title: "function type".to_string(),
@@ -30,41 +39,68 @@ impl FunctionType {
             return_types,
         })
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(0x60);
+        self.params_types.encode(buf);
+        self.return_types.encode(buf);
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ResultType {
-    pub(super) valu_types: Vec<ValueType>,
+    pub(crate) valu_types: Vec<ValueType>,
 }
 impl ResultType {
+    pub fn valu_types(&self) -> &[ValueType] {
+        &self.valu_types
+    }
+
     fn parse(data: &mut &[u8]) -> Result<Self> {
-        let v = parse_vec(data, |data| ValueType::parse(data))?;
+        let v = parse_vec(data, ValueType::parse)?;
         Ok(Self { valu_types: v })
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        encode::encode_vec(buf, &self.valu_types, |buf, t| t.encode(buf));
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum ValueType {
     Number(NumberType),
+    Vector(VectorType),
     Reference(ReferenceType),
 }
 impl ValueType {
-    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
-        let by = u8::try_from(decode::decode_varint(data)?)?;
+    pub(super) fn new(by: u8) -> Option<Self> {
         if let Some(num_type) = NumberType::new(by) {
-            Ok(Self::Number(num_type))
-        } else if let Some(ref_type) = ReferenceType::new(by) {
-            Ok(Self::Reference(ref_type))
+            Some(Self::Number(num_type))
+        } else if let Some(vec_type) = VectorType::new(by) {
+            Some(Self::Vector(vec_type))
         } else {
-            Err(ParseError::UnexpectedByteValue {
-                title: "value type".to_string(),
-                got: by,
-            })
+            ReferenceType::new(by).map(Self::Reference)
+        }
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let [by] = decode::decode_8bit(data)?;
+        Self::new(by).ok_or_else(|| ParseError::UnexpectedByteValue {
+            title: "value type".to_string(),
+            got: by,
+        })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Number(t) => t.encode(buf),
+            Self::Vector(t) => t.encode(buf),
+            Self::Reference(t) => t.encode(buf),
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum NumberType {
     I32,
     I64,
@@ -81,19 +117,200 @@ impl NumberType {
             _ => None,
         }
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(match self {
+            Self::I32 => 0x7f,
+            Self::I64 => 0x7E,
+            Self::F32 => 0x7D,
+            Self::F64 => 0x7C,
+        });
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
+pub enum VectorType {
+    V128,
+}
+impl VectorType {
+    fn new(by: u8) -> Option<Self> {
+        match by {
+            0x7B => Some(Self::V128),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::V128 => buf.push(0x7B),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum ReferenceType {
     FunctionRef,
     ExternRef,
 }
 impl ReferenceType {
-    fn new(by: u8) -> Option<Self> {
+    pub(super) fn new(by: u8) -> Option<Self> {
         match by {
             0x70 => Some(Self::FunctionRef),
             0x6f => Some(Self::ExternRef),
             _ => None,
         }
     }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(match self {
+            Self::FunctionRef => 0x70,
+            Self::ExternRef => 0x6f,
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub(crate) min: u32,
+    pub(crate) max: Option<u32>,
+}
+impl Limits {
+    pub fn min(&self) -> u32 {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<u32> {
+        self.max
+    }
+
+    fn parse(data: &mut &[u8]) -> Result<Self> {
+        match decode::decode_8bit(data)?[0] {
+            0x00 => Ok(Self {
+                min: decode::decode_varint_u32(data)?,
+                max: None,
+            }),
+            0x01 => Ok(Self {
+                min: decode::decode_varint_u32(data)?,
+                max: Some(decode::decode_varint_u32(data)?),
+            }),
+            invalid => Err(ParseError::UnexpectedByteValue {
+                title: "limits".to_string(),
+                got: invalid,
+            }),
+        }
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self.max {
+            None => {
+                buf.push(0x00);
+                encode::encode_varint_u32(buf, self.min);
+            }
+            Some(max) => {
+                buf.push(0x01);
+                encode::encode_varint_u32(buf, self.min);
+                encode::encode_varint_u32(buf, max);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TableType {
+    pub(crate) element_type: ReferenceType,
+    pub(crate) limits: Limits,
+}
+impl TableType {
+    pub fn element_type(&self) -> &ReferenceType {
+        &self.element_type
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let [by] = decode::decode_8bit(data)?;
+        let element_type =
+            ReferenceType::new(by).ok_or_else(|| ParseError::UnexpectedByteValue {
+                title: "reference type".to_string(),
+                got: by,
+            })?;
+        let limits = Limits::parse(data)?;
+        Ok(Self {
+            element_type,
+            limits,
+        })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        self.element_type.encode(buf);
+        self.limits.encode(buf);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryType {
+    pub(crate) limits: Limits,
+}
+impl MemoryType {
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let limits = Limits::parse(data)?;
+        Ok(Self { limits })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        self.limits.encode(buf);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GlobalType {
+    pub(crate) value_type: ValueType,
+    pub(crate) mutability: Mutability,
+}
+impl GlobalType {
+    pub fn value_type(&self) -> &ValueType {
+        &self.value_type
+    }
+
+    pub fn mutability(&self) -> &Mutability {
+        &self.mutability
+    }
+
+    pub(super) fn parse(data: &mut &[u8]) -> Result<Self> {
+        let value_type = ValueType::parse(data)?;
+        let mutability = match decode::decode_8bit(data)?[0] {
+            0x00 => Mutability::Const,
+            0x01 => Mutability::Var,
+            invalid => {
+                return Err(ParseError::UnexpectedByteValue {
+                    title: "mutability".to_string(),
+                    got: invalid,
+                })
+            }
+        };
+        Ok(Self {
+            value_type,
+            mutability,
+        })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        self.value_type.encode(buf);
+        buf.push(match self.mutability {
+            Mutability::Const => 0x00,
+            Mutability::Var => 0x01,
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Mutability {
+    Const,
+    Var,
 }
@@ -1,48 +1,142 @@
+use std::io::Read;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
-pub(crate) enum DecodeError {
-    #[error("unexpected format. end come after MSB is 0")]
-    UnexpectFormat,
+#[non_exhaustive]
+pub enum DecodeError {
+    #[error("unexpected end of data")]
+    UnexpectedEof,
+    #[error("integer representation too long. expected {0}bit integer")]
+    IntegerRepresentationTooLong(u32),
+    #[error("integer too large. expected {0}bit integer")]
+    IntegerTooLarge(u32),
     #[error("no expected type value. got={0}")]
     UnexpectedWireDataValue(u128),
     #[error("read failed: {0}")]
-    FailedToRead(#[from] std::io::Error),
+    FailedToRead(std::io::Error),
+}
+
+impl From<std::io::Error> for DecodeError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => Self::UnexpectedEof,
+            _ => Self::FailedToRead(e),
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, DecodeError>;
 
-pub(crate) fn decode_varint<T: std::io::Read>(data: &mut T) -> Result<u64> {
-    // とりあえずu64で読んで、その後工程で varuint32へ変換できるかどうか見る方針
+pub(crate) fn decode_varint_u32<T: std::io::Read>(data: &mut T) -> Result<u32> {
+    Ok(decode_unsigned(data, 32)? as u32)
+}
+
+// wasm に符号なし 64 bit の LEB128 はないので、decode_unsigned の検証にだけ使う
+#[cfg(test)]
+fn decode_varint_u64<T: std::io::Read>(data: &mut T) -> Result<u64> {
+    decode_unsigned(data, 64)
+}
+
+pub(crate) fn decode_varint_s32<T: std::io::Read>(data: &mut T) -> Result<i32> {
+    Ok(decode_signed(data, 32)? as i32)
+}
+
+pub(crate) fn decode_varint_s33<T: std::io::Read>(data: &mut T) -> Result<i64> {
+    decode_signed(data, 33)
+}
+
+pub(crate) fn decode_varint_s64<T: std::io::Read>(data: &mut T) -> Result<i64> {
+    decode_signed(data, 64)
+}
+
+// N bit の値は最大 ceil(N/7) バイトで表現される
+fn max_len(bits: u32) -> u32 {
+    bits.div_ceil(7)
+}
+
+// 最終バイトのうち値として使われるビット数
+fn used_bits_of_last(bits: u32) -> u32 {
+    bits - 7 * (max_len(bits) - 1)
+}
 
+fn decode_unsigned<T: std::io::Read>(data: &mut T, bits: u32) -> Result<u64> {
+    let max_len = max_len(bits);
     let mut sum = 0;
-    let mut loop_count = 0;
-    loop {
-        let mut buf = [0; 1];
-        let result = data.read_exact(&mut buf);
-        if result.is_err() {
-            break Err(DecodeError::UnexpectFormat);
-        }
+    for count in 0..max_len {
+        let [by] = decode_8bit(data)?;
         // MSB は後続のバイトが続くかどうかの判定に使われる
         // 1 の場合、後続が続く
-        let top = buf[0] & 0b10000000;
-        let buf: u64 = (buf[0] & 0b01111111) as u64;
+        let top = by & 0b10000000;
+        let payload = by & 0b01111111;
+        if count == max_len - 1 {
+            if top != 0 {
+                return Err(DecodeError::IntegerRepresentationTooLong(bits));
+            }
+            if payload >> used_bits_of_last(bits) != 0 {
+                return Err(DecodeError::IntegerTooLarge(bits));
+            }
+        }
         // little endian
-        let buf = buf << (7 * loop_count);
-        sum += buf;
-        loop_count += 1;
-        if top != 0b10000000 {
-            break Ok(sum);
+        sum |= (payload as u64) << (7 * count);
+        if top == 0 {
+            break;
+        }
+    }
+    Ok(sum)
+}
+
+fn decode_signed<T: std::io::Read>(data: &mut T, bits: u32) -> Result<i64> {
+    let max_len = max_len(bits);
+    let mut sum: i64 = 0;
+    let mut shift = 0;
+    for count in 0..max_len {
+        let [by] = decode_8bit(data)?;
+        let top = by & 0b10000000;
+        let payload = by & 0b01111111;
+        if count == max_len - 1 {
+            if top != 0 {
+                return Err(DecodeError::IntegerRepresentationTooLong(bits));
+            }
+            // 最終バイトの未使用ビットは符号ビットと同じ値でなければならない
+            let used = used_bits_of_last(bits);
+            let rest = payload >> (used - 1);
+            if rest != 0 && rest != 0b01111111 >> (used - 1) {
+                return Err(DecodeError::IntegerTooLarge(bits));
+            }
+        }
+        sum |= (payload as i64) << shift;
+        shift += 7;
+        if top == 0 {
+            // 符号拡張
+            if shift < 64 && payload & 0b01000000 != 0 {
+                sum |= -1 << shift;
+            }
+            break;
         }
     }
+    Ok(sum)
 }
 
 pub(crate) fn decode_len<T: std::io::Read>(data: &mut T, len: usize) -> Result<Vec<u8>> {
-    let mut buf = vec![0; len];
-    data.read_exact(&mut buf)?;
+    // 長さは入力に書かれた値なので、先に len 分確保せず読めた分だけ確保する
+    let mut buf = Vec::new();
+    data.by_ref().take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(DecodeError::UnexpectedEof);
+    }
     Ok(buf)
 }
 
+// 入力がメモリ上にある場合はコピーせずに切り出す
+pub(crate) fn decode_slice<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
 fn decode_nbit<T: std::io::Read, const SIZE: usize>(data: &mut T) -> Result<[u8; SIZE]> {
     let mut buf = [0; SIZE];
     data.read_exact(&mut buf)?;
@@ -52,9 +146,159 @@ fn decode_nbit<T: std::io::Read, const SIZE: usize>(data: &mut T) -> Result<[u8;
 pub(crate) fn decode_8bit<T: std::io::Read>(data: &mut T) -> Result<[u8; 1]> {
     decode_nbit(data)
 }
-pub(crate) fn decode_16bit<T: std::io::Read>(data: &mut T) -> Result<[u8; 2]> {
-    decode_nbit(data)
+// 1 byte も読めずに入力が終わった場合は None を返す
+pub(crate) fn decode_8bit_or_eof<T: std::io::Read>(data: &mut T) -> Result<Option<u8>> {
+    let mut buf = [0; 1];
+    loop {
+        match data.read(&mut buf) {
+            Ok(0) => break Ok(None),
+            Ok(_) => break Ok(Some(buf[0])),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => break Err(e.into()),
+        }
+    }
 }
 pub(crate) fn decode_32bit<T: std::io::Read>(data: &mut T) -> Result<[u8; 4]> {
     decode_nbit(data)
 }
+pub(crate) fn decode_64bit<T: std::io::Read>(data: &mut T) -> Result<[u8; 8]> {
+    decode_nbit(data)
+}
+pub(crate) fn decode_128bit<T: std::io::Read>(data: &mut T) -> Result<[u8; 16]> {
+    decode_nbit(data)
+}
+
+// 読み込んだバイト数を数えて、入力の先頭からの位置が分かるようにする
+#[derive(Debug)]
+pub(crate) struct OffsetReader<R> {
+    reader: R,
+    offset: usize,
+}
+
+impl<R: Read> OffsetReader<R> {
+    pub(crate) fn new(reader: R, offset: usize) -> Self {
+        Self { reader, offset }
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_varint_signed() {
+        let cases: &[(&[u8], i32)] = &[
+            (&[0x00], 0),
+            (&[0x2a], 42),
+            (&[0x7f], -1),
+            (&[0x80, 0x7f], -128),
+            (&[0xff, 0x00], 127),
+            (&[0xff, 0xff, 0xff, 0xff, 0x07], i32::MAX),
+            (&[0x80, 0x80, 0x80, 0x80, 0x78], i32::MIN),
+        ];
+        for (input, expected) in cases {
+            let got = decode_varint_s32(&mut &input[..]).unwrap();
+            assert_eq!(got, *expected);
+        }
+
+        let cases: &[(&[u8], i64)] = &[
+            (&[0x56], -42),
+            (&[0x80, 0x80, 0x80, 0x80, 0x08], 1 << 31),
+            (
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00],
+                i64::MAX,
+            ),
+            (
+                &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f],
+                i64::MIN,
+            ),
+        ];
+        for (input, expected) in cases {
+            let got = decode_varint_s64(&mut &input[..]).unwrap();
+            assert_eq!(got, *expected);
+        }
+    }
+
+    #[test]
+    fn test_decode_varint_signed_overflow() {
+        let cases: &[&[u8]] = &[
+            // 未使用ビットが符号と一致しない
+            &[0xff, 0xff, 0xff, 0xff, 0x0f],
+            &[0x80, 0x80, 0x80, 0x80, 0x70],
+        ];
+        for input in cases {
+            let got = decode_varint_s32(&mut &input[..]);
+            assert!(matches!(got, Err(DecodeError::IntegerTooLarge(32))));
+        }
+        let input: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        let got = decode_varint_s64(&mut &input[..]);
+        assert!(matches!(got, Err(DecodeError::IntegerTooLarge(64))));
+    }
+
+    #[test]
+    fn test_decode_varint_unsigned() {
+        let cases: &[(&[u8], u32)] = &[
+            (&[0x00], 0),
+            (&[0x7f], 127),
+            (&[0xe5, 0x8e, 0x26], 624485),
+            // 冗長な表現でも最大長以内なら許容される
+            (&[0x83, 0x80, 0x80, 0x80, 0x00], 3),
+            (&[0xff, 0xff, 0xff, 0xff, 0x0f], u32::MAX),
+        ];
+        for (input, expected) in cases {
+            let got = decode_varint_u32(&mut &input[..]).unwrap();
+            assert_eq!(got, *expected);
+        }
+        let input: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert_eq!(decode_varint_u64(&mut &input[..]).unwrap(), u64::MAX);
+
+        // s33 は blocktype の型インデックスに使われる
+        let input: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert_eq!(decode_varint_s33(&mut &input[..]).unwrap(), u32::MAX as i64);
+        let input: &[u8] = &[0x40];
+        assert_eq!(decode_varint_s33(&mut &input[..]).unwrap(), -64);
+    }
+
+    #[test]
+    fn test_decode_varint_malformed() {
+        let input: &[u8] = &[0x80, 0x80, 0x80, 0x80, 0x80, 0x00];
+        let got = decode_varint_u32(&mut &input[..]);
+        assert!(matches!(
+            got,
+            Err(DecodeError::IntegerRepresentationTooLong(32))
+        ));
+        let got = decode_varint_s32(&mut &input[..]);
+        assert!(matches!(
+            got,
+            Err(DecodeError::IntegerRepresentationTooLong(32))
+        ));
+
+        let input: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0x1f];
+        let got = decode_varint_u32(&mut &input[..]);
+        assert!(matches!(got, Err(DecodeError::IntegerTooLarge(32))));
+        let input: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02];
+        let got = decode_varint_u64(&mut &input[..]);
+        assert!(matches!(got, Err(DecodeError::IntegerTooLarge(64))));
+        let input: &[u8] = &[0x80, 0x80, 0x80, 0x80, 0x50];
+        let got = decode_varint_s33(&mut &input[..]);
+        assert!(matches!(got, Err(DecodeError::IntegerTooLarge(33))));
+
+        let input: &[u8] = &[0x80, 0x80];
+        let got = decode_varint_u32(&mut &input[..]);
+        assert!(matches!(got, Err(DecodeError::UnexpectedEof)));
+        let got = decode_32bit(&mut &input[..]);
+        assert!(matches!(got, Err(DecodeError::UnexpectedEof)));
+    }
+}
@@ -0,0 +1,132 @@
+// decode モジュールの逆。値を最短の表現で buf の末尾に書き込む
+
+pub(crate) fn encode_varint_u32(buf: &mut Vec<u8>, value: u32) {
+    encode_unsigned(buf, value as u64)
+}
+
+pub(crate) fn encode_varint_s32(buf: &mut Vec<u8>, value: i32) {
+    encode_signed(buf, value as i64)
+}
+
+pub(crate) fn encode_varint_s33(buf: &mut Vec<u8>, value: i64) {
+    encode_signed(buf, value)
+}
+
+pub(crate) fn encode_varint_s64(buf: &mut Vec<u8>, value: i64) {
+    encode_signed(buf, value)
+}
+
+fn encode_unsigned(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let by = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(by);
+            break;
+        }
+        buf.push(by | 0x80);
+    }
+}
+
+fn encode_signed(buf: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let by = (value & 0x7f) as u8;
+        // 算術シフトなので、残りが符号ビットだけになったら終わり
+        value >>= 7;
+        let sign_bit = by & 0x40 != 0;
+        if (value == 0 && !sign_bit) || (value == -1 && sign_bit) {
+            buf.push(by);
+            break;
+        }
+        buf.push(by | 0x80);
+    }
+}
+
+// 最短の表現より長い width バイトで書く。読んだ時の幅を保つのに使う
+pub(crate) fn encode_varint_u32_padded(buf: &mut Vec<u8>, value: u32, width: usize) {
+    let mut value = value as u64;
+    for i in 0..width {
+        let by = (value & 0x7f) as u8;
+        value >>= 7;
+        buf.push(if i + 1 < width { by | 0x80 } else { by });
+    }
+}
+
+// 長さを前に付けたバイト列。name や data segment の初期値で使う
+pub(crate) fn encode_len(buf: &mut Vec<u8>, bytes: &[u8]) {
+    encode_varint_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(bytes);
+}
+
+pub(crate) fn encode_vec<T, F>(buf: &mut Vec<u8>, items: &[T], func: F)
+where
+    F: Fn(&mut Vec<u8>, &T),
+{
+    encode_varint_u32(buf, items.len() as u32);
+    for item in items {
+        func(buf, item);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decode;
+
+    #[test]
+    fn test_encode_varint() {
+        let cases: &[(u32, &[u8])] = &[
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (u32::MAX, &[0xff, 0xff, 0xff, 0xff, 0x0f]),
+        ];
+        for (value, expected) in cases {
+            let mut buf = Vec::new();
+            encode_varint_u32(&mut buf, *value);
+            assert_eq!(&buf, expected);
+        }
+
+        let cases: &[(i32, &[u8])] = &[
+            (0, &[0x00]),
+            (42, &[0x2a]),
+            (-1, &[0x7f]),
+            (-128, &[0x80, 0x7f]),
+            (127, &[0xff, 0x00]),
+            (i32::MIN, &[0x80, 0x80, 0x80, 0x80, 0x78]),
+        ];
+        for (value, expected) in cases {
+            let mut buf = Vec::new();
+            encode_varint_s32(&mut buf, *value);
+            assert_eq!(&buf, expected);
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_varint() {
+        for value in [0, 1, 63, 64, -64, -65, i64::MAX, i64::MIN] {
+            let mut buf = Vec::new();
+            encode_varint_s64(&mut buf, value);
+            assert_eq!(
+                decode::decode_varint_s64(&mut buf.as_slice()).unwrap(),
+                value
+            );
+        }
+        for value in [0, -1, (1 << 32) - 1, -(1 << 32)] {
+            let mut buf = Vec::new();
+            encode_varint_s33(&mut buf, value);
+            assert_eq!(
+                decode::decode_varint_s33(&mut buf.as_slice()).unwrap(),
+                value
+            );
+        }
+        for value in [0, 1 << 31, u32::MAX] {
+            let mut buf = Vec::new();
+            encode_varint_u32(&mut buf, value);
+            assert_eq!(
+                decode::decode_varint_u32(&mut buf.as_slice()).unwrap(),
+                value
+            );
+        }
+    }
+}
@@ -1,9 +1,23 @@
-use std::{collections::HashMap, hash::Hash};
+mod vector;
+
+use std::collections::HashMap;
+
+use thiserror::Error;
 
 use crate::{
     ast::{module, section},
     object::{self, instance, value},
 };
+
+#[derive(Error, Debug, PartialEq)]
+enum Trap {
+    #[error("out of bounds memory access")]
+    OutOfBoundsMemoryAccess,
+    // 検証済みの関数では起きない
+    #[error("operand stack underflow")]
+    StackUnderflow,
+}
+
 struct Store {
     funcs: Vec<instance::FunctionInstance>,
 }
@@ -23,26 +37,35 @@ impl Executor {
             match s.payload_data {
                 crate::ast::section::SectionData::Custom(_) => {} // do noting
                 crate::ast::section::SectionData::Type(t) => typ = Some(t),
-                crate::ast::section::SectionData::Import => {}
+                crate::ast::section::SectionData::Import(_) => {}
                 crate::ast::section::SectionData::Function(f) => func = Some(f),
-                crate::ast::section::SectionData::Table => {}
-                crate::ast::section::SectionData::Memory => {}
-                crate::ast::section::SectionData::Global => {}
+                crate::ast::section::SectionData::Table(_) => {}
+                crate::ast::section::SectionData::Memory(_) => {}
+                crate::ast::section::SectionData::Global(_) => {}
                 crate::ast::section::SectionData::Export(ex) => {
                     for e in ex.exports {
-                        if let section::ExportDesc::FuncIndex(index) = e.desc {
-                            exp.insert(
-                                String::from_utf8(e.name).unwrap(),
-                                object::value::ExternVal::FuncAddr(index),
-                            );
-                        }
+                        let val = match e.desc {
+                            section::ExportDesc::FuncIndex(index) => {
+                                object::value::ExternVal::Func(index)
+                            }
+                            section::ExportDesc::TableIndex(index) => {
+                                object::value::ExternVal::Table(index)
+                            }
+                            section::ExportDesc::MemIndex(index) => {
+                                object::value::ExternVal::Mem(index)
+                            }
+                            section::ExportDesc::GlobalIndex(index) => {
+                                object::value::ExternVal::Global(index)
+                            }
+                        };
+                        exp.insert(String::from_utf8(e.name).unwrap(), val);
                     }
                 }
-                crate::ast::section::SectionData::Start => {}
-                crate::ast::section::SectionData::Element => {}
+                crate::ast::section::SectionData::Start(_) => {}
+                crate::ast::section::SectionData::Element(_) => {}
                 crate::ast::section::SectionData::Code(c) => code = Some(c),
-                crate::ast::section::SectionData::Data => {}
-                crate::ast::section::SectionData::DataCount => {}
+                crate::ast::section::SectionData::Data(_) => {}
+                crate::ast::section::SectionData::DataCount(_) => {}
             }
         }
 
@@ -51,7 +74,7 @@ impl Executor {
             funcs = f
                 .indexies
                 .into_iter()
-                .zip(c.codes.into_iter())
+                .zip(c.codes)
                 .map(|(f_index, code)| (ty.funcs.get(f_index as usize).unwrap(), code))
                 .map(|(f, c)| instance::FunctionInstance::new(f.clone(), c))
                 .collect();
@@ -63,25 +86,16 @@ impl Executor {
             export_map: exp,
         }
     }
-    fn invoke(&self, param: Parameter) {
-        // TODO 戻り値最高
-        let extern_val = self.export_map.get(&param.func_name);
-        if extern_val.is_some() {
-            println!("exist")
-        } else {
-            println!("no exist")
+    // TODO 実行して戻り値を返す。引数の型が一致するかも確認する
+    fn invoke(&self, param: Parameter) -> Option<&instance::FunctionInstance> {
+        match self.export_map.get(&param.func_name)? {
+            value::ExternVal::Func(index) => self.store.funcs.get(*index as usize),
+            _ => None,
         }
+    }
 
-        let extern_val = extern_val.unwrap(); //FIXME とりあえず
-
-        let value::ExternVal::FuncAddr(index) = extern_val;
-        let f = self.store.funcs.get(*index as usize);
-        if f.is_some() {
-            println!("exist")
-        } else {
-            println!("no exist")
-        }
-        // TODO 引数も一bimyou ni致するか確認
+    fn export(&self, name: &str) -> Option<&value::ExternVal> {
+        self.export_map.get(name)
     }
 }
 
@@ -100,6 +114,7 @@ mod test {
     use std::io::Cursor;
 
     use super::{Executor, Parameter};
+    use crate::object::value::ExternVal;
     #[test]
     fn call() {
         let input: &[u8] = &[
@@ -126,7 +141,46 @@ mod test {
         let input = &mut Cursor::new(input);
         let module = crate::ast::module::Module::parse(input).unwrap();
         let exe = Executor::new(module);
-        let param = Parameter::new("add".to_string());
-        exe.invoke(param);
+        assert!(exe.invoke(Parameter::new("add".to_string())).is_some());
+        assert!(exe.invoke(Parameter::new("sub".to_string())).is_none());
+    }
+
+    #[test]
+    fn export() {
+        // (module
+        //   (func $f)
+        //   (table $t 1 funcref)
+        //   (memory $m 1)
+        //   (global $g i32 (i32.const 0))
+        //   (export "f" (func $f))
+        //   (export "t" (table $t))
+        //   (export "memory" (memory $m))
+        //   (export "g" (global $g))
+        // )
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, // magic number
+            0x01, 0x00, 0x00, 0x00, // version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x01, // table section
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, // global section
+            // export section
+            0x07, 0x16, // id, length
+            0x04, // number of element
+            0x01, 0x66, 0x00, 0x00, // func
+            0x01, 0x74, 0x01, 0x00, // table
+            0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, // memory
+            0x01, 0x67, 0x03, 0x00, // global
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+        ];
+        let input = &mut Cursor::new(input);
+        let module = crate::ast::module::Module::parse(input).unwrap();
+        let exe = Executor::new(module);
+        assert!(matches!(exe.export("f"), Some(ExternVal::Func(0))));
+        assert!(matches!(exe.export("t"), Some(ExternVal::Table(0))));
+        assert!(matches!(exe.export("memory"), Some(ExternVal::Mem(0))));
+        assert!(matches!(exe.export("g"), Some(ExternVal::Global(0))));
+        assert!(exe.export("none").is_none());
     }
 }
@@ -0,0 +1,803 @@
+// 0xFD prefix の SIMD 命令の実行
+//
+// v128 を u128 のまま持ち、lane ごとのスカラーのループで計算する。lane 0 が最下位のバイト
+// スタックの値は全て u128 で、i32/i64 は符号なしに直した値、f32/f64 はビット列を入れる
+use crate::ast::instruction::{MemArg, VectorInstruction};
+
+use super::Trap;
+
+type Result<T> = std::result::Result<T, Trap>;
+
+// lane の値。BITS 幅のビット列と相互に変換する
+trait Lane: Copy {
+    const BITS: u32;
+    fn from_bits(bits: u64) -> Self;
+    fn to_bits(self) -> u64;
+}
+
+macro_rules! int_lane {
+    ($($t:ty),*) => {
+        $(
+            impl Lane for $t {
+                const BITS: u32 = <$t>::BITS;
+                fn from_bits(bits: u64) -> Self {
+                    bits as $t
+                }
+                fn to_bits(self) -> u64 {
+                    self as u64
+                }
+            }
+        )*
+    };
+}
+
+int_lane!(i8, u8, i16, u16, i32, u32, i64, u64);
+
+impl Lane for f32 {
+    const BITS: u32 = 32;
+    fn from_bits(bits: u64) -> Self {
+        f32::from_bits(bits as u32)
+    }
+    fn to_bits(self) -> u64 {
+        self.to_bits() as u64
+    }
+}
+
+impl Lane for f64 {
+    const BITS: u32 = 64;
+    fn from_bits(bits: u64) -> Self {
+        f64::from_bits(bits)
+    }
+    fn to_bits(self) -> u64 {
+        self.to_bits()
+    }
+}
+
+fn count<T: Lane>() -> u32 {
+    128 / T::BITS
+}
+
+fn mask<T: Lane>() -> u128 {
+    (1 << T::BITS) - 1
+}
+
+fn lane<T: Lane>(v: u128, i: u32) -> T {
+    T::from_bits(((v >> (i * T::BITS)) & mask::<T>()) as u64)
+}
+
+fn with_lane<T: Lane>(v: u128, i: u32, x: T) -> u128 {
+    let shift = i * T::BITS;
+    v & !(mask::<T>() << shift) | (x.to_bits() as u128 & mask::<T>()) << shift
+}
+
+fn splat<T: Lane>(x: T) -> u128 {
+    (0..count::<T>()).fold(0, |v, i| with_lane(v, i, x))
+}
+
+fn map<T: Lane, U: Lane>(v: u128, f: impl Fn(T) -> U) -> u128 {
+    (0..count::<T>()).fold(0, |r, i| with_lane(r, i, f(lane(v, i))))
+}
+
+fn zip<T: Lane>(a: u128, b: u128, f: impl Fn(T, T) -> T) -> u128 {
+    (0..count::<T>()).fold(0, |r, i| with_lane(r, i, f(lane(a, i), lane(b, i))))
+}
+
+// 比較の結果は真なら全ビットが 1 の lane になる
+fn compare<T: Lane>(a: u128, b: u128, f: impl Fn(T, T) -> bool) -> u128 {
+    (0..count::<T>()).fold(0, |r, i| {
+        let bits = if f(lane(a, i), lane(b, i)) {
+            u64::MAX
+        } else {
+            0
+        };
+        r | (bits as u128 & mask::<T>()) << (i * T::BITS)
+    })
+}
+
+// 幅が倍の lane に広げる。high なら上半分の lane を使う
+fn extend<T: Lane, U: Lane>(v: u128, high: bool, f: impl Fn(T) -> U) -> u128 {
+    let offset = if high { count::<U>() } else { 0 };
+    (0..count::<U>()).fold(0, |r, i| with_lane(r, i, f(lane(v, i + offset))))
+}
+
+fn extmul<T: Lane, U: Lane>(a: u128, b: u128, high: bool, f: impl Fn(T, T) -> U) -> u128 {
+    let offset = if high { count::<U>() } else { 0 };
+    (0..count::<U>()).fold(0, |r, i| {
+        with_lane(r, i, f(lane(a, i + offset), lane(b, i + offset)))
+    })
+}
+
+// 隣り合う 2 つの lane から幅が倍の lane を作る
+fn pairwise<T: Lane, U: Lane>(a: u128, b: u128, f: impl Fn(T, T, T, T) -> U) -> u128 {
+    (0..count::<U>()).fold(0, |r, i| {
+        let (j, k) = (2 * i, 2 * i + 1);
+        with_lane(r, i, f(lane(a, j), lane(a, k), lane(b, j), lane(b, k)))
+    })
+}
+
+// a の lane を下半分に、b の lane を上半分に入れて幅が半分の lane にする
+fn narrow<T: Lane, U: Lane>(a: u128, b: u128, f: impl Fn(T) -> U) -> u128 {
+    let n = count::<T>();
+    (0..n).fold(0, |r, i| {
+        let r = with_lane(r, i, f(lane(a, i)));
+        with_lane(r, i + n, f(lane(b, i)))
+    })
+}
+
+fn all_true<T: Lane>(v: u128) -> bool {
+    (0..count::<T>()).all(|i| lane::<T>(v, i).to_bits() != 0)
+}
+
+fn bitmask<T: Lane>(v: u128) -> u32 {
+    (0..count::<T>()).fold(0, |r, i| {
+        r | ((lane::<T>(v, i).to_bits() >> (T::BITS - 1)) as u32 & 1) << i
+    })
+}
+
+// シフト量は lane の幅で割った余りを使う
+fn shift<T: Lane>(v: u128, n: u32, f: impl Fn(T, u32) -> T) -> u128 {
+    map(v, |x: T| f(x, n % T::BITS))
+}
+
+fn fmin<T: Float>(a: T, b: T) -> T {
+    if a.is_nan() || b.is_nan() {
+        T::NAN
+    } else if a.is_zero() && b.is_zero() {
+        // -0 と +0 は -0 を選ぶ
+        if a.is_sign_negative() {
+            a
+        } else {
+            b
+        }
+    } else if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+fn fmax<T: Float>(a: T, b: T) -> T {
+    if a.is_nan() || b.is_nan() {
+        T::NAN
+    } else if a.is_zero() && b.is_zero() {
+        if a.is_sign_negative() {
+            b
+        } else {
+            a
+        }
+    } else if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+// fmin/fmax を f32 と f64 で共通にする
+trait Float: Lane + PartialOrd {
+    const NAN: Self;
+    fn is_nan(self) -> bool;
+    fn is_zero(self) -> bool;
+    fn is_sign_negative(self) -> bool;
+}
+
+impl Float for f32 {
+    const NAN: Self = f32::NAN;
+    fn is_nan(self) -> bool {
+        f32::is_nan(self)
+    }
+    fn is_zero(self) -> bool {
+        self == 0.0
+    }
+    fn is_sign_negative(self) -> bool {
+        f32::is_sign_negative(self)
+    }
+}
+
+impl Float for f64 {
+    const NAN: Self = f64::NAN;
+    fn is_nan(self) -> bool {
+        f64::is_nan(self)
+    }
+    fn is_zero(self) -> bool {
+        self == 0.0
+    }
+    fn is_sign_negative(self) -> bool {
+        f64::is_sign_negative(self)
+    }
+}
+
+fn pop(stack: &mut Vec<u128>) -> Result<u128> {
+    stack.pop().ok_or(Trap::StackUnderflow)
+}
+
+fn pop2(stack: &mut Vec<u128>) -> Result<(u128, u128)> {
+    let b = pop(stack)?;
+    let a = pop(stack)?;
+    Ok((a, b))
+}
+
+// 実効アドレスから len バイトの範囲。memory の外なら trap する
+fn effective_range(
+    addr: u128,
+    memarg: &MemArg,
+    len: usize,
+    memory: &[u8],
+) -> Result<std::ops::Range<usize>> {
+    let start = addr as u32 as u64 + memarg.offset() as u64;
+    let end = start + len as u64;
+    if end > memory.len() as u64 {
+        return Err(Trap::OutOfBoundsMemoryAccess);
+    }
+    Ok(start as usize..end as usize)
+}
+
+fn load(stack: &mut Vec<u128>, memarg: &MemArg, len: usize, memory: &[u8]) -> Result<u128> {
+    let addr = pop(stack)?;
+    let range = effective_range(addr, memarg, len, memory)?;
+    let mut bytes = [0; 16];
+    bytes[..len].copy_from_slice(&memory[range]);
+    Ok(u128::from_le_bytes(bytes))
+}
+
+fn store(
+    stack: &mut Vec<u128>,
+    memarg: &MemArg,
+    len: usize,
+    memory: &mut [u8],
+    value: u128,
+) -> Result<()> {
+    let addr = pop(stack)?;
+    let range = effective_range(addr, memarg, len, memory)?;
+    memory[range].copy_from_slice(&value.to_le_bytes()[..len]);
+    Ok(())
+}
+
+fn load_lane<T: Lane>(
+    stack: &mut Vec<u128>,
+    memarg: &MemArg,
+    index: u8,
+    memory: &[u8],
+) -> Result<u128> {
+    let v = pop(stack)?;
+    let x = load(stack, memarg, (T::BITS / 8) as usize, memory)?;
+    Ok(with_lane(v, index as u32, T::from_bits(x as u64)))
+}
+
+fn store_lane<T: Lane>(
+    stack: &mut Vec<u128>,
+    memarg: &MemArg,
+    index: u8,
+    memory: &mut [u8],
+) -> Result<()> {
+    let v = pop(stack)?;
+    let x = lane::<T>(v, index as u32).to_bits() as u128;
+    store(stack, memarg, (T::BITS / 8) as usize, memory, x)
+}
+
+fn bool_value(b: bool) -> u128 {
+    b as u128
+}
+
+/// SIMD 命令を 1 つ実行する
+///
+/// 検証済みの命令を前提にしているので、lane index が範囲外の場合は panic する
+pub(crate) fn execute(
+    instr: &VectorInstruction,
+    stack: &mut Vec<u128>,
+    memory: &mut [u8],
+) -> Result<()> {
+    use VectorInstruction::*;
+    let result = match instr {
+        Load(m) => load(stack, m, 16, memory)?,
+        Load8x8S(m) => extend(load(stack, m, 8, memory)?, false, |x: i8| x as i16),
+        Load8x8U(m) => extend(load(stack, m, 8, memory)?, false, |x: u8| x as u16),
+        Load16x4S(m) => extend(load(stack, m, 8, memory)?, false, |x: i16| x as i32),
+        Load16x4U(m) => extend(load(stack, m, 8, memory)?, false, |x: u16| x as u32),
+        Load32x2S(m) => extend(load(stack, m, 8, memory)?, false, |x: i32| x as i64),
+        Load32x2U(m) => extend(load(stack, m, 8, memory)?, false, |x: u32| x as u64),
+        Load8Splat(m) => splat(load(stack, m, 1, memory)? as u8),
+        Load16Splat(m) => splat(load(stack, m, 2, memory)? as u16),
+        Load32Splat(m) => splat(load(stack, m, 4, memory)? as u32),
+        Load64Splat(m) => splat(load(stack, m, 8, memory)? as u64),
+        Load32Zero(m) => load(stack, m, 4, memory)?,
+        Load64Zero(m) => load(stack, m, 8, memory)?,
+        Store(m) => {
+            let v = pop(stack)?;
+            return store(stack, m, 16, memory, v);
+        }
+        Load8Lane(m, i) => load_lane::<u8>(stack, m, *i, memory)?,
+        Load16Lane(m, i) => load_lane::<u16>(stack, m, *i, memory)?,
+        Load32Lane(m, i) => load_lane::<u32>(stack, m, *i, memory)?,
+        Load64Lane(m, i) => load_lane::<u64>(stack, m, *i, memory)?,
+        Store8Lane(m, i) => return store_lane::<u8>(stack, m, *i, memory),
+        Store16Lane(m, i) => return store_lane::<u16>(stack, m, *i, memory),
+        Store32Lane(m, i) => return store_lane::<u32>(stack, m, *i, memory),
+        Store64Lane(m, i) => return store_lane::<u64>(stack, m, *i, memory),
+        Const(bytes) => u128::from_le_bytes(*bytes),
+        ShuffleI8x16(lanes) => {
+            let (a, b) = pop2(stack)?;
+            (0..16).fold(0, |r, i| {
+                let j = lanes[i as usize] as u32;
+                let x: u8 = if j < 16 { lane(a, j) } else { lane(b, j - 16) };
+                with_lane(r, i, x)
+            })
+        }
+        SwizzleI8x16 => {
+            let (a, s) = pop2(stack)?;
+            map(s, |j: u8| if j < 16 { lane::<u8>(a, j as u32) } else { 0 })
+        }
+        SplatI8x16 => splat(pop(stack)? as u8),
+        SplatI16x8 => splat(pop(stack)? as u16),
+        SplatI32x4 | SplatF32x4 => splat(pop(stack)? as u32),
+        SplatI64x2 | SplatF64x2 => splat(pop(stack)? as u64),
+        ExtractLaneSI8x16(i) => lane::<i8>(pop(stack)?, *i as u32) as i32 as u32 as u128,
+        ExtractLaneUI8x16(i) => lane::<u8>(pop(stack)?, *i as u32) as u128,
+        ExtractLaneSI16x8(i) => lane::<i16>(pop(stack)?, *i as u32) as i32 as u32 as u128,
+        ExtractLaneUI16x8(i) => lane::<u16>(pop(stack)?, *i as u32) as u128,
+        ExtractLaneI32x4(i) | ExtractLaneF32x4(i) => lane::<u32>(pop(stack)?, *i as u32) as u128,
+        ExtractLaneI64x2(i) | ExtractLaneF64x2(i) => lane::<u64>(pop(stack)?, *i as u32) as u128,
+        ReplaceLaneI8x16(i) => {
+            let (v, x) = pop2(stack)?;
+            with_lane(v, *i as u32, x as u8)
+        }
+        ReplaceLaneI16x8(i) => {
+            let (v, x) = pop2(stack)?;
+            with_lane(v, *i as u32, x as u16)
+        }
+        ReplaceLaneI32x4(i) | ReplaceLaneF32x4(i) => {
+            let (v, x) = pop2(stack)?;
+            with_lane(v, *i as u32, x as u32)
+        }
+        ReplaceLaneI64x2(i) | ReplaceLaneF64x2(i) => {
+            let (v, x) = pop2(stack)?;
+            with_lane(v, *i as u32, x as u64)
+        }
+        NotV128 => !pop(stack)?,
+        AndV128 | AndNotV128 | OrV128 | XorV128 => {
+            let (a, b) = pop2(stack)?;
+            match instr {
+                AndV128 => a & b,
+                AndNotV128 => a & !b,
+                OrV128 => a | b,
+                _ => a ^ b,
+            }
+        }
+        BitselectV128 => {
+            let c = pop(stack)?;
+            let (a, b) = pop2(stack)?;
+            a & c | b & !c
+        }
+        AnyTrueV128 => bool_value(pop(stack)? != 0),
+        AllTrueI8x16 => bool_value(all_true::<u8>(pop(stack)?)),
+        AllTrueI16x8 => bool_value(all_true::<u16>(pop(stack)?)),
+        AllTrueI32x4 => bool_value(all_true::<u32>(pop(stack)?)),
+        AllTrueI64x2 => bool_value(all_true::<u64>(pop(stack)?)),
+        BitmaskI8x16 => bitmask::<u8>(pop(stack)?) as u128,
+        BitmaskI16x8 => bitmask::<u16>(pop(stack)?) as u128,
+        BitmaskI32x4 => bitmask::<u32>(pop(stack)?) as u128,
+        BitmaskI64x2 => bitmask::<u64>(pop(stack)?) as u128,
+        ShlI8x16 | ShrSI8x16 | ShrUI8x16 | ShlI16x8 | ShrSI16x8 | ShrUI16x8 | ShlI32x4
+        | ShrSI32x4 | ShrUI32x4 | ShlI64x2 | ShrSI64x2 | ShrUI64x2 => {
+            let (v, n) = pop2(stack)?;
+            let n = n as u32;
+            match instr {
+                ShlI8x16 => shift(v, n, |x: u8, n| x << n),
+                ShrSI8x16 => shift(v, n, |x: i8, n| x >> n),
+                ShrUI8x16 => shift(v, n, |x: u8, n| x >> n),
+                ShlI16x8 => shift(v, n, |x: u16, n| x << n),
+                ShrSI16x8 => shift(v, n, |x: i16, n| x >> n),
+                ShrUI16x8 => shift(v, n, |x: u16, n| x >> n),
+                ShlI32x4 => shift(v, n, |x: u32, n| x << n),
+                ShrSI32x4 => shift(v, n, |x: i32, n| x >> n),
+                ShrUI32x4 => shift(v, n, |x: u32, n| x >> n),
+                ShlI64x2 => shift(v, n, |x: u64, n| x << n),
+                ShrSI64x2 => shift(v, n, |x: i64, n| x >> n),
+                _ => shift(v, n, |x: u64, n| x >> n),
+            }
+        }
+        _ => return unary_or_binary(instr, stack),
+    };
+    stack.push(result);
+    Ok(())
+}
+
+// v128 を 1 つか 2 つ取って v128 を返す lane ごとの演算
+fn unary_or_binary(instr: &VectorInstruction, stack: &mut Vec<u128>) -> Result<()> {
+    use VectorInstruction::*;
+    let v = pop(stack)?;
+    let result = match instr {
+        AbsI8x16 => map(v, i8::wrapping_abs),
+        NegI8x16 => map(v, i8::wrapping_neg),
+        PopcntI8x16 => map(v, |x: u8| x.count_ones() as u8),
+        AbsI16x8 => map(v, i16::wrapping_abs),
+        NegI16x8 => map(v, i16::wrapping_neg),
+        AbsI32x4 => map(v, i32::wrapping_abs),
+        NegI32x4 => map(v, i32::wrapping_neg),
+        AbsI64x2 => map(v, i64::wrapping_abs),
+        NegI64x2 => map(v, i64::wrapping_neg),
+        AbsF32x4 => map(v, f32::abs),
+        NegF32x4 => map(v, |x: f32| -x),
+        SqrtF32x4 => map(v, f32::sqrt),
+        CeilF32x4 => map(v, f32::ceil),
+        FloorF32x4 => map(v, f32::floor),
+        TruncF32x4 => map(v, f32::trunc),
+        NearestF32x4 => map(v, f32::round_ties_even),
+        AbsF64x2 => map(v, f64::abs),
+        NegF64x2 => map(v, |x: f64| -x),
+        SqrtF64x2 => map(v, f64::sqrt),
+        CeilF64x2 => map(v, f64::ceil),
+        FloorF64x2 => map(v, f64::floor),
+        TruncF64x2 => map(v, f64::trunc),
+        NearestF64x2 => map(v, f64::round_ties_even),
+        ExtaddPairwiseSI16x8I8x16 => pairwise(v, v, |x: i8, y, _, _| x as i16 + y as i16),
+        ExtaddPairwiseUI16x8I8x16 => pairwise(v, v, |x: u8, y, _, _| x as u16 + y as u16),
+        ExtaddPairwiseSI32x4I16x8 => pairwise(v, v, |x: i16, y, _, _| x as i32 + y as i32),
+        ExtaddPairwiseUI32x4I16x8 => pairwise(v, v, |x: u16, y, _, _| x as u32 + y as u32),
+        ExtendLowSI16x8I8x16 => extend(v, false, |x: i8| x as i16),
+        ExtendHighSI16x8I8x16 => extend(v, true, |x: i8| x as i16),
+        ExtendLowUI16x8I8x16 => extend(v, false, |x: u8| x as u16),
+        ExtendHighUI16x8I8x16 => extend(v, true, |x: u8| x as u16),
+        ExtendLowSI32x4I16x8 => extend(v, false, |x: i16| x as i32),
+        ExtendHighSI32x4I16x8 => extend(v, true, |x: i16| x as i32),
+        ExtendLowUI32x4I16x8 => extend(v, false, |x: u16| x as u32),
+        ExtendHighUI32x4I16x8 => extend(v, true, |x: u16| x as u32),
+        ExtendLowSI64x2I32x4 => extend(v, false, |x: i32| x as i64),
+        ExtendHighSI64x2I32x4 => extend(v, true, |x: i32| x as i64),
+        ExtendLowUI64x2I32x4 => extend(v, false, |x: u32| x as u64),
+        ExtendHighUI64x2I32x4 => extend(v, true, |x: u32| x as u64),
+        // as による変換は飽和し、NaN は 0 になる
+        TruncSatSI32x4F32x4 => map(v, |x: f32| x as i32),
+        TruncSatUI32x4F32x4 => map(v, |x: f32| x as u32),
+        ConvertSF32x4I32x4 => map(v, |x: i32| x as f32),
+        ConvertUF32x4I32x4 => map(v, |x: u32| x as f32),
+        TruncSatZeroSI32x4F64x2 => narrow(v, 0, |x: f64| x as i32),
+        TruncSatZeroUI32x4F64x2 => narrow(v, 0, |x: f64| x as u32),
+        ConvertLowSF64x2I32x4 => extend(v, false, |x: i32| x as f64),
+        ConvertLowUF64x2I32x4 => extend(v, false, |x: u32| x as f64),
+        DemoteZeroF32x4F64x2 => narrow(v, 0, |x: f64| x as f32),
+        PromoteLowF64x2F32x4 => extend(v, false, |x: f32| x as f64),
+        _ => {
+            let (a, b) = (pop(stack)?, v);
+            binary(instr, a, b)
+        }
+    };
+    stack.push(result);
+    Ok(())
+}
+
+fn binary(instr: &VectorInstruction, a: u128, b: u128) -> u128 {
+    use VectorInstruction::*;
+    match instr {
+        EqI8x16 => compare(a, b, |x: u8, y| x == y),
+        NeI8x16 => compare(a, b, |x: u8, y| x != y),
+        LtSI8x16 => compare(a, b, |x: i8, y| x < y),
+        LtUI8x16 => compare(a, b, |x: u8, y| x < y),
+        GtSI8x16 => compare(a, b, |x: i8, y| x > y),
+        GtUI8x16 => compare(a, b, |x: u8, y| x > y),
+        LeSI8x16 => compare(a, b, |x: i8, y| x <= y),
+        LeUI8x16 => compare(a, b, |x: u8, y| x <= y),
+        GeSI8x16 => compare(a, b, |x: i8, y| x >= y),
+        GeUI8x16 => compare(a, b, |x: u8, y| x >= y),
+        EqI16x8 => compare(a, b, |x: u16, y| x == y),
+        NeI16x8 => compare(a, b, |x: u16, y| x != y),
+        LtSI16x8 => compare(a, b, |x: i16, y| x < y),
+        LtUI16x8 => compare(a, b, |x: u16, y| x < y),
+        GtSI16x8 => compare(a, b, |x: i16, y| x > y),
+        GtUI16x8 => compare(a, b, |x: u16, y| x > y),
+        LeSI16x8 => compare(a, b, |x: i16, y| x <= y),
+        LeUI16x8 => compare(a, b, |x: u16, y| x <= y),
+        GeSI16x8 => compare(a, b, |x: i16, y| x >= y),
+        GeUI16x8 => compare(a, b, |x: u16, y| x >= y),
+        EqI32x4 => compare(a, b, |x: u32, y| x == y),
+        NeI32x4 => compare(a, b, |x: u32, y| x != y),
+        LtSI32x4 => compare(a, b, |x: i32, y| x < y),
+        LtUI32x4 => compare(a, b, |x: u32, y| x < y),
+        GtSI32x4 => compare(a, b, |x: i32, y| x > y),
+        GtUI32x4 => compare(a, b, |x: u32, y| x > y),
+        LeSI32x4 => compare(a, b, |x: i32, y| x <= y),
+        LeUI32x4 => compare(a, b, |x: u32, y| x <= y),
+        GeSI32x4 => compare(a, b, |x: i32, y| x >= y),
+        GeUI32x4 => compare(a, b, |x: u32, y| x >= y),
+        EqI64x2 => compare(a, b, |x: u64, y| x == y),
+        NeI64x2 => compare(a, b, |x: u64, y| x != y),
+        LtSI64x2 => compare(a, b, |x: i64, y| x < y),
+        GtSI64x2 => compare(a, b, |x: i64, y| x > y),
+        LeSI64x2 => compare(a, b, |x: i64, y| x <= y),
+        GeSI64x2 => compare(a, b, |x: i64, y| x >= y),
+        EqF32x4 => compare(a, b, |x: f32, y| x == y),
+        NeF32x4 => compare(a, b, |x: f32, y| x != y),
+        LtF32x4 => compare(a, b, |x: f32, y| x < y),
+        GtF32x4 => compare(a, b, |x: f32, y| x > y),
+        LeF32x4 => compare(a, b, |x: f32, y| x <= y),
+        GeF32x4 => compare(a, b, |x: f32, y| x >= y),
+        EqF64x2 => compare(a, b, |x: f64, y| x == y),
+        NeF64x2 => compare(a, b, |x: f64, y| x != y),
+        LtF64x2 => compare(a, b, |x: f64, y| x < y),
+        GtF64x2 => compare(a, b, |x: f64, y| x > y),
+        LeF64x2 => compare(a, b, |x: f64, y| x <= y),
+        GeF64x2 => compare(a, b, |x: f64, y| x >= y),
+        NarrowSI8x16I16x8 => narrow(a, b, |x: i16| x.clamp(i8::MIN as i16, i8::MAX as i16) as i8),
+        NarrowUI8x16I16x8 => narrow(a, b, |x: i16| x.clamp(0, u8::MAX as i16) as u8),
+        NarrowSI16x8I32x4 => narrow(a, b, |x: i32| {
+            x.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+        }),
+        NarrowUI16x8I32x4 => narrow(a, b, |x: i32| x.clamp(0, u16::MAX as i32) as u16),
+        AddI8x16 => zip(a, b, u8::wrapping_add),
+        AddSatSI8x16 => zip(a, b, i8::saturating_add),
+        AddSatUI8x16 => zip(a, b, u8::saturating_add),
+        SubI8x16 => zip(a, b, u8::wrapping_sub),
+        SubSatSI8x16 => zip(a, b, i8::saturating_sub),
+        SubSatUI8x16 => zip(a, b, u8::saturating_sub),
+        MinSI8x16 => zip(a, b, i8::min),
+        MinUI8x16 => zip(a, b, u8::min),
+        MaxSI8x16 => zip(a, b, i8::max),
+        MaxUI8x16 => zip(a, b, u8::max),
+        AvgrUI8x16 => zip(a, b, |x: u8, y| ((x as u16 + y as u16 + 1) >> 1) as u8),
+        AddI16x8 => zip(a, b, u16::wrapping_add),
+        AddSatSI16x8 => zip(a, b, i16::saturating_add),
+        AddSatUI16x8 => zip(a, b, u16::saturating_add),
+        SubI16x8 => zip(a, b, u16::wrapping_sub),
+        SubSatSI16x8 => zip(a, b, i16::saturating_sub),
+        SubSatUI16x8 => zip(a, b, u16::saturating_sub),
+        MulI16x8 => zip(a, b, u16::wrapping_mul),
+        MinSI16x8 => zip(a, b, i16::min),
+        MinUI16x8 => zip(a, b, u16::min),
+        MaxSI16x8 => zip(a, b, i16::max),
+        MaxUI16x8 => zip(a, b, u16::max),
+        AvgrUI16x8 => zip(a, b, |x: u16, y| ((x as u32 + y as u32 + 1) >> 1) as u16),
+        Q15mulrSatSI16x8 => zip(a, b, |x: i16, y| {
+            let r = (x as i32 * y as i32 + 0x4000) >> 15;
+            r.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+        }),
+        ExtmulLowSI16x8I8x16 => extmul(a, b, false, |x: i8, y| x as i16 * y as i16),
+        ExtmulHighSI16x8I8x16 => extmul(a, b, true, |x: i8, y| x as i16 * y as i16),
+        ExtmulLowUI16x8I8x16 => extmul(a, b, false, |x: u8, y| x as u16 * y as u16),
+        ExtmulHighUI16x8I8x16 => extmul(a, b, true, |x: u8, y| x as u16 * y as u16),
+        AddI32x4 => zip(a, b, u32::wrapping_add),
+        SubI32x4 => zip(a, b, u32::wrapping_sub),
+        MulI32x4 => zip(a, b, u32::wrapping_mul),
+        MinSI32x4 => zip(a, b, i32::min),
+        MinUI32x4 => zip(a, b, u32::min),
+        MaxSI32x4 => zip(a, b, i32::max),
+        MaxUI32x4 => zip(a, b, u32::max),
+        // -32768 * -32768 を 2 つ足すと i32 に収まらないので wrapping で足す
+        DotSI32x4I16x8 => pairwise(a, b, |a0: i16, a1, b0, b1| {
+            (a0 as i32 * b0 as i32).wrapping_add(a1 as i32 * b1 as i32)
+        }),
+        ExtmulLowSI32x4I16x8 => extmul(a, b, false, |x: i16, y| x as i32 * y as i32),
+        ExtmulHighSI32x4I16x8 => extmul(a, b, true, |x: i16, y| x as i32 * y as i32),
+        ExtmulLowUI32x4I16x8 => extmul(a, b, false, |x: u16, y| x as u32 * y as u32),
+        ExtmulHighUI32x4I16x8 => extmul(a, b, true, |x: u16, y| x as u32 * y as u32),
+        AddI64x2 => zip(a, b, u64::wrapping_add),
+        SubI64x2 => zip(a, b, u64::wrapping_sub),
+        MulI64x2 => zip(a, b, u64::wrapping_mul),
+        ExtmulLowSI64x2I32x4 => extmul(a, b, false, |x: i32, y| x as i64 * y as i64),
+        ExtmulHighSI64x2I32x4 => extmul(a, b, true, |x: i32, y| x as i64 * y as i64),
+        ExtmulLowUI64x2I32x4 => extmul(a, b, false, |x: u32, y| x as u64 * y as u64),
+        ExtmulHighUI64x2I32x4 => extmul(a, b, true, |x: u32, y| x as u64 * y as u64),
+        AddF32x4 => zip(a, b, |x: f32, y| x + y),
+        SubF32x4 => zip(a, b, |x: f32, y| x - y),
+        MulF32x4 => zip(a, b, |x: f32, y| x * y),
+        DivF32x4 => zip(a, b, |x: f32, y| x / y),
+        MinF32x4 => zip(a, b, fmin::<f32>),
+        MaxF32x4 => zip(a, b, fmax::<f32>),
+        PminF32x4 => zip(a, b, |x: f32, y| if y < x { y } else { x }),
+        PmaxF32x4 => zip(a, b, |x: f32, y| if x < y { y } else { x }),
+        AddF64x2 => zip(a, b, |x: f64, y| x + y),
+        SubF64x2 => zip(a, b, |x: f64, y| x - y),
+        MulF64x2 => zip(a, b, |x: f64, y| x * y),
+        DivF64x2 => zip(a, b, |x: f64, y| x / y),
+        MinF64x2 => zip(a, b, fmin::<f64>),
+        MaxF64x2 => zip(a, b, fmax::<f64>),
+        PminF64x2 => zip(a, b, |x: f64, y| if y < x { y } else { x }),
+        PmaxF64x2 => zip(a, b, |x: f64, y| if x < y { y } else { x }),
+        _ => unreachable!("not a binary vector instruction: {:?}", instr),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::instruction::VectorInstruction::*;
+
+    fn v<T: Lane>(lanes: &[T]) -> u128 {
+        assert_eq!(lanes.len() as u32, count::<T>());
+        (0..).zip(lanes).fold(0, |r, (i, x)| with_lane(r, i, *x))
+    }
+
+    fn lanes<T: Lane>(v: u128) -> Vec<T> {
+        (0..count::<T>()).map(|i| lane(v, i)).collect()
+    }
+
+    fn run(instr: VectorInstruction, operands: &[u128]) -> u128 {
+        let mut stack = operands.to_vec();
+        execute(&instr, &mut stack, &mut []).unwrap();
+        assert_eq!(stack.len(), 1);
+        stack[0]
+    }
+
+    fn memarg(offset: u32) -> MemArg {
+        MemArg { align: 0, offset }
+    }
+
+    #[test]
+    fn test_integer() {
+        let a = v::<i8>(&[127, -128, 1, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 100]);
+        let b = v::<i8>(&[1, -1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 100]);
+        assert_eq!(
+            lanes::<i8>(run(AddSatSI8x16, &[a, b]))[..4],
+            [127, -128, 2, 0]
+        );
+        assert_eq!(lanes::<i8>(run(AddI8x16, &[a, b]))[..4], [-128, 127, 2, 0]);
+        assert_eq!(lanes::<u8>(run(AvgrUI8x16, &[a, b]))[15], 100);
+        assert_eq!(lanes::<i8>(run(AbsI8x16, &[a]))[1], -128);
+        assert_eq!(lanes::<u8>(run(PopcntI8x16, &[a]))[..4], [7, 1, 1, 8]);
+
+        // シフト量は lane の幅で割った余り
+        let x = v::<i16>(&[-4, 4, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(lanes::<i16>(run(ShrSI16x8, &[x, 17]))[..2], [-2, 2]);
+        assert_eq!(lanes::<u16>(run(ShrUI16x8, &[x, 1]))[..2], [0x7ffe, 2]);
+        assert_eq!(lanes::<i16>(run(ShlI16x8, &[x, 16]))[..2], [-4, 4]);
+
+        let x = v::<i16>(&[i16::MIN, 0x4000, -300, 300, 1, 2, 3, 4]);
+        let y = v::<i16>(&[i16::MIN, 0x4000, 2, 2, 5, 6, 7, 8]);
+        assert_eq!(
+            lanes::<i16>(run(Q15mulrSatSI16x8, &[x, y]))[..2],
+            [i16::MAX, 0x2000]
+        );
+        assert_eq!(
+            lanes::<i32>(run(DotSI32x4I16x8, &[x, y])),
+            [(1 << 30) + (1 << 28), 0, 17, 53]
+        );
+        let min = v::<i16>(&[i16::MIN; 8]);
+        assert_eq!(
+            lanes::<i32>(run(DotSI32x4I16x8, &[min, min])),
+            [i32::MIN; 4]
+        );
+        assert_eq!(
+            lanes::<i8>(run(NarrowSI8x16I16x8, &[x, y]))[..8],
+            [-128, 127, -128, 127, 1, 2, 3, 4]
+        );
+        assert_eq!(
+            lanes::<u8>(run(NarrowUI8x16I16x8, &[x, y]))[..8],
+            [0, 255, 0, 255, 1, 2, 3, 4]
+        );
+        assert_eq!(
+            lanes::<i32>(run(ExtmulHighSI32x4I16x8, &[x, y])),
+            [5, 12, 21, 32]
+        );
+        assert_eq!(
+            lanes::<i32>(run(ExtendLowSI32x4I16x8, &[x])),
+            [-32768, 0x4000, -300, 300]
+        );
+        assert_eq!(
+            lanes::<u32>(run(ExtaddPairwiseUI32x4I16x8, &[x])),
+            [0x8000 + 0x4000, 65236 + 300, 3, 7]
+        );
+
+        assert_eq!(run(BitmaskI16x8, &[x]), 0b101);
+        assert_eq!(run(AllTrueI16x8, &[x]), 1);
+        assert_eq!(run(AllTrueI32x4, &[x]), 1);
+        assert_eq!(run(AllTrueI64x2, &[v::<i64>(&[1, 0])]), 0);
+        assert_eq!(run(AnyTrueV128, &[0]), 0);
+        assert_eq!(
+            lanes::<i64>(run(GtSI64x2, &[v::<i64>(&[-1, 1]), v::<i64>(&[0, 0])])),
+            [0, -1]
+        );
+    }
+
+    #[test]
+    fn test_float() {
+        let a = v::<f32>(&[f32::NAN, -0.0, 0.0, 2.5]);
+        let b = v::<f32>(&[1.0, 0.0, -0.0, -3.5]);
+        let min = lanes::<f32>(run(MinF32x4, &[a, b]));
+        assert!(min[0].is_nan());
+        assert!(min[1].is_sign_negative() && min[2].is_sign_negative());
+        assert_eq!(min[3], -3.5);
+        let max = lanes::<f32>(run(MaxF32x4, &[a, b]));
+        assert!(max[0].is_nan());
+        assert!(max[1].is_sign_positive() && max[2].is_sign_positive());
+        // pmin は b < a の時だけ b を選ぶ
+        let pmin = lanes::<f32>(run(PminF32x4, &[a, b]));
+        assert!(pmin[0].is_nan());
+        assert!(pmin[1].is_sign_negative() && pmin[2].is_sign_positive());
+
+        assert_eq!(
+            lanes::<f32>(run(NearestF32x4, &[v::<f32>(&[0.5, 1.5, 2.5, -2.5])])),
+            [0.0, 2.0, 2.0, -2.0]
+        );
+        assert_eq!(
+            lanes::<i32>(run(
+                TruncSatSI32x4F32x4,
+                &[v::<f32>(&[f32::NAN, 3e9, -3e9, -1.9])]
+            )),
+            [0, i32::MAX, i32::MIN, -1]
+        );
+        assert_eq!(
+            lanes::<u32>(run(TruncSatZeroUI32x4F64x2, &[v::<f64>(&[-1.0, 5e9])])),
+            [0, u32::MAX, 0, 0]
+        );
+        assert_eq!(
+            lanes::<f64>(run(ConvertLowUF64x2I32x4, &[v::<i32>(&[-1, 2, 3, 4])])),
+            [u32::MAX as f64, 2.0]
+        );
+        assert_eq!(
+            lanes::<f32>(run(DemoteZeroF32x4F64x2, &[v::<f64>(&[1.5, -2.0])])),
+            [1.5, -2.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            lanes::<i32>(run(
+                LtF64x2,
+                &[v::<f64>(&[1.0, f64::NAN]), v::<f64>(&[2.0, 2.0])]
+            )),
+            [-1, -1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_lane() {
+        let a = v::<u8>(&(0..16).collect::<Vec<_>>());
+        let b = v::<u8>(&(16..32).collect::<Vec<_>>());
+        let lanes_index = [31, 0, 17, 15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 16];
+        assert_eq!(
+            lanes::<u8>(run(ShuffleI8x16(lanes_index), &[a, b]))[..4],
+            [31, 0, 17, 15]
+        );
+        let s = v::<u8>(&[3, 16, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 15]);
+        assert_eq!(
+            lanes::<u8>(run(SwizzleI8x16, &[a, s])),
+            [3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 15]
+        );
+
+        let x = v::<i8>(&[-1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(run(ExtractLaneSI8x16(0), &[x]), 0xffff_ffff);
+        assert_eq!(run(ExtractLaneUI8x16(0), &[x]), 0xff);
+        let x = run(ReplaceLaneI64x2(1), &[0, u64::MAX as u128]);
+        assert_eq!(lanes::<u64>(x), [0, u64::MAX]);
+        assert_eq!(run(SplatI16x8, &[0x1_0002]), v::<u16>(&[2; 8]));
+        assert_eq!(
+            run(SplatF64x2, &[1.5f64.to_bits() as u128]),
+            v::<f64>(&[1.5, 1.5])
+        );
+        assert_eq!(
+            run(BitselectV128, &[0xff00, 0x0ff0, 0xf0f0]),
+            0xf0f0 & 0xff00 | 0x0ff0 & !0xf0f0
+        );
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut memory: Vec<u8> = (0..32).collect();
+        let mut stack = vec![1];
+        execute(&Load8x8S(memarg(2)), &mut stack, &mut memory).unwrap();
+        assert_eq!(lanes::<i16>(stack[0]), [3, 4, 5, 6, 7, 8, 9, 10]);
+
+        let mut stack = vec![0, u128::MAX];
+        execute(&Load16Lane(memarg(4), 7), &mut stack, &mut memory).unwrap();
+        assert_eq!(lanes::<u16>(stack[0])[6..], [u16::MAX, 0x0504]);
+
+        let mut stack = vec![0];
+        execute(&Load32Zero(memarg(0)), &mut stack, &mut memory).unwrap();
+        assert_eq!(stack[0], 0x0302_0100);
+
+        let mut stack = vec![30, v::<u16>(&[1, 0xabcd, 0, 0, 0, 0, 0, 0])];
+        execute(&Store16Lane(memarg(0), 1), &mut stack, &mut memory).unwrap();
+        assert!(stack.is_empty());
+        assert_eq!(memory[30..], [0xcd, 0xab]);
+
+        // 実効アドレスは 32bit を越えても trap する
+        let mut stack = vec![u32::MAX as u128];
+        assert_eq!(
+            execute(&Load(memarg(1)), &mut stack, &mut memory),
+            Err(Trap::OutOfBoundsMemoryAccess)
+        );
+        let mut stack = vec![17, 0];
+        assert_eq!(
+            execute(&Store(memarg(0)), &mut stack, &mut memory),
+            Err(Trap::OutOfBoundsMemoryAccess)
+        );
+        let mut stack = vec![16, 0];
+        execute(&Store(memarg(0)), &mut stack, &mut memory).unwrap();
+        assert_eq!(memory[16..], [0; 16]);
+    }
+}
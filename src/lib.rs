@@ -1,10 +1,61 @@
-mod ast;
+pub mod ast;
 mod decode;
+mod encode;
+// 実行部分は作りかけで、まだテストからしか使っていない
+#[allow(dead_code)]
 mod evaluator;
+#[allow(dead_code)]
 mod object;
+pub mod validator;
+pub mod wat;
+
+pub use ast::{from_reader, parse_module, parse_module_borrowed, Error, ErrorContext};
+pub use wat::{parse_wat, print_wat};
 
 #[cfg(test)]
 mod tests {
+    use crate::ast::section::SectionData;
+
     #[test]
     fn it_works() {}
+
+    #[test]
+    fn parse() {
+        // (module
+        //   (func $add  (result i32)
+        //   i32.const 42
+        //   )
+        // )
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x2a, 0x0b, // code section
+        ];
+        let module = crate::parse_module(input).unwrap();
+        assert_eq!(module.magic_number(), 0x6d736100);
+        assert_eq!(module.version(), 1);
+        assert_eq!(module.sections().len(), 3);
+        let ids: Vec<u8> = module.sections().iter().map(|s| s.id()).collect();
+        assert_eq!(ids, vec![1, 3, 10]);
+        match module.sections()[2].payload_data() {
+            SectionData::Code(cs) => {
+                assert_eq!(cs.codes().len(), 1);
+                assert_eq!(cs.codes()[0].expression().unwrap().instrs().len(), 1);
+            }
+            _ => panic!("expected code section"),
+        }
+
+        let module = crate::from_reader(input).unwrap();
+        assert_eq!(module.sections().len(), 3);
+
+        let err = crate::parse_module(&input[..input.len() - 1])
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.kind(),
+            crate::Error::Decode(crate::ast::DecodeError::UnexpectedEof)
+        ));
+        assert_eq!(err.context().unwrap().offset(), Some(input.len() - 1));
+    }
 }
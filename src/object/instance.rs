@@ -1,5 +1,5 @@
 use super::value;
-use crate::ast::{self, module, section};
+use crate::ast;
 
 pub struct FunctionInstance {
     pub func_type: ast::wasm_type::FunctionType,
@@ -0,0 +1,6 @@
+pub enum ExternVal {
+    Func(u32),
+    Table(u32),
+    Mem(u32),
+    Global(u32),
+}
@@ -0,0 +1,1255 @@
+// 関数本体の命令の型を検証する
+//
+// 仕様の付録にある検証アルゴリズムと同じく、operand の型のスタックと control frame のスタックで
+// 命令を先頭から順に確かめる。入れ子が深い関数でもスタックを使い果たさないよう再帰しない
+//
+// 定数式や import/export の重複などモジュール全体の規則はまだ検証しない
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::ast::instruction::{
+    BlockType, ControlInstruction, Expression, Instruction, MemArg, MemoryInstruction,
+    NumericInstruction, ParametricInstruction, PlainNumericInstruction, ReferenceInstruction,
+    SaturatingTruncationInstruction, TableInstruction, VariableInstruction, VectorInstruction,
+};
+use crate::ast::module::Module;
+use crate::ast::section::{Code, ElementInit, ExportDesc, ImportDesc, SectionData};
+use crate::ast::wasm_type::{
+    FunctionType, Mutability, NumberType, ReferenceType, ValueType, VectorType,
+};
+use crate::ast::Error as ParseError;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ValidationError {
+    #[error("{0}")]
+    Parse(#[from] ParseError),
+    #[error("function and code section sizes differ. functions={funcs}, codes={codes}")]
+    FunctionCountMismatch { funcs: usize, codes: usize },
+    #[error("func {func}: {message}")]
+    Function { func: u32, message: String },
+    /// instr はデコード時のエラーと同じく、block の中の命令や else, end も数えた番号
+    #[error("func {func}, instr {instr}: {message}")]
+    Instruction {
+        func: u32,
+        instr: u32,
+        message: String,
+    },
+}
+
+type Result<T> = std::result::Result<T, ValidationError>;
+
+/// module の全ての関数本体を検証する
+///
+/// まだデコードされていない関数本体はここでデコードする
+pub fn validate(module: &Module) -> Result<()> {
+    let ctx = Context::new(module);
+    let codes = ctx.codes(module)?;
+    ctx.validate_codes(&codes)
+}
+
+/// `validate` と同じ検証を、関数本体のデコードも含めて `std::thread::scope` で並列に行う
+///
+/// エラーがあった場合は関数の index が最も小さいもののエラーを返す
+pub fn validate_parallel(module: &Module) -> Result<()> {
+    let ctx = Context::new(module);
+    let codes = ctx.codes(module)?;
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if threads <= 1 || codes.len() <= 1 {
+        return ctx.validate_codes(&codes);
+    }
+    let chunk_size = codes.len().div_ceil(threads);
+    let ctx = &ctx;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = codes
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || ctx.validate_codes(chunk)))
+            .collect();
+        // chunk の順に結果を見るので、最初に見つかったエラーが最も小さいインデックスのもの
+        handles
+            .into_iter()
+            .try_for_each(|h| h.join().expect("validation thread panicked"))
+    })
+}
+
+// 検証で区別する値の型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Val {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    FuncRef,
+    ExternRef,
+}
+
+impl Val {
+    fn is_ref(self) -> bool {
+        matches!(self, Self::FuncRef | Self::ExternRef)
+    }
+}
+
+impl From<&ValueType> for Val {
+    fn from(t: &ValueType) -> Self {
+        match t {
+            ValueType::Number(NumberType::I32) => Self::I32,
+            ValueType::Number(NumberType::I64) => Self::I64,
+            ValueType::Number(NumberType::F32) => Self::F32,
+            ValueType::Number(NumberType::F64) => Self::F64,
+            ValueType::Vector(VectorType::V128) => Self::V128,
+            ValueType::Reference(t) => Self::from(t),
+        }
+    }
+}
+
+impl From<&ReferenceType> for Val {
+    fn from(t: &ReferenceType) -> Self {
+        match t {
+            ReferenceType::FunctionRef => Self::FuncRef,
+            ReferenceType::ExternRef => Self::ExternRef,
+        }
+    }
+}
+
+impl std::fmt::Display for Val {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+            Self::V128 => "v128",
+            Self::FuncRef => "funcref",
+            Self::ExternRef => "externref",
+        };
+        f.write_str(name)
+    }
+}
+
+fn vals(types: &[ValueType]) -> Vec<Val> {
+    types.iter().map(Val::from).collect()
+}
+
+// 関数本体から参照できる module の定義。index space は import を先に数える
+#[derive(Default)]
+struct Context<'m> {
+    types: Vec<&'m FunctionType>,
+    funcs: Vec<u32>,
+    imported_funcs: u32,
+    tables: Vec<Val>,
+    memories: u32,
+    globals: Vec<(Val, bool)>,
+    elems: Vec<Val>,
+    data_count: Option<u32>,
+    // ref.func で参照してよい関数。関数本体以外で参照されているもの
+    refs: HashSet<u32>,
+}
+
+impl<'m> Context<'m> {
+    fn new(module: &'m Module) -> Self {
+        let mut ctx = Self::default();
+        for section in module.sections() {
+            match section.payload_data() {
+                SectionData::Type(section) => ctx.types.extend(section.funcs()),
+                SectionData::Import(section) => {
+                    for import in section.imports() {
+                        match import.desc() {
+                            ImportDesc::TypeIndex(index) => {
+                                ctx.funcs.push(*index);
+                                ctx.imported_funcs += 1;
+                            }
+                            ImportDesc::Table(t) => ctx.tables.push(Val::from(t.element_type())),
+                            ImportDesc::Memory(_) => ctx.memories += 1,
+                            ImportDesc::Global(t) => ctx.globals.push((
+                                Val::from(t.value_type()),
+                                matches!(t.mutability(), Mutability::Var),
+                            )),
+                        }
+                    }
+                }
+                SectionData::Function(section) => ctx.funcs.extend(section.indexies()),
+                SectionData::Table(section) => ctx
+                    .tables
+                    .extend(section.tables().iter().map(|t| Val::from(t.element_type()))),
+                SectionData::Memory(section) => ctx.memories += section.memories().len() as u32,
+                SectionData::Global(section) => {
+                    for global in section.globals() {
+                        let t = global.global_type();
+                        ctx.globals.push((
+                            Val::from(t.value_type()),
+                            matches!(t.mutability(), Mutability::Var),
+                        ));
+                        ctx.declare_refs(global.init());
+                    }
+                }
+                SectionData::Export(section) => {
+                    for export in section.exports() {
+                        if let ExportDesc::FuncIndex(index) = export.desc() {
+                            ctx.refs.insert(*index);
+                        }
+                    }
+                }
+                SectionData::Element(section) => {
+                    for element in section.elements() {
+                        ctx.elems.push(Val::from(element.element_type()));
+                        match element.init() {
+                            ElementInit::FuncIndexies(indexies) => ctx.refs.extend(indexies),
+                            ElementInit::Expressions(exprs) => {
+                                exprs.iter().for_each(|expr| ctx.declare_refs(expr))
+                            }
+                        }
+                    }
+                }
+                SectionData::DataCount(section) => ctx.data_count = Some(section.count()),
+                _ => {}
+            }
+        }
+        ctx
+    }
+
+    // 定数式の中の ref.func
+    fn declare_refs(&mut self, expr: &Expression) {
+        for instr in expr.instrs() {
+            if let Instruction::Reference(ReferenceInstruction::RefFunc(index)) = instr {
+                self.refs.insert(*index);
+            }
+        }
+    }
+
+    // 関数本体と、import を含めた関数の index の組
+    fn codes<'c>(&self, module: &'c Module) -> Result<Vec<(u32, &'c Code)>> {
+        let codes: Vec<&Code> = module
+            .sections()
+            .iter()
+            .filter_map(|section| match section.payload_data() {
+                SectionData::Code(section) => Some(section.codes()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let defined = self.funcs.len() - self.imported_funcs as usize;
+        if defined != codes.len() {
+            return Err(ValidationError::FunctionCountMismatch {
+                funcs: defined,
+                codes: codes.len(),
+            });
+        }
+        Ok((self.imported_funcs..).zip(codes).collect())
+    }
+
+    fn validate_codes(&self, codes: &[(u32, &Code)]) -> Result<()> {
+        codes
+            .iter()
+            .try_for_each(|(func, code)| self.validate_code(*func, code))
+    }
+
+    fn validate_code(&self, func: u32, code: &Code) -> Result<()> {
+        let error = |message: String| ValidationError::Function { func, message };
+        let type_index = self.funcs[func as usize];
+        let func_type = self
+            .types
+            .get(type_index as usize)
+            .ok_or_else(|| error(format!("unknown type {}", type_index)))?;
+        let mut locals = vals(func_type.params_types().valu_types());
+        locals.extend(code.locals()?.iter().map(Val::from));
+        let results = vals(func_type.return_types().valu_types());
+        let mut validator = FuncValidator {
+            ctx: self,
+            locals,
+            results: results.clone(),
+            vals: Vec::new(),
+            ctrls: Vec::new(),
+        };
+        validator.push_ctrl(FrameKind::Func, Vec::new(), results);
+        validator
+            .run(code.expression()?.instrs())
+            .map_err(|(instr, message)| ValidationError::Instruction {
+                func,
+                instr,
+                message,
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Func,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+struct Frame {
+    kind: FrameKind,
+    params: Vec<Val>,
+    results: Vec<Val>,
+    // frame に入った時の operand スタックの高さ
+    height: usize,
+    unreachable: bool,
+}
+
+impl Frame {
+    // br で渡す値の型。loop は先頭に戻るので引数になる
+    fn label_types(&self) -> &[Val] {
+        match self.kind {
+            FrameKind::Loop => &self.params,
+            _ => &self.results,
+        }
+    }
+}
+
+// まだ辿っていない命令列と、block の終わりの印
+enum Work<'a> {
+    Instrs(&'a [Instruction]),
+    Else(&'a [Instruction]),
+    End,
+}
+
+type Check<T> = std::result::Result<T, String>;
+
+struct FuncValidator<'c, 'm> {
+    ctx: &'c Context<'m>,
+    locals: Vec<Val>,
+    results: Vec<Val>,
+    // None は unreachable の後に積まれた、どの型にもなれる値
+    vals: Vec<Option<Val>>,
+    ctrls: Vec<Frame>,
+}
+
+impl FuncValidator<'_, '_> {
+    // エラーの場合は命令の番号とメッセージを返す
+    fn run(&mut self, instrs: &[Instruction]) -> std::result::Result<(), (u32, String)> {
+        let mut work = vec![Work::End, Work::Instrs(instrs)];
+        let mut index = 0;
+        while let Some(item) = work.pop() {
+            let result = match item {
+                Work::Instrs(instrs) => {
+                    let (instr, rest) = match instrs.split_first() {
+                        Some(x) => x,
+                        None => continue,
+                    };
+                    work.push(Work::Instrs(rest));
+                    self.instr(instr, &mut work)
+                }
+                Work::Else(instrs) => {
+                    work.push(Work::Instrs(instrs));
+                    self.pop_ctrl()
+                        .map(|frame| self.push_ctrl(FrameKind::Else, frame.params, frame.results))
+                }
+                Work::End => self.end(),
+            };
+            result.map_err(|message| (index, message))?;
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn push_val(&mut self, val: Option<Val>) {
+        self.vals.push(val);
+    }
+
+    fn pop_val(&mut self) -> Check<Option<Val>> {
+        let frame = self.ctrls.last().unwrap();
+        if self.vals.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            return Err("type mismatch: operand stack is empty".to_string());
+        }
+        Ok(self.vals.pop().unwrap())
+    }
+
+    fn pop_expect(&mut self, expected: Val) -> Check<Option<Val>> {
+        match self.pop_val()? {
+            Some(actual) if actual != expected => Err(format!(
+                "type mismatch: expected {}, got {}",
+                expected, actual
+            )),
+            actual => Ok(actual.or(Some(expected))),
+        }
+    }
+
+    fn push_vals(&mut self, vals: &[Val]) {
+        self.vals.extend(vals.iter().copied().map(Some));
+    }
+
+    fn pop_vals(&mut self, vals: &[Val]) -> Check<Vec<Option<Val>>> {
+        let mut popped = vals
+            .iter()
+            .rev()
+            .map(|val| self.pop_expect(*val))
+            .collect::<Check<Vec<_>>>()?;
+        popped.reverse();
+        Ok(popped)
+    }
+
+    // params を取り出して results を積む
+    fn op(&mut self, params: &[Val], results: &[Val]) -> Check<()> {
+        self.pop_vals(params)?;
+        self.push_vals(results);
+        Ok(())
+    }
+
+    fn push_ctrl(&mut self, kind: FrameKind, params: Vec<Val>, results: Vec<Val>) {
+        let height = self.vals.len();
+        self.push_vals(&params);
+        self.ctrls.push(Frame {
+            kind,
+            params,
+            results,
+            height,
+            unreachable: false,
+        });
+    }
+
+    fn pop_ctrl(&mut self) -> Check<Frame> {
+        let results = self.ctrls.last().unwrap().results.clone();
+        self.pop_vals(&results)?;
+        let frame = self.ctrls.pop().unwrap();
+        if self.vals.len() != frame.height {
+            return Err("type mismatch: values remain at end of block".to_string());
+        }
+        Ok(frame)
+    }
+
+    fn unreachable(&mut self) {
+        let frame = self.ctrls.last_mut().unwrap();
+        self.vals.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    fn end(&mut self) -> Check<()> {
+        let frame = self.pop_ctrl()?;
+        // else のない if は、引数をそのまま結果として渡す else があるものとして扱う
+        if frame.kind == FrameKind::If && frame.params != frame.results {
+            return Err("type mismatch: if without else must return its params".to_string());
+        }
+        self.push_vals(&frame.results);
+        Ok(())
+    }
+
+    fn label(&self, depth: u32) -> Check<Vec<Val>> {
+        let frame = self
+            .ctrls
+            .len()
+            .checked_sub(depth as usize + 1)
+            .map(|i| &self.ctrls[i])
+            .ok_or_else(|| format!("unknown label {}", depth))?;
+        Ok(frame.label_types().to_vec())
+    }
+
+    fn block_type(&self, block_type: &BlockType) -> Check<(Vec<Val>, Vec<Val>)> {
+        match block_type {
+            BlockType::Empty => Ok((Vec::new(), Vec::new())),
+            BlockType::Value(t) => Ok((Vec::new(), vec![Val::from(t)])),
+            BlockType::TypeIndex(index) => self.func_type(*index),
+        }
+    }
+
+    fn func_type(&self, index: u32) -> Check<(Vec<Val>, Vec<Val>)> {
+        let func_type = self
+            .ctx
+            .types
+            .get(index as usize)
+            .ok_or_else(|| format!("unknown type {}", index))?;
+        Ok((
+            vals(func_type.params_types().valu_types()),
+            vals(func_type.return_types().valu_types()),
+        ))
+    }
+
+    fn local(&self, index: u32) -> Check<Val> {
+        self.locals
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| format!("unknown local {}", index))
+    }
+
+    fn global(&self, index: u32) -> Check<(Val, bool)> {
+        self.ctx
+            .globals
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| format!("unknown global {}", index))
+    }
+
+    fn table(&self, index: u32) -> Check<Val> {
+        self.ctx
+            .tables
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| format!("unknown table {}", index))
+    }
+
+    fn elem(&self, index: u32) -> Check<Val> {
+        self.ctx
+            .elems
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| format!("unknown elem segment {}", index))
+    }
+
+    // memory.init と data.drop は data count section がないと使えない
+    fn data(&self, index: u32) -> Check<()> {
+        let count = self
+            .ctx
+            .data_count
+            .ok_or("data count section is required")?;
+        if index >= count {
+            return Err(format!("unknown data segment {}", index));
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> Check<()> {
+        if self.ctx.memories == 0 {
+            return Err("unknown memory 0".to_string());
+        }
+        Ok(())
+    }
+
+    // align は 2 の指数で、アクセスするバイト数を越えてはいけない
+    fn memarg(&self, memarg: &MemArg, bytes: u32) -> Check<()> {
+        self.memory()?;
+        if memarg.align() >= 32 || 1 << memarg.align() > bytes {
+            return Err(format!(
+                "alignment must not be larger than natural. align={}, bytes={}",
+                memarg.align(),
+                bytes
+            ));
+        }
+        Ok(())
+    }
+
+    fn instr<'a>(&mut self, instr: &'a Instruction, work: &mut Vec<Work<'a>>) -> Check<()> {
+        match instr {
+            Instruction::Control(instr) => self.control(instr, work),
+            Instruction::Numeric(NumericInstruction::Const(instr)) => {
+                use crate::ast::instruction::ConstNumericInstruction::*;
+                let t = match instr {
+                    ConstI32(_) => Val::I32,
+                    ConstI64(_) => Val::I64,
+                    ConstF32(_) => Val::F32,
+                    ConstF64(_) => Val::F64,
+                };
+                self.op(&[], &[t])
+            }
+            Instruction::Numeric(NumericInstruction::Plain(instr)) => {
+                let (params, results) = numeric(instr);
+                self.op(params, results)
+            }
+            Instruction::Numeric(NumericInstruction::SaturatingTruncation(instr)) => {
+                use SaturatingTruncationInstruction::*;
+                let (param, result) = match instr {
+                    TruncSatSI32F32 | TruncSatUI32F32 => (Val::F32, Val::I32),
+                    TruncSatSI32F64 | TruncSatUI32F64 => (Val::F64, Val::I32),
+                    TruncSatSI64F32 | TruncSatUI64F32 => (Val::F32, Val::I64),
+                    TruncSatSI64F64 | TruncSatUI64F64 => (Val::F64, Val::I64),
+                };
+                self.op(&[param], &[result])
+            }
+            Instruction::Reference(instr) => self.reference(instr),
+            Instruction::Parametric(instr) => self.parametric(instr),
+            Instruction::Variable(instr) => self.variable(instr),
+            Instruction::Table(instr) => self.table_instr(instr),
+            Instruction::Memory(instr) => self.memory_instr(instr),
+            Instruction::Vector(instr) => self.vector(instr),
+        }
+    }
+
+    fn control<'a>(
+        &mut self,
+        instr: &'a ControlInstruction,
+        work: &mut Vec<Work<'a>>,
+    ) -> Check<()> {
+        match instr {
+            ControlInstruction::Unreachable => self.unreachable(),
+            ControlInstruction::Nop => {}
+            ControlInstruction::Block { block_type, instrs }
+            | ControlInstruction::Loop { block_type, instrs } => {
+                let (params, results) = self.block_type(block_type)?;
+                self.pop_vals(&params)?;
+                let kind = match instr {
+                    ControlInstruction::Loop { .. } => FrameKind::Loop,
+                    _ => FrameKind::Block,
+                };
+                self.push_ctrl(kind, params, results);
+                work.push(Work::End);
+                work.push(Work::Instrs(instrs));
+            }
+            ControlInstruction::IfElse {
+                block_type,
+                then_instrs,
+                else_instrs,
+            } => {
+                let (params, results) = self.block_type(block_type)?;
+                self.pop_expect(Val::I32)?;
+                self.pop_vals(&params)?;
+                self.push_ctrl(FrameKind::If, params, results);
+                work.push(Work::End);
+                if let Some(else_instrs) = else_instrs {
+                    work.push(Work::Else(else_instrs));
+                }
+                work.push(Work::Instrs(then_instrs));
+            }
+            ControlInstruction::Br(depth) => {
+                let types = self.label(*depth)?;
+                self.pop_vals(&types)?;
+                self.unreachable();
+            }
+            ControlInstruction::BrIf(depth) => {
+                self.pop_expect(Val::I32)?;
+                let types = self.label(*depth)?;
+                self.op(&types, &types)?;
+            }
+            ControlInstruction::BrTable { labels, default } => {
+                self.pop_expect(Val::I32)?;
+                let arity = self.label(*default)?.len();
+                for depth in labels {
+                    let types = self.label(*depth)?;
+                    if types.len() != arity {
+                        return Err(format!(
+                            "type mismatch: br_table label {} has {} values, default has {}",
+                            depth,
+                            types.len(),
+                            arity
+                        ));
+                    }
+                    let popped = self.pop_vals(&types)?;
+                    self.vals.extend(popped);
+                }
+                let types = self.label(*default)?;
+                self.pop_vals(&types)?;
+                self.unreachable();
+            }
+            ControlInstruction::Return => {
+                let results = self.results.clone();
+                self.pop_vals(&results)?;
+                self.unreachable();
+            }
+            ControlInstruction::Call(index) => {
+                let type_index = *self
+                    .ctx
+                    .funcs
+                    .get(*index as usize)
+                    .ok_or_else(|| format!("unknown function {}", index))?;
+                let (params, results) = self.func_type(type_index)?;
+                self.op(&params, &results)?;
+            }
+            ControlInstruction::CallIndirect {
+                type_index,
+                table_index,
+            } => {
+                if self.table(*table_index)? != Val::FuncRef {
+                    return Err(format!("table {} is not a funcref table", table_index));
+                }
+                let (params, results) = self.func_type(*type_index)?;
+                self.pop_expect(Val::I32)?;
+                self.op(&params, &results)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn reference(&mut self, instr: &ReferenceInstruction) -> Check<()> {
+        match instr {
+            ReferenceInstruction::RefNull(t) => self.push_vals(&[Val::from(t)]),
+            ReferenceInstruction::RefIsNull => {
+                if let Some(t) = self.pop_val()? {
+                    if !t.is_ref() {
+                        return Err(format!("type mismatch: expected reference, got {}", t));
+                    }
+                }
+                self.push_vals(&[Val::I32]);
+            }
+            ReferenceInstruction::RefFunc(index) => {
+                if *index as usize >= self.ctx.funcs.len() {
+                    return Err(format!("unknown function {}", index));
+                }
+                if !self.ctx.refs.contains(index) {
+                    return Err(format!("undeclared function reference {}", index));
+                }
+                self.push_vals(&[Val::FuncRef]);
+            }
+        }
+        Ok(())
+    }
+
+    fn parametric(&mut self, instr: &ParametricInstruction) -> Check<()> {
+        match instr {
+            ParametricInstruction::Drop => {
+                self.pop_val()?;
+            }
+            // 型を書かない select は数値とベクタだけに使える
+            ParametricInstruction::Select => {
+                self.pop_expect(Val::I32)?;
+                let first = self.pop_val()?;
+                let second = self.pop_val()?;
+                if let Some(t) = first.or(second).filter(|t| t.is_ref()) {
+                    return Err(format!("type mismatch: select without type got {}", t));
+                }
+                if let (Some(first), Some(second)) = (first, second) {
+                    if first != second {
+                        return Err(format!(
+                            "type mismatch: select operands are {} and {}",
+                            second, first
+                        ));
+                    }
+                }
+                self.push_val(first.or(second));
+            }
+            ParametricInstruction::SelectTyped(types) => {
+                let t = match types.as_slice() {
+                    [t] => Val::from(t),
+                    _ => return Err("select must have exactly one result type".to_string()),
+                };
+                self.op(&[t, t, Val::I32], &[t])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn variable(&mut self, instr: &VariableInstruction) -> Check<()> {
+        match instr {
+            VariableInstruction::LocalGet(index) => {
+                let t = self.local(*index)?;
+                self.op(&[], &[t])
+            }
+            VariableInstruction::LocalSet(index) => {
+                let t = self.local(*index)?;
+                self.op(&[t], &[])
+            }
+            VariableInstruction::LocalTee(index) => {
+                let t = self.local(*index)?;
+                self.op(&[t], &[t])
+            }
+            VariableInstruction::GlobalGet(index) => {
+                let (t, _) = self.global(*index)?;
+                self.op(&[], &[t])
+            }
+            VariableInstruction::GlobalSet(index) => {
+                let (t, mutable) = self.global(*index)?;
+                if !mutable {
+                    return Err(format!("global {} is immutable", index));
+                }
+                self.op(&[t], &[])
+            }
+        }
+    }
+
+    fn table_instr(&mut self, instr: &TableInstruction) -> Check<()> {
+        use Val::I32;
+        match instr {
+            TableInstruction::Get(index) => {
+                let t = self.table(*index)?;
+                self.op(&[I32], &[t])
+            }
+            TableInstruction::Set(index) => {
+                let t = self.table(*index)?;
+                self.op(&[I32, t], &[])
+            }
+            TableInstruction::Init {
+                elem_index,
+                table_index,
+            } => {
+                let (table, elem) = (self.table(*table_index)?, self.elem(*elem_index)?);
+                if table != elem {
+                    return Err(format!(
+                        "type mismatch: table is {}, elem segment is {}",
+                        table, elem
+                    ));
+                }
+                self.op(&[I32, I32, I32], &[])
+            }
+            TableInstruction::ElemDrop(index) => self.elem(*index).map(|_| ()),
+            TableInstruction::Copy { dst, src } => {
+                let (dst, src) = (self.table(*dst)?, self.table(*src)?);
+                if dst != src {
+                    return Err(format!(
+                        "type mismatch: copying {} table to {} table",
+                        src, dst
+                    ));
+                }
+                self.op(&[I32, I32, I32], &[])
+            }
+            TableInstruction::Grow(index) => {
+                let t = self.table(*index)?;
+                self.op(&[t, I32], &[I32])
+            }
+            TableInstruction::Size(index) => {
+                self.table(*index)?;
+                self.op(&[], &[I32])
+            }
+            TableInstruction::Fill(index) => {
+                let t = self.table(*index)?;
+                self.op(&[I32, t, I32], &[])
+            }
+        }
+    }
+
+    fn memory_instr(&mut self, instr: &MemoryInstruction) -> Check<()> {
+        use MemoryInstruction::*;
+        use Val::{F32, F64, I32, I64};
+        let (memarg, bytes, params, results): (_, _, &[Val], &[Val]) = match instr {
+            LoadI32(m) => (m, 4, &[I32], &[I32]),
+            LoadI64(m) => (m, 8, &[I32], &[I64]),
+            LoadF32(m) => (m, 4, &[I32], &[F32]),
+            LoadF64(m) => (m, 8, &[I32], &[F64]),
+            Load8SI32(m) | Load8UI32(m) => (m, 1, &[I32], &[I32]),
+            Load16SI32(m) | Load16UI32(m) => (m, 2, &[I32], &[I32]),
+            Load8SI64(m) | Load8UI64(m) => (m, 1, &[I32], &[I64]),
+            Load16SI64(m) | Load16UI64(m) => (m, 2, &[I32], &[I64]),
+            Load32SI64(m) | Load32UI64(m) => (m, 4, &[I32], &[I64]),
+            StoreI32(m) => (m, 4, &[I32, I32], &[]),
+            StoreI64(m) => (m, 8, &[I32, I64], &[]),
+            StoreF32(m) => (m, 4, &[I32, F32], &[]),
+            StoreF64(m) => (m, 8, &[I32, F64], &[]),
+            Store8I32(m) => (m, 1, &[I32, I32], &[]),
+            Store16I32(m) => (m, 2, &[I32, I32], &[]),
+            Store8I64(m) => (m, 1, &[I32, I64], &[]),
+            Store16I64(m) => (m, 2, &[I32, I64], &[]),
+            Store32I64(m) => (m, 4, &[I32, I64], &[]),
+            Size => {
+                self.memory()?;
+                return self.op(&[], &[I32]);
+            }
+            Grow => {
+                self.memory()?;
+                return self.op(&[I32], &[I32]);
+            }
+            Init(index) => {
+                self.memory()?;
+                self.data(*index)?;
+                return self.op(&[I32, I32, I32], &[]);
+            }
+            DataDrop(index) => return self.data(*index),
+            Copy | Fill => {
+                self.memory()?;
+                return self.op(&[I32, I32, I32], &[]);
+            }
+        };
+        self.memarg(memarg, bytes)?;
+        self.op(params, results)
+    }
+
+    fn vector(&mut self, instr: &VectorInstruction) -> Check<()> {
+        use Val::{F32, F64, I32, I64, V128};
+        use VectorInstruction::*;
+        let (params, results): (&[Val], &[Val]) = match instr {
+            Load(m) => return self.vector_memory(m, 16, None, &[I32], &[V128]),
+            Load8x8S(m) | Load8x8U(m) | Load16x4S(m) | Load16x4U(m) | Load32x2S(m)
+            | Load32x2U(m) => return self.vector_memory(m, 8, None, &[I32], &[V128]),
+            Load8Splat(m) => return self.vector_memory(m, 1, None, &[I32], &[V128]),
+            Load16Splat(m) => return self.vector_memory(m, 2, None, &[I32], &[V128]),
+            Load32Splat(m) | Load32Zero(m) => {
+                return self.vector_memory(m, 4, None, &[I32], &[V128])
+            }
+            Load64Splat(m) | Load64Zero(m) => {
+                return self.vector_memory(m, 8, None, &[I32], &[V128])
+            }
+            Store(m) => return self.vector_memory(m, 16, None, &[I32, V128], &[]),
+            Load8Lane(m, lane) => {
+                return self.vector_memory(m, 1, Some(*lane), &[I32, V128], &[V128])
+            }
+            Load16Lane(m, lane) => {
+                return self.vector_memory(m, 2, Some(*lane), &[I32, V128], &[V128])
+            }
+            Load32Lane(m, lane) => {
+                return self.vector_memory(m, 4, Some(*lane), &[I32, V128], &[V128])
+            }
+            Load64Lane(m, lane) => {
+                return self.vector_memory(m, 8, Some(*lane), &[I32, V128], &[V128])
+            }
+            Store8Lane(m, lane) => return self.vector_memory(m, 1, Some(*lane), &[I32, V128], &[]),
+            Store16Lane(m, lane) => {
+                return self.vector_memory(m, 2, Some(*lane), &[I32, V128], &[])
+            }
+            Store32Lane(m, lane) => {
+                return self.vector_memory(m, 4, Some(*lane), &[I32, V128], &[])
+            }
+            Store64Lane(m, lane) => {
+                return self.vector_memory(m, 8, Some(*lane), &[I32, V128], &[])
+            }
+            Const(_) => (&[], &[V128]),
+            ShuffleI8x16(lanes) => {
+                if let Some(lane) = lanes.iter().find(|&&lane| lane >= 32) {
+                    return Err(format!("invalid lane index {}", lane));
+                }
+                (&[V128, V128], &[V128])
+            }
+            SplatI8x16 | SplatI16x8 | SplatI32x4 => (&[I32], &[V128]),
+            SplatI64x2 => (&[I64], &[V128]),
+            SplatF32x4 => (&[F32], &[V128]),
+            SplatF64x2 => (&[F64], &[V128]),
+            ExtractLaneSI8x16(lane) | ExtractLaneUI8x16(lane) => {
+                lane_index(*lane, 16)?;
+                (&[V128], &[I32])
+            }
+            ExtractLaneSI16x8(lane) | ExtractLaneUI16x8(lane) => {
+                lane_index(*lane, 8)?;
+                (&[V128], &[I32])
+            }
+            ExtractLaneI32x4(lane) => {
+                lane_index(*lane, 4)?;
+                (&[V128], &[I32])
+            }
+            ExtractLaneI64x2(lane) => {
+                lane_index(*lane, 2)?;
+                (&[V128], &[I64])
+            }
+            ExtractLaneF32x4(lane) => {
+                lane_index(*lane, 4)?;
+                (&[V128], &[F32])
+            }
+            ExtractLaneF64x2(lane) => {
+                lane_index(*lane, 2)?;
+                (&[V128], &[F64])
+            }
+            ReplaceLaneI8x16(lane) => {
+                lane_index(*lane, 16)?;
+                (&[V128, I32], &[V128])
+            }
+            ReplaceLaneI16x8(lane) => {
+                lane_index(*lane, 8)?;
+                (&[V128, I32], &[V128])
+            }
+            ReplaceLaneI32x4(lane) => {
+                lane_index(*lane, 4)?;
+                (&[V128, I32], &[V128])
+            }
+            ReplaceLaneI64x2(lane) => {
+                lane_index(*lane, 2)?;
+                (&[V128, I64], &[V128])
+            }
+            ReplaceLaneF32x4(lane) => {
+                lane_index(*lane, 4)?;
+                (&[V128, F32], &[V128])
+            }
+            ReplaceLaneF64x2(lane) => {
+                lane_index(*lane, 2)?;
+                (&[V128, F64], &[V128])
+            }
+            BitselectV128 => (&[V128, V128, V128], &[V128]),
+            AnyTrueV128 | AllTrueI8x16 | AllTrueI16x8 | AllTrueI32x4 | AllTrueI64x2
+            | BitmaskI8x16 | BitmaskI16x8 | BitmaskI32x4 | BitmaskI64x2 => (&[V128], &[I32]),
+            ShlI8x16 | ShrSI8x16 | ShrUI8x16 | ShlI16x8 | ShrSI16x8 | ShrUI16x8 | ShlI32x4
+            | ShrSI32x4 | ShrUI32x4 | ShlI64x2 | ShrSI64x2 | ShrUI64x2 => (&[V128, I32], &[V128]),
+            NotV128
+            | AbsI8x16
+            | NegI8x16
+            | PopcntI8x16
+            | AbsI16x8
+            | NegI16x8
+            | AbsI32x4
+            | NegI32x4
+            | AbsI64x2
+            | NegI64x2
+            | AbsF32x4
+            | NegF32x4
+            | SqrtF32x4
+            | CeilF32x4
+            | FloorF32x4
+            | TruncF32x4
+            | NearestF32x4
+            | AbsF64x2
+            | NegF64x2
+            | SqrtF64x2
+            | CeilF64x2
+            | FloorF64x2
+            | TruncF64x2
+            | NearestF64x2
+            | ExtaddPairwiseSI16x8I8x16
+            | ExtaddPairwiseUI16x8I8x16
+            | ExtaddPairwiseSI32x4I16x8
+            | ExtaddPairwiseUI32x4I16x8
+            | ExtendLowSI16x8I8x16
+            | ExtendHighSI16x8I8x16
+            | ExtendLowUI16x8I8x16
+            | ExtendHighUI16x8I8x16
+            | ExtendLowSI32x4I16x8
+            | ExtendHighSI32x4I16x8
+            | ExtendLowUI32x4I16x8
+            | ExtendHighUI32x4I16x8
+            | ExtendLowSI64x2I32x4
+            | ExtendHighSI64x2I32x4
+            | ExtendLowUI64x2I32x4
+            | ExtendHighUI64x2I32x4
+            | TruncSatSI32x4F32x4
+            | TruncSatUI32x4F32x4
+            | ConvertSF32x4I32x4
+            | ConvertUF32x4I32x4
+            | TruncSatZeroSI32x4F64x2
+            | TruncSatZeroUI32x4F64x2
+            | ConvertLowSF64x2I32x4
+            | ConvertLowUF64x2I32x4
+            | DemoteZeroF32x4F64x2
+            | PromoteLowF64x2F32x4 => (&[V128], &[V128]),
+            // 残りは 2 つの v128 から v128 を作る lane ごとの演算
+            _ => (&[V128, V128], &[V128]),
+        };
+        self.op(params, results)
+    }
+
+    fn vector_memory(
+        &mut self,
+        memarg: &MemArg,
+        bytes: u32,
+        lane: Option<u8>,
+        params: &[Val],
+        results: &[Val],
+    ) -> Check<()> {
+        self.memarg(memarg, bytes)?;
+        if let Some(lane) = lane {
+            lane_index(lane, (16 / bytes) as u8)?;
+        }
+        self.op(params, results)
+    }
+}
+
+fn lane_index(lane: u8, count: u8) -> Check<()> {
+    if lane >= count {
+        return Err(format!("invalid lane index {}", lane));
+    }
+    Ok(())
+}
+
+// 数値命令の (引数の型, 結果の型)
+fn numeric(instr: &PlainNumericInstruction) -> (&'static [Val], &'static [Val]) {
+    use PlainNumericInstruction::*;
+    use Val::{F32, F64, I32, I64};
+    match instr {
+        EqzI32 | ClzI32 | CtzI32 | PopcntI32 | Extend8SI32 | Extend16SI32 => (&[I32], &[I32]),
+        EqI32 | NeI32 | LtSI32 | LtUI32 | GtSI32 | GtUI32 | LeSI32 | LeUI32 | GeSI32 | GeUI32 => {
+            (&[I32, I32], &[I32])
+        }
+        EqzI64 => (&[I64], &[I32]),
+        EqI64 | NeI64 | LtSI64 | LtUI64 | GtSI64 | GtUI64 | LeSI64 | LeUI64 | GeSI64 | GeUI64 => {
+            (&[I64, I64], &[I32])
+        }
+        EqF32 | NeF32 | LtF32 | GtF32 | LeF32 | GeF32 => (&[F32, F32], &[I32]),
+        EqF64 | NeF64 | LtF64 | GtF64 | LeF64 | GeF64 => (&[F64, F64], &[I32]),
+        AddI32 | SubI32 | MulI32 | DivSI32 | DivUI32 | RemSI32 | RemUI32 | AndI32 | OrI32
+        | XorI32 | ShlI32 | ShrSI32 | ShrUI32 | RotlI32 | RotrI32 => (&[I32, I32], &[I32]),
+        ClzI64 | CtzI64 | PopcntI64 | Extend8SI64 | Extend16SI64 | Extend32SI64 => (&[I64], &[I64]),
+        AddI64 | SubI64 | MulI64 | DivSI64 | DivUI64 | RemSI64 | RemUI64 | AndI64 | OrI64
+        | XorI64 | ShlI64 | ShrSI64 | ShrUI64 | RotlI64 | RotrI64 => (&[I64, I64], &[I64]),
+        AbsF32 | NegF32 | CeilF32 | FloorF32 | TruncF32 | NearestF32 | SqrtF32 => (&[F32], &[F32]),
+        AddF32 | SubF32 | MulF32 | DivF32 | MinF32 | MaxF32 | CopysignF32 => (&[F32, F32], &[F32]),
+        AbsF64 | NegF64 | CeilF64 | FloorF64 | TruncF64 | NearestF64 | SqrtF64 => (&[F64], &[F64]),
+        AddF64 | SubF64 | MulF64 | DivF64 | MinF64 | MaxF64 | CopysignF64 => (&[F64, F64], &[F64]),
+        WrapI32I64 => (&[I64], &[I32]),
+        TruncSI32F32 | TruncUI32F32 | ReinterpretI32F32 => (&[F32], &[I32]),
+        TruncSI32F64 | TruncUI32F64 => (&[F64], &[I32]),
+        ExtendSI64I32 | ExtendUI64I32 => (&[I32], &[I64]),
+        TruncSI64F32 | TruncUI64F32 => (&[F32], &[I64]),
+        TruncSI64F64 | TruncUI64F64 | ReinterpretI64F64 => (&[F64], &[I64]),
+        ConvertSF32I32 | ConvertUF32I32 | ReinterpretF32I32 => (&[I32], &[F32]),
+        ConvertSF32I64 | ConvertUF32I64 => (&[I64], &[F32]),
+        DemoteF32F64 => (&[F64], &[F32]),
+        ConvertSF64I32 | ConvertUF64I32 => (&[I32], &[F64]),
+        ConvertSF64I64 | ConvertUF64I64 | ReinterpretF64I64 => (&[I64], &[F64]),
+        PromoteF64F32 => (&[F32], &[F64]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_wat;
+
+    fn check(text: &str) -> Result<()> {
+        validate(&parse_wat(text).unwrap())
+    }
+
+    #[test]
+    fn test_validate() {
+        check(
+            r#"(module
+              (type $t (func (param i32) (result i32)))
+              (import "env" "f" (func $imported (type $t)))
+              (memory 1)
+              (table 1 funcref)
+              (global $g (mut i64) (i64.const 0))
+              (elem declare func $add)
+              (func $add (param i32 i32) (result i32)
+                (i32.add (local.get 0) (local.get 1)))
+              (func (param $x i32) (result i32) (local f64)
+                (block $b (result i32)
+                  (br_if $b (i32.const 1) (local.get $x))
+                  (loop $l (param i32) (result i32)
+                    (if (result i32)
+                      (then (i32.const 2))
+                      (else (br $l (i32.const 3)))))
+                  (call $imported)
+                  (call_indirect (type $t) (i32.const 0)))
+                (global.set $g (i64.extend_i32_u (i32.load offset=4 (i32.const 0))))
+                (drop (ref.func $add))
+                (select (local.get $x) (i32.const 0) (i32.const 1))
+                (unreachable)
+                (i32.add))
+              (func (param v128) (result i32)
+                (v128.store64_lane 1 (i32.const 0) (local.get 0))
+                (i8x16.extract_lane_s 15
+                  (i8x16.shuffle 0 1 2 3 4 5 6 7 16 17 18 19 20 21 22 31
+                    (local.get 0)
+                    (f32x4.splat (f32.const 1)))))
+              (func (result i32)
+                (br_table 0 0 (i32.const 1) (i32.const 0))))"#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_validate_error() {
+        let cases = [
+            (
+                "(module (func (result i32) (i64.const 0)))",
+                "func 0, instr 1: type mismatch: expected i32, got i64",
+            ),
+            (
+                "(module (func (i32.const 0)))",
+                "func 0, instr 1: type mismatch: values remain at end of block",
+            ),
+            (
+                "(module (func (drop)))",
+                "func 0, instr 0: type mismatch: operand stack is empty",
+            ),
+            (
+                "(module (func (result i32) (if (result i32) (i32.const 0) (then (i32.const 1)))))",
+                "func 0, instr 3: type mismatch: if without else must return its params",
+            ),
+            (
+                "(module (func (br 1)))",
+                "func 0, instr 0: unknown label 1",
+            ),
+            (
+                "(module (global i32 (i32.const 0)) (func (global.set 0 (i32.const 1))))",
+                "func 0, instr 1: global 0 is immutable",
+            ),
+            (
+                "(module (func (drop (i32.load (i32.const 0)))))",
+                "func 0, instr 1: unknown memory 0",
+            ),
+            (
+                "(module (memory 1) (func (drop (i32.load align=8 (i32.const 0)))))",
+                "func 0, instr 1: alignment must not be larger than natural. align=3, bytes=4",
+            ),
+            (
+                "(module (func $f (drop (ref.func $f))))",
+                "func 0, instr 0: undeclared function reference 0",
+            ),
+            (
+                "(module (func (select (ref.null func) (ref.null func) (i32.const 0)) (drop)))",
+                "func 0, instr 3: type mismatch: select without type got funcref",
+            ),
+            (
+                "(module (func (param v128) (drop (i32x4.extract_lane 4 (local.get 0)))))",
+                "func 0, instr 1: invalid lane index 4",
+            ),
+            (
+                "(module (memory 1) (func (param v128) (drop (v128.load16_lane 8 (i32.const 0) (local.get 0)))))",
+                "func 0, instr 2: invalid lane index 8",
+            ),
+            (
+                "(module (func (param v128) (result v128) (i8x16.add (local.get 0) (i32.const 0))))",
+                "func 0, instr 2: type mismatch: expected v128, got i32",
+            ),
+        ];
+        for (text, expected) in cases.iter() {
+            let err = check(text).expect_err(text).to_string();
+            assert_eq!(&err, expected, "{}", text);
+        }
+
+        // data count section のない data.drop
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x0a, 0x07, 0x01, 0x05, 0x00, 0xfc, 0x09, 0x00, 0x0b, // code section
+            0x0b, 0x03, 0x01, 0x01, 0x00, // data section
+        ];
+        let err = validate(&crate::parse_module(input).unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "func 0, instr 0: data count section is required"
+        );
+    }
+
+    #[test]
+    fn test_validate_imported_function_index() {
+        // func は import を含めた index
+        let err = check(
+            r#"(module
+              (import "env" "f" (func))
+              (func)
+              (func (result i32) (nop)))"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "func 2, instr 1: type mismatch: operand stack is empty"
+        );
+    }
+
+    #[test]
+    fn test_validate_parallel() {
+        // import した関数の後に 1 つの正しい関数と、多数の関数を並べる
+        let funcs = |bad: &[usize]| {
+            let mut text = String::from("(module (import \"env\" \"f\" (func))");
+            for i in 0..64 {
+                if bad.contains(&i) {
+                    text.push_str("(func (result i32) (i64.const 0))");
+                } else {
+                    text.push_str("(func (result i32) (i32.const 0))");
+                }
+            }
+            text.push(')');
+            parse_wat(&text).unwrap()
+        };
+        validate_parallel(&funcs(&[])).unwrap();
+        for bad in [&[5, 40][..], &[63], &[0, 1]].iter() {
+            let module = funcs(bad);
+            let expected = validate(&module).unwrap_err().to_string();
+            let err = validate_parallel(&module).unwrap_err().to_string();
+            assert_eq!(err, expected);
+            assert!(
+                err.starts_with(&format!("func {}, ", bad[0] + 1)),
+                "{}",
+                err
+            );
+        }
+
+        // デコードのエラーも関数の index が最も小さいものを返す
+        let module = funcs(&[]);
+        let mut bytes = module.encode();
+        let end = bytes.len() - 1;
+        assert_eq!(bytes[end], 0x0b);
+        bytes[end] = 0x41;
+        let module = crate::parse_module(&bytes).unwrap();
+        let err = validate_parallel(&module).unwrap_err();
+        assert!(matches!(err, ValidationError::Parse(_)), "{}", err);
+        assert_eq!(err.to_string(), validate(&module).unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_validate_deep_nesting() {
+        // 入れ子が深くてもスタックを使い果たさない
+        let depth = 100_000;
+        let mut body = vec![0x00];
+        body.extend([0x02, 0x40].repeat(depth));
+        body.extend(vec![0x0b; depth + 1]);
+        let mut code = vec![0x01];
+        crate::encode::encode_len(&mut code, &body);
+        let mut input = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        input.extend([
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02, 0x01, 0x00, 0x0a,
+        ]);
+        crate::encode::encode_len(&mut input, &code);
+        let module = crate::parse_module(&input).unwrap();
+        validate(&module).unwrap();
+    }
+}
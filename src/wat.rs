@@ -0,0 +1,780 @@
+mod lexer;
+mod opcode;
+mod parser;
+mod printer;
+
+use thiserror::Error;
+
+use crate::ast::{self, module};
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("{line}:{column}: {message}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[error("invalid module: {0}")]
+    Binary(#[from] ast::Error),
+}
+
+// S 式の入れ子の深さの上限。parser も printer も S 式の入れ子を再帰で辿るので、
+// スタックが溢れる前にエラーにする。flat に書いた block の入れ子には上限はない
+const MAX_NESTING: usize = 256;
+
+// テキスト中のバイト位置で持つエラー。外に出す時に行と列に直す
+struct ErrorAt {
+    offset: usize,
+    message: String,
+}
+
+impl ErrorAt {
+    fn new(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    fn into_error(self, text: &str) -> Error {
+        let before = &text[..self.offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Error::Syntax {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message: self.message,
+        }
+    }
+}
+
+/// テキスト形式 (WAT) の wasm モジュールをパースする
+///
+/// `$name` を index に解決してバイナリ形式に変換し、`parse_module` と同じ AST を返す
+pub fn parse_wat(text: &str) -> Result<module::Module, Error> {
+    let bytes = parser::parse(text).map_err(|e| e.into_error(text))?;
+    Ok(ast::parse_module(&bytes)?)
+}
+
+/// `print_wat` の命令の書き方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    /// 1 行に 1 命令を並べる
+    #[default]
+    Flat,
+    /// operand を入れ子の S 式にまとめる
+    Folded,
+}
+
+/// module をテキスト形式 (WAT) で書き出す
+///
+/// name section に名前があれば `$name` を使い、なければ index で書く
+pub fn print_wat(module: &module::Module, style: Style) -> Result<String, ast::Error> {
+    printer::Printer::new(module, style).print()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::section::SectionData;
+
+    fn wat_to_bytes(text: &str) -> Vec<u8> {
+        parse_wat(text).unwrap().encode()
+    }
+
+    fn expression(module: &module::Module) -> Vec<u8> {
+        let code = module
+            .sections()
+            .iter()
+            .find_map(|s| match s.payload_data() {
+                SectionData::Code(cs) => Some(&cs.codes()[0]),
+                _ => None,
+            })
+            .unwrap();
+        let mut buf = Vec::new();
+        code.expression().unwrap().encode(&mut buf);
+        buf
+    }
+
+    #[test]
+    fn test_parse_wat() {
+        let input = r#"
+            (module
+              (func $add (export "add") (param $lhs i32) (param $rhs i32) (result i32)
+                local.get $lhs
+                local.get $rhs
+                i32.add))
+        "#;
+        let expected: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export section
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // code section
+        ];
+        assert_eq!(wat_to_bytes(input), expected);
+    }
+
+    #[test]
+    fn test_parse_wat_export() {
+        // evaluator の export テストと同じモジュール
+        let input = r#"
+            (module
+              (func $f)
+              (table $t 1 funcref)
+              (memory $m 1)
+              (global $g i32 (i32.const 0))
+              (export "f" (func $f))
+              (export "t" (table $t))
+              (export "memory" (memory $m))
+              (export "g" (global $g)))
+        "#;
+        let expected: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x04, 0x04, 0x01, 0x70, 0x00, 0x01, // table section
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, // global section
+            0x07, 0x16, 0x04, // export section
+            0x01, 0x66, 0x00, 0x00, // f
+            0x01, 0x74, 0x01, 0x00, // t
+            0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, // memory
+            0x01, 0x67, 0x03, 0x00, // g
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+        ];
+        assert_eq!(wat_to_bytes(input), expected);
+    }
+
+    #[test]
+    fn test_parse_wat_folded() {
+        // 折り畳んだ書き方と平たい書き方は同じ命令列になる
+        let folded = r#"
+            (func (param i32) (result i32)
+              (if (result i32) (i32.eqz (local.get 0))
+                (then (i32.const 1))
+                (else (i32.mul (local.get 0) (i32.const 2)))))
+        "#;
+        let flat = r#"
+            (func (param i32) (result i32)
+              local.get 0
+              i32.eqz
+              if (result i32)
+                i32.const 1
+              else
+                local.get 0
+                i32.const 2
+                i32.mul
+              end)
+        "#;
+        assert_eq!(wat_to_bytes(folded), wat_to_bytes(flat));
+
+        let module = parse_wat(folded).unwrap();
+        assert_eq!(
+            expression(&module),
+            [
+                0x20, 0x00, 0x45, 0x04, 0x7f, 0x41, 0x01, 0x05, 0x20, 0x00, 0x41, 0x02, 0x6c, 0x0b,
+                0x0b
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_wat_labels() {
+        let input = r#"
+            (func $loop (param $n i32) (local $i i32)
+              (block $done
+                (loop $next
+                  (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                  (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                  (br $next)))
+              block $a
+                block $b
+                  br_table $a $b 0
+                end $b
+              end)
+        "#;
+        let module = parse_wat(input).unwrap();
+        assert_eq!(
+            expression(&module),
+            [
+                0x02, 0x40, 0x03, 0x40, // block, loop
+                0x20, 0x01, 0x20, 0x00, 0x4f, 0x0d, 0x01, // br_if $done
+                0x20, 0x01, 0x41, 0x01, 0x6a, 0x21, 0x01, // local.set $i
+                0x0c, 0x00, 0x0b, 0x0b, // br $next
+                0x02, 0x40, 0x02, 0x40, 0x0e, 0x02, 0x01, 0x00, 0x00, 0x0b, 0x0b, // br_table
+                0x0b,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_wat_imports() {
+        // import は同じ種類の定義より前の index になる
+        let input = r#"
+            (module
+              (type $v (func))
+              (func $g (call $f))
+              (import "env" "f" (func $f (type $v)))
+              (func $h (import "env" "h") (param i32))
+              (global $x (import "env" "x") (mut i32))
+              (memory (import "env" "mem") 1 2)
+              (table 2 externref)
+              (func (export "run") (call $g) (call $h (i32.const 0)) (global.set $x (i32.const 1))))
+        "#;
+        let module = parse_wat(input).unwrap();
+        let imports = match module.sections()[1].payload_data() {
+            SectionData::Import(is) => is,
+            _ => panic!("expected import section"),
+        };
+        let names: Vec<&[u8]> = imports.imports().iter().map(|i| i.name()).collect();
+        assert_eq!(names, [&b"f"[..], b"h", b"x", b"mem"]);
+        let ids: Vec<u8> = module.sections().iter().map(|s| s.id()).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 7, 10]);
+        match module.sections()[4].payload_data() {
+            SectionData::Export(es) => match es.exports()[0].desc() {
+                crate::ast::section::ExportDesc::FuncIndex(index) => assert_eq!(*index, 3),
+                _ => panic!("expected func export"),
+            },
+            _ => panic!("expected export section"),
+        }
+        match module.sections()[5].payload_data() {
+            SectionData::Code(cs) => {
+                let mut buf = Vec::new();
+                cs.codes()[0].expression().unwrap().encode(&mut buf);
+                assert_eq!(buf, [0x10, 0x00, 0x0b]);
+                let mut buf = Vec::new();
+                cs.codes()[1].expression().unwrap().encode(&mut buf);
+                assert_eq!(
+                    buf,
+                    [0x10, 0x02, 0x41, 0x00, 0x10, 0x01, 0x41, 0x01, 0x24, 0x00, 0x0b]
+                );
+            }
+            _ => panic!("expected code section"),
+        }
+    }
+
+    #[test]
+    fn test_parse_wat_segments() {
+        let input = r#"
+            (module
+              (memory $m (data "hi\00\ff"))
+              (table funcref (elem $f $f))
+              (func $f
+                (memory.init $d (i32.const 0) (i32.const 0) (i32.const 1))
+                (data.drop $d))
+              (elem declare func $f)
+              (elem (i32.const 1) $f)
+              (data $d "passive" "\u{3042}")
+              (data (memory $m) (offset (i32.const 8)) "x"))
+        "#;
+        let module = parse_wat(input).unwrap();
+        let ids: Vec<u8> = module.sections().iter().map(|s| s.id()).collect();
+        assert_eq!(ids, [1, 3, 4, 5, 9, 12, 10, 11]);
+        let bytes = module.encode();
+        let data_section: &[u8] = &[
+            0x0b, 0x1c, 0x03, // data section
+            0x00, 0x41, 0x00, 0x0b, 0x04, b'h', b'i', 0x00, 0xff, // inline data
+            0x01, 0x0a, b'p', b'a', b's', b's', b'i', b'v', b'e', 0xe3, 0x81, 0x82, // $d
+            0x00, 0x41, 0x08, 0x0b, 0x01, b'x',
+        ];
+        assert!(bytes.ends_with(data_section));
+        let elem_section: &[u8] = &[
+            0x09, 0x12, 0x03, // element section
+            0x00, 0x41, 0x00, 0x0b, 0x02, 0x00, 0x00, // inline elem
+            0x03, 0x00, 0x01, 0x00, // declare
+            0x00, 0x41, 0x01, 0x0b, 0x01, 0x00,
+        ];
+        assert!(bytes.windows(elem_section.len()).any(|w| w == elem_section));
+    }
+
+    #[test]
+    fn test_parse_wat_literals() {
+        let input = r#"
+            (func
+              i32.const -1 i32.const 0xffff_ffff i32.const 1_000
+              i64.const -0x8000_0000_0000_0000
+              f32.const 1.5 f32.const -inf f32.const nan:0x200000 f32.const 0x1.8p1
+              f64.const -0.0 f64.const 1e10 f64.const nan
+              v128.const i32x4 1 -1 0 0x10
+              i32.load8_u offset=4 align=1
+              i64.store
+              i8x16.shuffle 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+              select (result i32)
+              ref.null extern)
+        "#;
+        let module = parse_wat(input).unwrap();
+        let mut expected = vec![0x41, 0x7f, 0x41, 0x7f, 0x41, 0xe8, 0x07];
+        expected.extend([
+            0x42, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f,
+        ]);
+        expected.push(0x43);
+        expected.extend(1.5f32.to_le_bytes());
+        expected.push(0x43);
+        expected.extend(f32::NEG_INFINITY.to_le_bytes());
+        expected.push(0x43);
+        expected.extend(0x7fa0_0000u32.to_le_bytes());
+        expected.push(0x43);
+        expected.extend(3.0f32.to_le_bytes());
+        expected.push(0x44);
+        expected.extend((-0.0f64).to_le_bytes());
+        expected.push(0x44);
+        expected.extend(1e10f64.to_le_bytes());
+        expected.push(0x44);
+        expected.extend(0x7ff8_0000_0000_0000u64.to_le_bytes());
+        expected.extend([0xfd, 0x0c]);
+        for lane in [1u32, u32::MAX, 0, 0x10] {
+            expected.extend(lane.to_le_bytes());
+        }
+        expected.extend([0x2d, 0x00, 0x04, 0x37, 0x03, 0x00, 0xfd, 0x0d]);
+        expected.extend(0..16);
+        expected.extend([0x1c, 0x01, 0x7f, 0xd0, 0x6f, 0x0b]);
+        assert_eq!(expression(&module), expected);
+    }
+
+    #[test]
+    fn test_parse_wat_hex_float() {
+        // f32.const/f64.const の即値のビット列
+        fn bits(instr: &str) -> u64 {
+            let module = parse_wat(&format!("(func {} drop)", instr)).unwrap();
+            let code = expression(&module);
+            let mut bytes = [0; 8];
+            let len = if code[0] == 0x43 { 4 } else { 8 };
+            bytes[..len].copy_from_slice(&code[1..1 + len]);
+            u64::from_le_bytes(bytes)
+        }
+        let cases: &[(&str, u64)] = &[
+            // f64 を経由して 2 回丸めると 0x3f800000 になる
+            ("f32.const 0x1.00000100000001p0", 0x3f80_0001),
+            // ちょうど中間は偶数へ
+            ("f32.const 0x1.000001p0", 0x3f80_0000),
+            ("f32.const 0x1.000003p0", 0x3f80_0002),
+            ("f32.const 0x1.0000010p0", 0x3f80_0000),
+            ("f32.const 0x1.fffffep127", 0x7f7f_ffff),
+            ("f32.const 0x1.ffffffp127", 0x7f80_0000),
+            ("f32.const 0x1p128", 0x7f80_0000),
+            // 非正規化数
+            ("f32.const 0x1p-149", 0x0000_0001),
+            ("f32.const 0x1p-150", 0x0000_0000),
+            ("f32.const 0x1.000001p-150", 0x0000_0001),
+            ("f32.const 0x1.8p-149", 0x0000_0002),
+            ("f32.const 0x1.fffffcp-127", 0x007f_ffff),
+            // 非正規化数の最大値との中間は正規化数へ繰り上がる
+            ("f32.const 0x1.fffffep-127", 0x0080_0000),
+            ("f32.const -0x1p-126", 0x8080_0000),
+            // 64 bit に入りきらない桁は sticky bit として残す
+            (
+                "f64.const 0x1.00000000000008000000001p0",
+                0x3ff0_0000_0000_0001,
+            ),
+            ("f64.const 0x1.00000000000008p0", 0x3ff0_0000_0000_0000),
+            ("f64.const 0x1.00000000000018p0", 0x3ff0_0000_0000_0002),
+            ("f64.const 0x1_0000_0000_0000_0800p0", 0x43f0_0000_0000_0000),
+            ("f64.const 0x1_0000_0000_0000_0801p0", 0x43f0_0000_0000_0001),
+            ("f64.const 0x1p-1074", 0x0000_0000_0000_0001),
+            ("f64.const 0x1p-1075", 0x0000_0000_0000_0000),
+            ("f64.const 0x1.8p-1075", 0x0000_0000_0000_0001),
+            ("f64.const 0x0.0p0", 0x0000_0000_0000_0000),
+            // 指数が大きすぎても panic しない
+            (
+                "f64.const 0x10000000000000000p2147483647",
+                0x7ff0_0000_0000_0000,
+            ),
+            ("f64.const 0x1p99999999999999999999", 0x7ff0_0000_0000_0000),
+            ("f64.const 0x1p-99999999999999999999", 0x0000_0000_0000_0000),
+            ("f32.const -0x1p-2147483649", 0x8000_0000),
+        ];
+        for (instr, expected) in cases {
+            assert_eq!(bits(instr), *expected, "{}", instr);
+        }
+    }
+
+    #[test]
+    fn test_parse_wat_all_instructions() {
+        // 即値に 0 を入れて、表の全ての命令がバイナリ形式のパーサーで読めることを確かめる
+        for op in opcode::OPCODES {
+            let immediate = match op.immediate {
+                opcode::Immediate::Block => continue,
+                opcode::Immediate::None if matches!(op.name, "else" | "end") => continue,
+                opcode::Immediate::None
+                | opcode::Immediate::Select
+                | opcode::Immediate::Table
+                | opcode::Immediate::TableCopy
+                | opcode::Immediate::Memory
+                | opcode::Immediate::MemoryCopy
+                | opcode::Immediate::MemArg(_) => "",
+                opcode::Immediate::CallIndirect => "(type 0)",
+                opcode::Immediate::F32 | opcode::Immediate::F64 => "0.0",
+                opcode::Immediate::V128 => "i64x2 0 0",
+                opcode::Immediate::Shuffle => "0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+                opcode::Immediate::RefNull => "func",
+                _ => "0",
+            };
+            let input = format!(
+                "(type (func)) (table 1 funcref) (memory 1) (global i32 (i32.const 0))
+                 (elem func 0) (data \"\") (func (local i32) {} {})",
+                op.name, immediate
+            );
+            let module = parse_wat(&input).unwrap_or_else(|e| panic!("{}: {}", op.name, e));
+            let code = expression(&module);
+            assert_eq!(code[0], op.code, "{}", op.name);
+        }
+    }
+
+    #[test]
+    fn test_parse_wat_error() {
+        let cases = [
+            ("(module (func (i32.const 1))", "1:1: unclosed '('"),
+            ("(func\n  local.get $x)", "2:13: unknown local $x"),
+            ("(func\n  i32.foo)", "2:3: unknown instruction i32.foo"),
+            ("(func\n  (br $l))", "2:7: unknown label $l"),
+            ("(func $f) (func $f)", "1:11: duplicate identifier $f"),
+            ("(func i32.const 1x)", "1:17: invalid i32 literal 1x"),
+            (
+                "(func i32.const 0x1_0000_0000)",
+                "1:17: invalid i32 literal 0x1_0000_0000",
+            ),
+            ("(func block $a end $b)", "1:20: mismatched label $b"),
+            ("(data \"\\x\")", "1:8: invalid escape in string"),
+            ("(foo)", "1:1: unknown module field foo"),
+            ("(module (func (type 5)))", "1:21: unknown type 5"),
+            ("(data \"\\+f\")", "1:8: invalid escape in string"),
+            ("(data \"\\f\")", "1:8: invalid escape in string"),
+            ("(data \"\\u{+41}\")", "1:8: invalid unicode escape"),
+            ("(func $α)", "1:8: invalid character 'α' in identifier"),
+            ("(func $ )", "1:7: empty identifier"),
+            ("(func α)", "1:7: unexpected character 'α'"),
+        ];
+        for (input, message) in cases {
+            let err = parse_wat(input).err().unwrap();
+            assert_eq!(err.to_string(), message, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_wat_deep_nesting() {
+        let deep = 100_000;
+        let cases = [
+            format!("(func {}{})", "(block ".repeat(deep), ")".repeat(deep)),
+            format!(
+                "(func (result i32) {}(i32.const 0){})",
+                "(i32.eqz ".repeat(deep),
+                ")".repeat(deep)
+            ),
+        ];
+        for input in cases {
+            let err = parse_wat(&input).err().unwrap();
+            assert!(err.to_string().ends_with("nesting too deep"), "{}", err);
+        }
+
+        // flat な block の入れ子には上限がない
+        let flat = format!("(func {}{})", "block ".repeat(deep), "end ".repeat(deep));
+        let module = parse_wat(&flat).unwrap();
+        let mut expected = vec![0x00];
+        for _ in 0..deep {
+            expected.extend([0x02, 0x40]);
+        }
+        expected.extend(vec![0x0b; deep + 1]);
+        assert!(module.encode().ends_with(&expected));
+        let flat = format!(
+            "(func {}{})",
+            "block ".repeat(deep),
+            "end ".repeat(deep - 1)
+        );
+        let err = parse_wat(&flat).err().unwrap();
+        assert!(err.to_string().ends_with("expected end"), "{}", err);
+
+        // S 式は上限ちょうどまでは読める
+        let depth = MAX_NESTING - 2;
+        let flat = format!("(func {}{})", "block ".repeat(depth), "end ".repeat(depth));
+        let folded = format!(
+            "(module (func {}{}))",
+            "(block ".repeat(depth),
+            ")".repeat(depth)
+        );
+        assert_eq!(
+            parse_wat(&flat).unwrap().encode(),
+            parse_wat(&folded).unwrap().encode()
+        );
+        let folded = format!(
+            "(module (func {}{}))",
+            "(block ".repeat(depth + 1),
+            ")".repeat(depth + 1)
+        );
+        assert!(parse_wat(&folded).is_err());
+    }
+
+    // print_wat の出力を読み戻すと同じバイナリになる
+    fn assert_round_trip(input: &str) {
+        let module = parse_wat(input).unwrap();
+        for style in [Style::Flat, Style::Folded] {
+            let text = print_wat(&module, style).unwrap();
+            let printed = parse_wat(&text).unwrap_or_else(|e| panic!("{}\n{}", e, text));
+            assert_eq!(printed.encode(), module.encode(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_print_wat() {
+        let input: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number, version
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export section
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // code section
+            0x00, 0x1c, 0x04, 0x6e, 0x61, 0x6d, 0x65, // name section
+            0x01, 0x06, 0x01, 0x00, 0x03, 0x61, 0x64, 0x64, // func names
+            0x02, 0x0d, 0x01, 0x00, 0x02, 0x00, 0x03, 0x6c, 0x68, 0x73, 0x01, 0x03, 0x72, 0x68,
+            0x73, // local names
+        ];
+        let module = ast::parse_module(input).unwrap();
+        assert_eq!(
+            print_wat(&module, Style::Flat).unwrap(),
+            r#"(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (export "add" (func $add))
+  (func $add (type 0) (param $lhs i32) (param $rhs i32) (result i32)
+    local.get $lhs
+    local.get $rhs
+    i32.add))
+"#
+        );
+        assert_eq!(
+            print_wat(&module, Style::Folded).unwrap(),
+            r#"(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (export "add" (func $add))
+  (func $add (type 0) (param $lhs i32) (param $rhs i32) (result i32)
+    (i32.add
+      (local.get $lhs)
+      (local.get $rhs))))
+"#
+        );
+    }
+
+    #[test]
+    fn test_print_wat_extended_names() {
+        let input = r#"
+            (module
+              (type (func (result i32)))
+              (global (mut i32) (i32.const 0))
+              (func (type 0)
+                (block (result i32)
+                  (global.set 0 (i32.const 1))
+                  (br 0 (global.get 0)))))
+        "#;
+        let mut bytes = wat_to_bytes(input);
+        let payload: &[u8] = &[
+            0x01, 0x04, 0x01, 0x00, 0x01, b'f', // funcs
+            0x03, 0x08, 0x01, 0x00, 0x01, 0x00, 0x03, b'o', b'u', b't', // labels
+            0x04, 0x04, 0x01, 0x00, 0x01, b't', // types
+            0x07, 0x04, 0x01, 0x00, 0x01, b'g', // globals
+        ];
+        bytes.extend([0x00, 5 + payload.len() as u8, 0x04]);
+        bytes.extend(b"name");
+        bytes.extend(payload);
+        let module = ast::parse_module(&bytes).unwrap();
+        let names = module.name_section().unwrap().unwrap();
+        assert_eq!(names.label(0, 0), Some(&b"out"[..]));
+        let text = print_wat(&module, Style::Flat).unwrap();
+        assert_eq!(
+            text,
+            r#"(module
+  (type $t (func (result i32)))
+  (global $g (mut i32) i32.const 0)
+  (func $f (type $t) (result i32)
+    block $out (result i32)
+      i32.const 1
+      global.set $g
+      global.get $g
+      br $out
+    end))
+"#
+        );
+        assert_eq!(parse_wat(&text).unwrap().encode(), wat_to_bytes(input));
+    }
+
+    #[test]
+    fn test_print_wat_folded() {
+        let input = r#"
+            (func (param i32) (result i32) (local i64 i64)
+              (if (result i32) (i32.eqz (local.get 0))
+                (then (i32.const 1))
+                (else (i32.mul (local.get 0) (i32.const 2)))))
+        "#;
+        let module = parse_wat(input).unwrap();
+        assert_eq!(
+            print_wat(&module, Style::Folded).unwrap(),
+            r#"(module
+  (type (;0;) (func (param i32) (result i32)))
+  (func (;0;) (type 0) (param i32) (result i32)
+    (local i64 i64)
+    (if (result i32)
+      (i32.eqz
+        (local.get 0))
+      (then
+        (i32.const 1))
+      (else
+        (i32.mul
+          (local.get 0)
+          (i32.const 2))))))
+"#
+        );
+    }
+
+    #[test]
+    fn test_print_wat_round_trip() {
+        let inputs = [
+            r#"
+            (module
+              (type $v (func))
+              (func $g (call $f))
+              (import "env" "f" (func $f (type $v)))
+              (func $h (import "env" "h") (param i32))
+              (global $x (import "env" "x") (mut i32))
+              (memory (import "env" "mem") 1 2)
+              (table 2 externref)
+              (global (mut f64) (f64.const -0x1p-1074))
+              (start $g)
+              (func (export "run") (call $g) (call $h (i32.const 0)) (global.set $x (i32.const 1))))
+            "#,
+            r#"
+            (module
+              (memory $m (data "hi\00\ff\"\\"))
+              (table funcref (elem $f $f))
+              (table $t 1 10 funcref)
+              (func $f
+                (memory.init $d (i32.const 0) (i32.const 0) (i32.const 1))
+                (data.drop $d))
+              (elem declare func $f)
+              (elem (table $t) (i32.const 1) funcref (ref.func $f) (ref.null func))
+              (elem externref (ref.null extern))
+              (data $d "passive" "\u{3042}")
+              (data (memory $m) (offset (i32.const 8)) "x"))
+            "#,
+            r#"
+            (func $loop (param $n i32) (result i32) (local $i i32)
+              (block $done
+                (loop $next
+                  (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                  (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                  (br $next)))
+              block $a
+                block $b
+                  br_table $a $b 0
+                end $b
+              end
+              (block (result i32) (i32.const 1) (br 0 (i32.const 2)))
+              drop
+              (if (local.get $n) (then nop))
+              (loop (result i32) (i32.const 3))
+              (select (local.get $n) (i32.const 4) (i32.const 5))
+              return)
+            "#,
+            r#"
+            (func
+              i32.const -1 i32.const 0xffff_ffff drop drop
+              i64.const -0x8000_0000_0000_0000 drop
+              f32.const 1.5 f32.const -inf f32.const nan:0x200000 f32.const -nan drop drop drop drop
+              f32.const 0x1p-149 f32.const 3.4e38 drop drop
+              f64.const -0.0 f64.const 1e100 f64.const nan:0x1 drop drop drop
+              v128.const i32x4 1 -1 0 0x10 drop
+              i32.const 0 i32.load8_u offset=4 align=1 drop
+              i32.const 0 i64.const 0 i64.store
+              i32.const 0 v128.const i64x2 0 0 v128.load32_lane offset=2 1 drop
+              i32.const 0 i32.const 1 i32.const 2 select (result i32) drop
+              ref.null extern drop)
+            "#,
+        ];
+        for input in inputs {
+            assert_round_trip(input);
+        }
+    }
+
+    #[test]
+    fn test_print_wat_deep_nesting() {
+        // 深い block をバイナリで作る
+        fn deep_blocks(depth: usize) -> module::Module {
+            let mut body = vec![0x00];
+            body.extend([0x02, 0x40].repeat(depth));
+            body.extend(vec![0x0b; depth + 1]);
+            let mut code = vec![0x01];
+            crate::encode::encode_len(&mut code, &body);
+            let mut bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+            bytes.extend([
+                0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02, 0x01, 0x00, 0x0a,
+            ]);
+            crate::encode::encode_len(&mut bytes, &code);
+            ast::parse_module(&bytes).unwrap()
+        }
+        // folded でも S 式が上限を超える block は flat 形式で書くので、どの深さでも読み戻せる
+        let module = deep_blocks(100_000);
+        for style in [Style::Flat, Style::Folded] {
+            let text = print_wat(&module, style).unwrap();
+            assert_eq!(parse_wat(&text).unwrap().encode(), module.encode());
+        }
+        assert_round_trip(&format!(
+            "(func {}{})",
+            "block ".repeat(MAX_NESTING * 2),
+            "end ".repeat(MAX_NESTING * 2)
+        ));
+        assert_round_trip(&format!(
+            "(func (param i32) {}local.get 0 if nop else br 1 end {})",
+            "block loop ".repeat(MAX_NESTING),
+            "end end ".repeat(MAX_NESTING)
+        ));
+
+        // 入れ子にすると深くなりすぎる operand は前に並べる
+        let deep = 10_000;
+        assert_round_trip(&format!(
+            "(func (result i32) i32.const 0 {})",
+            "i32.eqz ".repeat(deep)
+        ));
+        assert_round_trip(&format!(
+            "(global i32 i32.const 0 {})",
+            "i32.const 1 i32.add ".repeat(deep)
+        ));
+        assert_round_trip(&format!(
+            "(func (result i32) {}i32.const 0 {})",
+            "block (result i32) ".repeat(MAX_NESTING / 2),
+            "i32.eqz end ".repeat(MAX_NESTING / 2)
+        ));
+    }
+
+    #[test]
+    fn test_print_wat_all_instructions() {
+        for op in opcode::OPCODES {
+            let immediate = match op.immediate {
+                opcode::Immediate::Block => continue,
+                opcode::Immediate::None if matches!(op.name, "else" | "end") => continue,
+                opcode::Immediate::None
+                | opcode::Immediate::Select
+                | opcode::Immediate::Table
+                | opcode::Immediate::TableCopy
+                | opcode::Immediate::Memory
+                | opcode::Immediate::MemoryCopy
+                | opcode::Immediate::MemArg(_) => "",
+                opcode::Immediate::CallIndirect => "(type 0)",
+                opcode::Immediate::F32 | opcode::Immediate::F64 => "0.0",
+                opcode::Immediate::V128 => "i64x2 0 0",
+                opcode::Immediate::Shuffle => "0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+                opcode::Immediate::RefNull => "func",
+                _ => "0",
+            };
+            assert_round_trip(&format!(
+                "(type (func)) (table 1 funcref) (memory 1) (global i32 (i32.const 0))
+                 (elem func 0) (data \"\") (func (local i32) {} {})",
+                op.name, immediate
+            ));
+        }
+    }
+
+    #[test]
+    fn test_module_debug() {
+        let module = parse_wat("(func (export \"f\") nop)").unwrap();
+        let text = format!("{:?}", module);
+        assert!(text.contains("ExportSection"), "{}", text);
+        assert!(text.contains("range"), "{}", text);
+    }
+}
@@ -0,0 +1,183 @@
+// テキスト形式を token に分ける
+use super::ErrorAt;
+
+pub(super) enum TokenKind<'a> {
+    LParen,
+    RParen,
+    // keyword、数値、offset=4 のような予約語をまとめて扱う
+    Atom(&'a str),
+    // $ を除いた識別子
+    Id(&'a str),
+    String(Vec<u8>),
+}
+
+pub(super) struct Token<'a> {
+    pub(super) kind: TokenKind<'a>,
+    pub(super) offset: usize,
+}
+
+pub(super) fn tokenize(text: &str) -> Result<Vec<Token<'_>>, ErrorAt> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let offset = pos;
+        match bytes[pos] {
+            b' ' | b'\t' | b'\n' | b'\r' => pos += 1,
+            b';' if bytes.get(pos + 1) == Some(&b';') => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            }
+            b'(' if bytes.get(pos + 1) == Some(&b';') => pos = skip_block_comment(bytes, pos)?,
+            b'(' => {
+                tokens.push(Token {
+                    kind: TokenKind::LParen,
+                    offset,
+                });
+                pos += 1;
+            }
+            b')' => {
+                tokens.push(Token {
+                    kind: TokenKind::RParen,
+                    offset,
+                });
+                pos += 1;
+            }
+            b'"' => {
+                let (s, end) = lex_string(bytes, pos)?;
+                tokens.push(Token {
+                    kind: TokenKind::String(s),
+                    offset,
+                });
+                pos = end;
+            }
+            by if is_idchar(by) => {
+                while pos < bytes.len() && is_idchar(bytes[pos]) {
+                    pos += 1;
+                }
+                let s = &text[offset..pos];
+                let kind = match s.strip_prefix('$') {
+                    Some(id) => {
+                        // $ の後ろに idchar 以外の文字が続く場合は、空ではなく不正な文字
+                        if let Some(c) = text[pos..].chars().next().filter(|c| !is_delimiter(*c)) {
+                            return Err(ErrorAt::new(
+                                pos,
+                                format!("invalid character {:?} in identifier", c),
+                            ));
+                        }
+                        if id.is_empty() {
+                            return Err(ErrorAt::new(offset, "empty identifier"));
+                        }
+                        TokenKind::Id(id)
+                    }
+                    None => TokenKind::Atom(s),
+                };
+                tokens.push(Token { kind, offset });
+            }
+            _ => {
+                // 非 ASCII の文字もバイトではなく文字として表示する
+                let c = text[offset..].chars().next().unwrap();
+                return Err(ErrorAt::new(
+                    offset,
+                    format!("unexpected character {:?}", c),
+                ));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+pub(super) fn is_idchar(by: u8) -> bool {
+    by.is_ascii_alphanumeric() || b"!#$%&'*+-./:<=>?@\\^_`|~".contains(&by)
+}
+
+// token の区切りになる文字
+fn is_delimiter(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '(' | ')' | '"' | ';')
+}
+
+// (; ... ;) は入れ子にできる
+fn skip_block_comment(bytes: &[u8], start: usize) -> Result<usize, ErrorAt> {
+    let mut depth = 0;
+    let mut pos = start;
+    while pos + 1 < bytes.len() {
+        match &bytes[pos..pos + 2] {
+            b"(;" => {
+                depth += 1;
+                pos += 2;
+            }
+            b";)" => {
+                depth -= 1;
+                pos += 2;
+                if depth == 0 {
+                    return Ok(pos);
+                }
+            }
+            _ => pos += 1,
+        }
+    }
+    Err(ErrorAt::new(start, "unterminated block comment"))
+}
+
+// 文字列はバイト列として読む。\hh で任意のバイトを書ける
+fn lex_string(bytes: &[u8], start: usize) -> Result<(Vec<u8>, usize), ErrorAt> {
+    let mut s = Vec::new();
+    let mut pos = start + 1;
+    loop {
+        let by = *bytes
+            .get(pos)
+            .ok_or_else(|| ErrorAt::new(start, "unterminated string"))?;
+        pos += 1;
+        match by {
+            b'"' => return Ok((s, pos)),
+            b'\\' => {
+                let escape = *bytes
+                    .get(pos)
+                    .ok_or_else(|| ErrorAt::new(start, "unterminated string"))?;
+                pos += 1;
+                match escape {
+                    b't' => s.push(b'\t'),
+                    b'n' => s.push(b'\n'),
+                    b'r' => s.push(b'\r'),
+                    b'"' | b'\'' | b'\\' => s.push(escape),
+                    b'u' => {
+                        let (c, end) = lex_unicode_escape(bytes, pos)
+                            .ok_or_else(|| ErrorAt::new(pos - 2, "invalid unicode escape"))?;
+                        let mut buf = [0; 4];
+                        s.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                        pos = end;
+                    }
+                    _ => {
+                        // from_str_radix は符号も受け付けるので、16 進の数字 2 つを自分で読む
+                        let digit =
+                            |i: usize| bytes.get(i).and_then(|&by| (by as char).to_digit(16));
+                        let hex = digit(pos - 1)
+                            .zip(digit(pos))
+                            .map(|(high, low)| (high * 16 + low) as u8)
+                            .ok_or_else(|| ErrorAt::new(pos - 2, "invalid escape in string"))?;
+                        s.push(hex);
+                        pos += 1;
+                    }
+                }
+            }
+            by => s.push(by),
+        }
+    }
+}
+
+// \u{hex} の { から読む
+fn lex_unicode_escape(bytes: &[u8], pos: usize) -> Option<(char, usize)> {
+    if bytes.get(pos) != Some(&b'{') {
+        return None;
+    }
+    let end = pos + bytes[pos..].iter().position(|&by| by == b'}')?;
+    let hex = std::str::from_utf8(&bytes[pos + 1..end])
+        .ok()?
+        .replace('_', "");
+    if hex.is_empty() || !hex.bytes().all(|by| by.is_ascii_hexdigit()) {
+        return None;
+    }
+    let c = char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?;
+    Some((c, end + 1))
+}
@@ -0,0 +1,516 @@
+// 命令の名前と opcode、即値の種類の対応表。テキスト形式の読み書きで共有する
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Immediate {
+    None,
+    Block,
+    Label,
+    BrTable,
+    Func,
+    CallIndirect,
+    Select,
+    Local,
+    Global,
+    Table,
+    TableInit,
+    TableCopy,
+    Elem,
+    Data,
+    // 自然なアラインメントを 2 の指数で持つ
+    MemArg(u32),
+    MemArgLane(u32),
+    // 予約バイトの memory index
+    Memory,
+    MemoryInit,
+    MemoryCopy,
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    Shuffle,
+    Lane,
+    RefNull,
+}
+
+pub(crate) struct Opcode {
+    pub(crate) name: &'static str,
+    pub(crate) code: u8,
+    // 0xFC, 0xFD prefix の命令の sub opcode
+    pub(crate) sub: Option<u32>,
+    pub(crate) immediate: Immediate,
+}
+
+const fn op(name: &'static str, code: u8, immediate: Immediate) -> Opcode {
+    Opcode {
+        name,
+        code,
+        sub: None,
+        immediate,
+    }
+}
+
+const fn fc(name: &'static str, sub: u32, immediate: Immediate) -> Opcode {
+    Opcode {
+        name,
+        code: 0xFC,
+        sub: Some(sub),
+        immediate,
+    }
+}
+
+const fn fd(name: &'static str, sub: u32, immediate: Immediate) -> Opcode {
+    Opcode {
+        name,
+        code: 0xFD,
+        sub: Some(sub),
+        immediate,
+    }
+}
+
+pub(crate) fn find_by_name(name: &str) -> Option<&'static Opcode> {
+    OPCODES.iter().find(|op| op.name == name)
+}
+
+pub(crate) fn find_by_code(code: u8, sub: Option<u32>) -> Option<&'static Opcode> {
+    OPCODES.iter().find(|op| op.code == code && op.sub == sub)
+}
+
+pub(crate) const OPCODES: &[Opcode] = &[
+    op("unreachable", 0x00, Immediate::None),
+    op("nop", 0x01, Immediate::None),
+    op("block", 0x02, Immediate::Block),
+    op("loop", 0x03, Immediate::Block),
+    op("if", 0x04, Immediate::Block),
+    op("else", 0x05, Immediate::None),
+    op("end", 0x0B, Immediate::None),
+    op("br", 0x0C, Immediate::Label),
+    op("br_if", 0x0D, Immediate::Label),
+    op("br_table", 0x0E, Immediate::BrTable),
+    op("return", 0x0F, Immediate::None),
+    op("call", 0x10, Immediate::Func),
+    op("call_indirect", 0x11, Immediate::CallIndirect),
+    op("drop", 0x1A, Immediate::None),
+    op("select", 0x1B, Immediate::Select),
+    op("local.get", 0x20, Immediate::Local),
+    op("local.set", 0x21, Immediate::Local),
+    op("local.tee", 0x22, Immediate::Local),
+    op("global.get", 0x23, Immediate::Global),
+    op("global.set", 0x24, Immediate::Global),
+    op("table.get", 0x25, Immediate::Table),
+    op("table.set", 0x26, Immediate::Table),
+    op("i32.load", 0x28, Immediate::MemArg(2)),
+    op("i64.load", 0x29, Immediate::MemArg(3)),
+    op("f32.load", 0x2A, Immediate::MemArg(2)),
+    op("f64.load", 0x2B, Immediate::MemArg(3)),
+    op("i32.load8_s", 0x2C, Immediate::MemArg(0)),
+    op("i32.load8_u", 0x2D, Immediate::MemArg(0)),
+    op("i32.load16_s", 0x2E, Immediate::MemArg(1)),
+    op("i32.load16_u", 0x2F, Immediate::MemArg(1)),
+    op("i64.load8_s", 0x30, Immediate::MemArg(0)),
+    op("i64.load8_u", 0x31, Immediate::MemArg(0)),
+    op("i64.load16_s", 0x32, Immediate::MemArg(1)),
+    op("i64.load16_u", 0x33, Immediate::MemArg(1)),
+    op("i64.load32_s", 0x34, Immediate::MemArg(2)),
+    op("i64.load32_u", 0x35, Immediate::MemArg(2)),
+    op("i32.store", 0x36, Immediate::MemArg(2)),
+    op("i64.store", 0x37, Immediate::MemArg(3)),
+    op("f32.store", 0x38, Immediate::MemArg(2)),
+    op("f64.store", 0x39, Immediate::MemArg(3)),
+    op("i32.store8", 0x3A, Immediate::MemArg(0)),
+    op("i32.store16", 0x3B, Immediate::MemArg(1)),
+    op("i64.store8", 0x3C, Immediate::MemArg(0)),
+    op("i64.store16", 0x3D, Immediate::MemArg(1)),
+    op("i64.store32", 0x3E, Immediate::MemArg(2)),
+    op("memory.size", 0x3F, Immediate::Memory),
+    op("memory.grow", 0x40, Immediate::Memory),
+    op("i32.const", 0x41, Immediate::I32),
+    op("i64.const", 0x42, Immediate::I64),
+    op("f32.const", 0x43, Immediate::F32),
+    op("f64.const", 0x44, Immediate::F64),
+    op("i32.eqz", 0x45, Immediate::None),
+    op("i32.eq", 0x46, Immediate::None),
+    op("i32.ne", 0x47, Immediate::None),
+    op("i32.lt_s", 0x48, Immediate::None),
+    op("i32.lt_u", 0x49, Immediate::None),
+    op("i32.gt_s", 0x4A, Immediate::None),
+    op("i32.gt_u", 0x4B, Immediate::None),
+    op("i32.le_s", 0x4C, Immediate::None),
+    op("i32.le_u", 0x4D, Immediate::None),
+    op("i32.ge_s", 0x4E, Immediate::None),
+    op("i32.ge_u", 0x4F, Immediate::None),
+    op("i64.eqz", 0x50, Immediate::None),
+    op("i64.eq", 0x51, Immediate::None),
+    op("i64.ne", 0x52, Immediate::None),
+    op("i64.lt_s", 0x53, Immediate::None),
+    op("i64.lt_u", 0x54, Immediate::None),
+    op("i64.gt_s", 0x55, Immediate::None),
+    op("i64.gt_u", 0x56, Immediate::None),
+    op("i64.le_s", 0x57, Immediate::None),
+    op("i64.le_u", 0x58, Immediate::None),
+    op("i64.ge_s", 0x59, Immediate::None),
+    op("i64.ge_u", 0x5A, Immediate::None),
+    op("f32.eq", 0x5B, Immediate::None),
+    op("f32.ne", 0x5C, Immediate::None),
+    op("f32.lt", 0x5D, Immediate::None),
+    op("f32.gt", 0x5E, Immediate::None),
+    op("f32.le", 0x5F, Immediate::None),
+    op("f32.ge", 0x60, Immediate::None),
+    op("f64.eq", 0x61, Immediate::None),
+    op("f64.ne", 0x62, Immediate::None),
+    op("f64.lt", 0x63, Immediate::None),
+    op("f64.gt", 0x64, Immediate::None),
+    op("f64.le", 0x65, Immediate::None),
+    op("f64.ge", 0x66, Immediate::None),
+    op("i32.clz", 0x67, Immediate::None),
+    op("i32.ctz", 0x68, Immediate::None),
+    op("i32.popcnt", 0x69, Immediate::None),
+    op("i32.add", 0x6A, Immediate::None),
+    op("i32.sub", 0x6B, Immediate::None),
+    op("i32.mul", 0x6C, Immediate::None),
+    op("i32.div_s", 0x6D, Immediate::None),
+    op("i32.div_u", 0x6E, Immediate::None),
+    op("i32.rem_s", 0x6F, Immediate::None),
+    op("i32.rem_u", 0x70, Immediate::None),
+    op("i32.and", 0x71, Immediate::None),
+    op("i32.or", 0x72, Immediate::None),
+    op("i32.xor", 0x73, Immediate::None),
+    op("i32.shl", 0x74, Immediate::None),
+    op("i32.shr_s", 0x75, Immediate::None),
+    op("i32.shr_u", 0x76, Immediate::None),
+    op("i32.rotl", 0x77, Immediate::None),
+    op("i32.rotr", 0x78, Immediate::None),
+    op("i64.clz", 0x79, Immediate::None),
+    op("i64.ctz", 0x7A, Immediate::None),
+    op("i64.popcnt", 0x7B, Immediate::None),
+    op("i64.add", 0x7C, Immediate::None),
+    op("i64.sub", 0x7D, Immediate::None),
+    op("i64.mul", 0x7E, Immediate::None),
+    op("i64.div_s", 0x7F, Immediate::None),
+    op("i64.div_u", 0x80, Immediate::None),
+    op("i64.rem_s", 0x81, Immediate::None),
+    op("i64.rem_u", 0x82, Immediate::None),
+    op("i64.and", 0x83, Immediate::None),
+    op("i64.or", 0x84, Immediate::None),
+    op("i64.xor", 0x85, Immediate::None),
+    op("i64.shl", 0x86, Immediate::None),
+    op("i64.shr_s", 0x87, Immediate::None),
+    op("i64.shr_u", 0x88, Immediate::None),
+    op("i64.rotl", 0x89, Immediate::None),
+    op("i64.rotr", 0x8A, Immediate::None),
+    op("f32.abs", 0x8B, Immediate::None),
+    op("f32.neg", 0x8C, Immediate::None),
+    op("f32.ceil", 0x8D, Immediate::None),
+    op("f32.floor", 0x8E, Immediate::None),
+    op("f32.trunc", 0x8F, Immediate::None),
+    op("f32.nearest", 0x90, Immediate::None),
+    op("f32.sqrt", 0x91, Immediate::None),
+    op("f32.add", 0x92, Immediate::None),
+    op("f32.sub", 0x93, Immediate::None),
+    op("f32.mul", 0x94, Immediate::None),
+    op("f32.div", 0x95, Immediate::None),
+    op("f32.min", 0x96, Immediate::None),
+    op("f32.max", 0x97, Immediate::None),
+    op("f32.copysign", 0x98, Immediate::None),
+    op("f64.abs", 0x99, Immediate::None),
+    op("f64.neg", 0x9A, Immediate::None),
+    op("f64.ceil", 0x9B, Immediate::None),
+    op("f64.floor", 0x9C, Immediate::None),
+    op("f64.trunc", 0x9D, Immediate::None),
+    op("f64.nearest", 0x9E, Immediate::None),
+    op("f64.sqrt", 0x9F, Immediate::None),
+    op("f64.add", 0xA0, Immediate::None),
+    op("f64.sub", 0xA1, Immediate::None),
+    op("f64.mul", 0xA2, Immediate::None),
+    op("f64.div", 0xA3, Immediate::None),
+    op("f64.min", 0xA4, Immediate::None),
+    op("f64.max", 0xA5, Immediate::None),
+    op("f64.copysign", 0xA6, Immediate::None),
+    op("i32.wrap_i64", 0xA7, Immediate::None),
+    op("i32.trunc_f32_s", 0xA8, Immediate::None),
+    op("i32.trunc_f32_u", 0xA9, Immediate::None),
+    op("i32.trunc_f64_s", 0xAA, Immediate::None),
+    op("i32.trunc_f64_u", 0xAB, Immediate::None),
+    op("i64.extend_i32_s", 0xAC, Immediate::None),
+    op("i64.extend_i32_u", 0xAD, Immediate::None),
+    op("i64.trunc_f32_s", 0xAE, Immediate::None),
+    op("i64.trunc_f32_u", 0xAF, Immediate::None),
+    op("i64.trunc_f64_s", 0xB0, Immediate::None),
+    op("i64.trunc_f64_u", 0xB1, Immediate::None),
+    op("f32.convert_i32_s", 0xB2, Immediate::None),
+    op("f32.convert_i32_u", 0xB3, Immediate::None),
+    op("f32.convert_i64_s", 0xB4, Immediate::None),
+    op("f32.convert_i64_u", 0xB5, Immediate::None),
+    op("f32.demote_f64", 0xB6, Immediate::None),
+    op("f64.convert_i32_s", 0xB7, Immediate::None),
+    op("f64.convert_i32_u", 0xB8, Immediate::None),
+    op("f64.convert_i64_s", 0xB9, Immediate::None),
+    op("f64.convert_i64_u", 0xBA, Immediate::None),
+    op("f64.promote_f32", 0xBB, Immediate::None),
+    op("i32.reinterpret_f32", 0xBC, Immediate::None),
+    op("i64.reinterpret_f64", 0xBD, Immediate::None),
+    op("f32.reinterpret_i32", 0xBE, Immediate::None),
+    op("f64.reinterpret_i64", 0xBF, Immediate::None),
+    op("i32.extend8_s", 0xC0, Immediate::None),
+    op("i32.extend16_s", 0xC1, Immediate::None),
+    op("i64.extend8_s", 0xC2, Immediate::None),
+    op("i64.extend16_s", 0xC3, Immediate::None),
+    op("i64.extend32_s", 0xC4, Immediate::None),
+    op("ref.null", 0xD0, Immediate::RefNull),
+    op("ref.is_null", 0xD1, Immediate::None),
+    op("ref.func", 0xD2, Immediate::Func),
+    fc("i32.trunc_sat_f32_s", 0, Immediate::None),
+    fc("i32.trunc_sat_f32_u", 1, Immediate::None),
+    fc("i32.trunc_sat_f64_s", 2, Immediate::None),
+    fc("i32.trunc_sat_f64_u", 3, Immediate::None),
+    fc("i64.trunc_sat_f32_s", 4, Immediate::None),
+    fc("i64.trunc_sat_f32_u", 5, Immediate::None),
+    fc("i64.trunc_sat_f64_s", 6, Immediate::None),
+    fc("i64.trunc_sat_f64_u", 7, Immediate::None),
+    fc("memory.init", 8, Immediate::MemoryInit),
+    fc("data.drop", 9, Immediate::Data),
+    fc("memory.copy", 10, Immediate::MemoryCopy),
+    fc("memory.fill", 11, Immediate::Memory),
+    fc("table.init", 12, Immediate::TableInit),
+    fc("elem.drop", 13, Immediate::Elem),
+    fc("table.copy", 14, Immediate::TableCopy),
+    fc("table.grow", 15, Immediate::Table),
+    fc("table.size", 16, Immediate::Table),
+    fc("table.fill", 17, Immediate::Table),
+    fd("v128.load", 0, Immediate::MemArg(4)),
+    fd("v128.load8x8_s", 1, Immediate::MemArg(3)),
+    fd("v128.load8x8_u", 2, Immediate::MemArg(3)),
+    fd("v128.load16x4_s", 3, Immediate::MemArg(3)),
+    fd("v128.load16x4_u", 4, Immediate::MemArg(3)),
+    fd("v128.load32x2_s", 5, Immediate::MemArg(3)),
+    fd("v128.load32x2_u", 6, Immediate::MemArg(3)),
+    fd("v128.load8_splat", 7, Immediate::MemArg(0)),
+    fd("v128.load16_splat", 8, Immediate::MemArg(1)),
+    fd("v128.load32_splat", 9, Immediate::MemArg(2)),
+    fd("v128.load64_splat", 10, Immediate::MemArg(3)),
+    fd("v128.store", 11, Immediate::MemArg(4)),
+    fd("v128.const", 12, Immediate::V128),
+    fd("i8x16.shuffle", 13, Immediate::Shuffle),
+    fd("i8x16.swizzle", 14, Immediate::None),
+    fd("i8x16.splat", 15, Immediate::None),
+    fd("i16x8.splat", 16, Immediate::None),
+    fd("i32x4.splat", 17, Immediate::None),
+    fd("i64x2.splat", 18, Immediate::None),
+    fd("f32x4.splat", 19, Immediate::None),
+    fd("f64x2.splat", 20, Immediate::None),
+    fd("i8x16.extract_lane_s", 21, Immediate::Lane),
+    fd("i8x16.extract_lane_u", 22, Immediate::Lane),
+    fd("i8x16.replace_lane", 23, Immediate::Lane),
+    fd("i16x8.extract_lane_s", 24, Immediate::Lane),
+    fd("i16x8.extract_lane_u", 25, Immediate::Lane),
+    fd("i16x8.replace_lane", 26, Immediate::Lane),
+    fd("i32x4.extract_lane", 27, Immediate::Lane),
+    fd("i32x4.replace_lane", 28, Immediate::Lane),
+    fd("i64x2.extract_lane", 29, Immediate::Lane),
+    fd("i64x2.replace_lane", 30, Immediate::Lane),
+    fd("f32x4.extract_lane", 31, Immediate::Lane),
+    fd("f32x4.replace_lane", 32, Immediate::Lane),
+    fd("f64x2.extract_lane", 33, Immediate::Lane),
+    fd("f64x2.replace_lane", 34, Immediate::Lane),
+    fd("i8x16.eq", 35, Immediate::None),
+    fd("i8x16.ne", 36, Immediate::None),
+    fd("i8x16.lt_s", 37, Immediate::None),
+    fd("i8x16.lt_u", 38, Immediate::None),
+    fd("i8x16.gt_s", 39, Immediate::None),
+    fd("i8x16.gt_u", 40, Immediate::None),
+    fd("i8x16.le_s", 41, Immediate::None),
+    fd("i8x16.le_u", 42, Immediate::None),
+    fd("i8x16.ge_s", 43, Immediate::None),
+    fd("i8x16.ge_u", 44, Immediate::None),
+    fd("i16x8.eq", 45, Immediate::None),
+    fd("i16x8.ne", 46, Immediate::None),
+    fd("i16x8.lt_s", 47, Immediate::None),
+    fd("i16x8.lt_u", 48, Immediate::None),
+    fd("i16x8.gt_s", 49, Immediate::None),
+    fd("i16x8.gt_u", 50, Immediate::None),
+    fd("i16x8.le_s", 51, Immediate::None),
+    fd("i16x8.le_u", 52, Immediate::None),
+    fd("i16x8.ge_s", 53, Immediate::None),
+    fd("i16x8.ge_u", 54, Immediate::None),
+    fd("i32x4.eq", 55, Immediate::None),
+    fd("i32x4.ne", 56, Immediate::None),
+    fd("i32x4.lt_s", 57, Immediate::None),
+    fd("i32x4.lt_u", 58, Immediate::None),
+    fd("i32x4.gt_s", 59, Immediate::None),
+    fd("i32x4.gt_u", 60, Immediate::None),
+    fd("i32x4.le_s", 61, Immediate::None),
+    fd("i32x4.le_u", 62, Immediate::None),
+    fd("i32x4.ge_s", 63, Immediate::None),
+    fd("i32x4.ge_u", 64, Immediate::None),
+    fd("f32x4.eq", 65, Immediate::None),
+    fd("f32x4.ne", 66, Immediate::None),
+    fd("f32x4.lt", 67, Immediate::None),
+    fd("f32x4.gt", 68, Immediate::None),
+    fd("f32x4.le", 69, Immediate::None),
+    fd("f32x4.ge", 70, Immediate::None),
+    fd("f64x2.eq", 71, Immediate::None),
+    fd("f64x2.ne", 72, Immediate::None),
+    fd("f64x2.lt", 73, Immediate::None),
+    fd("f64x2.gt", 74, Immediate::None),
+    fd("f64x2.le", 75, Immediate::None),
+    fd("f64x2.ge", 76, Immediate::None),
+    fd("v128.not", 77, Immediate::None),
+    fd("v128.and", 78, Immediate::None),
+    fd("v128.andnot", 79, Immediate::None),
+    fd("v128.or", 80, Immediate::None),
+    fd("v128.xor", 81, Immediate::None),
+    fd("v128.bitselect", 82, Immediate::None),
+    fd("v128.any_true", 83, Immediate::None),
+    fd("v128.load8_lane", 84, Immediate::MemArgLane(0)),
+    fd("v128.load16_lane", 85, Immediate::MemArgLane(1)),
+    fd("v128.load32_lane", 86, Immediate::MemArgLane(2)),
+    fd("v128.load64_lane", 87, Immediate::MemArgLane(3)),
+    fd("v128.store8_lane", 88, Immediate::MemArgLane(0)),
+    fd("v128.store16_lane", 89, Immediate::MemArgLane(1)),
+    fd("v128.store32_lane", 90, Immediate::MemArgLane(2)),
+    fd("v128.store64_lane", 91, Immediate::MemArgLane(3)),
+    fd("v128.load32_zero", 92, Immediate::MemArg(2)),
+    fd("v128.load64_zero", 93, Immediate::MemArg(3)),
+    fd("f32x4.demote_f64x2_zero", 94, Immediate::None),
+    fd("f64x2.promote_low_f32x4", 95, Immediate::None),
+    fd("i8x16.abs", 96, Immediate::None),
+    fd("i8x16.neg", 97, Immediate::None),
+    fd("i8x16.popcnt", 98, Immediate::None),
+    fd("i8x16.all_true", 99, Immediate::None),
+    fd("i8x16.bitmask", 100, Immediate::None),
+    fd("i8x16.narrow_i16x8_s", 101, Immediate::None),
+    fd("i8x16.narrow_i16x8_u", 102, Immediate::None),
+    fd("f32x4.ceil", 103, Immediate::None),
+    fd("f32x4.floor", 104, Immediate::None),
+    fd("f32x4.trunc", 105, Immediate::None),
+    fd("f32x4.nearest", 106, Immediate::None),
+    fd("i8x16.shl", 107, Immediate::None),
+    fd("i8x16.shr_s", 108, Immediate::None),
+    fd("i8x16.shr_u", 109, Immediate::None),
+    fd("i8x16.add", 110, Immediate::None),
+    fd("i8x16.add_sat_s", 111, Immediate::None),
+    fd("i8x16.add_sat_u", 112, Immediate::None),
+    fd("i8x16.sub", 113, Immediate::None),
+    fd("i8x16.sub_sat_s", 114, Immediate::None),
+    fd("i8x16.sub_sat_u", 115, Immediate::None),
+    fd("f64x2.ceil", 116, Immediate::None),
+    fd("f64x2.floor", 117, Immediate::None),
+    fd("i8x16.min_s", 118, Immediate::None),
+    fd("i8x16.min_u", 119, Immediate::None),
+    fd("i8x16.max_s", 120, Immediate::None),
+    fd("i8x16.max_u", 121, Immediate::None),
+    fd("f64x2.trunc", 122, Immediate::None),
+    fd("i8x16.avgr_u", 123, Immediate::None),
+    fd("i16x8.extadd_pairwise_i8x16_s", 124, Immediate::None),
+    fd("i16x8.extadd_pairwise_i8x16_u", 125, Immediate::None),
+    fd("i32x4.extadd_pairwise_i16x8_s", 126, Immediate::None),
+    fd("i32x4.extadd_pairwise_i16x8_u", 127, Immediate::None),
+    fd("i16x8.abs", 128, Immediate::None),
+    fd("i16x8.neg", 129, Immediate::None),
+    fd("i16x8.q15mulr_sat_s", 130, Immediate::None),
+    fd("i16x8.all_true", 131, Immediate::None),
+    fd("i16x8.bitmask", 132, Immediate::None),
+    fd("i16x8.narrow_i32x4_s", 133, Immediate::None),
+    fd("i16x8.narrow_i32x4_u", 134, Immediate::None),
+    fd("i16x8.extend_low_i8x16_s", 135, Immediate::None),
+    fd("i16x8.extend_high_i8x16_s", 136, Immediate::None),
+    fd("i16x8.extend_low_i8x16_u", 137, Immediate::None),
+    fd("i16x8.extend_high_i8x16_u", 138, Immediate::None),
+    fd("i16x8.shl", 139, Immediate::None),
+    fd("i16x8.shr_s", 140, Immediate::None),
+    fd("i16x8.shr_u", 141, Immediate::None),
+    fd("i16x8.add", 142, Immediate::None),
+    fd("i16x8.add_sat_s", 143, Immediate::None),
+    fd("i16x8.add_sat_u", 144, Immediate::None),
+    fd("i16x8.sub", 145, Immediate::None),
+    fd("i16x8.sub_sat_s", 146, Immediate::None),
+    fd("i16x8.sub_sat_u", 147, Immediate::None),
+    fd("f64x2.nearest", 148, Immediate::None),
+    fd("i16x8.mul", 149, Immediate::None),
+    fd("i16x8.min_s", 150, Immediate::None),
+    fd("i16x8.min_u", 151, Immediate::None),
+    fd("i16x8.max_s", 152, Immediate::None),
+    fd("i16x8.max_u", 153, Immediate::None),
+    fd("i16x8.avgr_u", 155, Immediate::None),
+    fd("i16x8.extmul_low_i8x16_s", 156, Immediate::None),
+    fd("i16x8.extmul_high_i8x16_s", 157, Immediate::None),
+    fd("i16x8.extmul_low_i8x16_u", 158, Immediate::None),
+    fd("i16x8.extmul_high_i8x16_u", 159, Immediate::None),
+    fd("i32x4.abs", 160, Immediate::None),
+    fd("i32x4.neg", 161, Immediate::None),
+    fd("i32x4.all_true", 163, Immediate::None),
+    fd("i32x4.bitmask", 164, Immediate::None),
+    fd("i32x4.extend_low_i16x8_s", 167, Immediate::None),
+    fd("i32x4.extend_high_i16x8_s", 168, Immediate::None),
+    fd("i32x4.extend_low_i16x8_u", 169, Immediate::None),
+    fd("i32x4.extend_high_i16x8_u", 170, Immediate::None),
+    fd("i32x4.shl", 171, Immediate::None),
+    fd("i32x4.shr_s", 172, Immediate::None),
+    fd("i32x4.shr_u", 173, Immediate::None),
+    fd("i32x4.add", 174, Immediate::None),
+    fd("i32x4.sub", 177, Immediate::None),
+    fd("i32x4.mul", 181, Immediate::None),
+    fd("i32x4.min_s", 182, Immediate::None),
+    fd("i32x4.min_u", 183, Immediate::None),
+    fd("i32x4.max_s", 184, Immediate::None),
+    fd("i32x4.max_u", 185, Immediate::None),
+    fd("i32x4.dot_i16x8_s", 186, Immediate::None),
+    fd("i32x4.extmul_low_i16x8_s", 188, Immediate::None),
+    fd("i32x4.extmul_high_i16x8_s", 189, Immediate::None),
+    fd("i32x4.extmul_low_i16x8_u", 190, Immediate::None),
+    fd("i32x4.extmul_high_i16x8_u", 191, Immediate::None),
+    fd("i64x2.abs", 192, Immediate::None),
+    fd("i64x2.neg", 193, Immediate::None),
+    fd("i64x2.all_true", 195, Immediate::None),
+    fd("i64x2.bitmask", 196, Immediate::None),
+    fd("i64x2.extend_low_i32x4_s", 199, Immediate::None),
+    fd("i64x2.extend_high_i32x4_s", 200, Immediate::None),
+    fd("i64x2.extend_low_i32x4_u", 201, Immediate::None),
+    fd("i64x2.extend_high_i32x4_u", 202, Immediate::None),
+    fd("i64x2.shl", 203, Immediate::None),
+    fd("i64x2.shr_s", 204, Immediate::None),
+    fd("i64x2.shr_u", 205, Immediate::None),
+    fd("i64x2.add", 206, Immediate::None),
+    fd("i64x2.sub", 209, Immediate::None),
+    fd("i64x2.mul", 213, Immediate::None),
+    fd("i64x2.eq", 214, Immediate::None),
+    fd("i64x2.ne", 215, Immediate::None),
+    fd("i64x2.lt_s", 216, Immediate::None),
+    fd("i64x2.gt_s", 217, Immediate::None),
+    fd("i64x2.le_s", 218, Immediate::None),
+    fd("i64x2.ge_s", 219, Immediate::None),
+    fd("i64x2.extmul_low_i32x4_s", 220, Immediate::None),
+    fd("i64x2.extmul_high_i32x4_s", 221, Immediate::None),
+    fd("i64x2.extmul_low_i32x4_u", 222, Immediate::None),
+    fd("i64x2.extmul_high_i32x4_u", 223, Immediate::None),
+    fd("f32x4.abs", 224, Immediate::None),
+    fd("f32x4.neg", 225, Immediate::None),
+    fd("f32x4.sqrt", 227, Immediate::None),
+    fd("f32x4.add", 228, Immediate::None),
+    fd("f32x4.sub", 229, Immediate::None),
+    fd("f32x4.mul", 230, Immediate::None),
+    fd("f32x4.div", 231, Immediate::None),
+    fd("f32x4.min", 232, Immediate::None),
+    fd("f32x4.max", 233, Immediate::None),
+    fd("f32x4.pmin", 234, Immediate::None),
+    fd("f32x4.pmax", 235, Immediate::None),
+    fd("f64x2.abs", 236, Immediate::None),
+    fd("f64x2.neg", 237, Immediate::None),
+    fd("f64x2.sqrt", 239, Immediate::None),
+    fd("f64x2.add", 240, Immediate::None),
+    fd("f64x2.sub", 241, Immediate::None),
+    fd("f64x2.mul", 242, Immediate::None),
+    fd("f64x2.div", 243, Immediate::None),
+    fd("f64x2.min", 244, Immediate::None),
+    fd("f64x2.max", 245, Immediate::None),
+    fd("f64x2.pmin", 246, Immediate::None),
+    fd("f64x2.pmax", 247, Immediate::None),
+    fd("i32x4.trunc_sat_f32x4_s", 248, Immediate::None),
+    fd("i32x4.trunc_sat_f32x4_u", 249, Immediate::None),
+    fd("f32x4.convert_i32x4_s", 250, Immediate::None),
+    fd("f32x4.convert_i32x4_u", 251, Immediate::None),
+    fd("i32x4.trunc_sat_f64x2_s_zero", 252, Immediate::None),
+    fd("i32x4.trunc_sat_f64x2_u_zero", 253, Immediate::None),
+    fd("f64x2.convert_low_i32x4_s", 254, Immediate::None),
+    fd("f64x2.convert_low_i32x4_u", 255, Immediate::None),
+];
@@ -0,0 +1,1538 @@
+// トークン列を S 式の木にして、バイナリ形式のモジュールを組み立てる
+//
+// 名前の解決は 2 段階で行う。先に全ての field を見て index space と $name を決め、
+// その後で field を順に section の要素に変換する
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use super::lexer::{self, Token, TokenKind};
+use super::opcode::{self, Immediate, Opcode};
+use super::{ErrorAt, MAX_NESTING};
+use crate::encode;
+
+type Result<T> = std::result::Result<T, ErrorAt>;
+
+struct Sexpr<'a> {
+    kind: SexprKind<'a>,
+    offset: usize,
+}
+
+enum SexprKind<'a> {
+    List(Vec<Sexpr<'a>>),
+    Atom(&'a str),
+    Id(&'a str),
+    String(Vec<u8>),
+}
+
+impl<'a> Sexpr<'a> {
+    fn atom(&self) -> Option<&'a str> {
+        match self.kind {
+            SexprKind::Atom(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn list(&self) -> Option<&[Sexpr<'a>]> {
+        match &self.kind {
+            SexprKind::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    // (keyword ...) の keyword
+    fn keyword(&self) -> Option<&'a str> {
+        self.list()?.first()?.atom()
+    }
+
+    fn is_index(&self) -> bool {
+        match self.kind {
+            SexprKind::Id(_) => true,
+            SexprKind::Atom(s) => s.starts_with(|c: char| c.is_ascii_digit()),
+            _ => false,
+        }
+    }
+}
+
+fn build_tree(tokens: Vec<Token<'_>>) -> Result<Vec<Sexpr<'_>>> {
+    // 開いている括弧の位置と、その中でここまでに読んだ要素
+    let mut stack = vec![(0, Vec::new())];
+    for token in tokens {
+        let (kind, offset) = match token.kind {
+            TokenKind::LParen => {
+                // 木を辿る処理は再帰なので、深すぎる入力はここで断る
+                if stack.len() > MAX_NESTING {
+                    return Err(ErrorAt::new(token.offset, "nesting too deep"));
+                }
+                stack.push((token.offset, Vec::new()));
+                continue;
+            }
+            TokenKind::RParen => {
+                if stack.len() == 1 {
+                    return Err(ErrorAt::new(token.offset, "unexpected ')'"));
+                }
+                let (offset, items) = stack.pop().unwrap();
+                (SexprKind::List(items), offset)
+            }
+            TokenKind::Atom(s) => (SexprKind::Atom(s), token.offset),
+            TokenKind::Id(s) => (SexprKind::Id(s), token.offset),
+            TokenKind::String(s) => (SexprKind::String(s), token.offset),
+        };
+        stack.last_mut().unwrap().1.push(Sexpr { kind, offset });
+    }
+    let (offset, items) = stack.pop().unwrap();
+    if !stack.is_empty() {
+        return Err(ErrorAt::new(offset, "unclosed '('"));
+    }
+    Ok(items)
+}
+
+// リストの要素を先頭から読む
+struct Cursor<'s, 'a> {
+    items: &'s [Sexpr<'a>],
+    pos: usize,
+    // 要素が足りない時のエラー位置として使う、囲んでいるリストの位置
+    offset: usize,
+}
+
+impl<'s, 'a> Cursor<'s, 'a> {
+    fn new(items: &'s [Sexpr<'a>], offset: usize) -> Self {
+        Self {
+            items,
+            pos: 0,
+            offset,
+        }
+    }
+
+    // (keyword ...) の keyword より後を読む
+    fn inner(list: &'s Sexpr<'a>) -> Self {
+        let items = list.list().map_or(&[][..], |items| &items[1..]);
+        Self::new(items, list.offset)
+    }
+
+    fn peek(&self) -> Option<&'s Sexpr<'a>> {
+        self.items.get(self.pos)
+    }
+
+    fn peek_atom(&self) -> Option<&'a str> {
+        self.peek()?.atom()
+    }
+
+    fn next(&mut self) -> Option<&'s Sexpr<'a>> {
+        let item = self.peek()?;
+        self.pos += 1;
+        Some(item)
+    }
+
+    fn expect(&mut self, what: &str) -> Result<&'s Sexpr<'a>> {
+        let offset = self.offset;
+        self.next()
+            .ok_or_else(|| ErrorAt::new(offset, format!("expected {}", what)))
+    }
+
+    fn next_id(&mut self) -> Option<&'a str> {
+        match self.peek()?.kind {
+            SexprKind::Id(id) => {
+                self.pos += 1;
+                Some(id)
+            }
+            _ => None,
+        }
+    }
+
+    fn next_index(&mut self) -> Option<&'s Sexpr<'a>> {
+        if self.peek()?.is_index() {
+            self.next()
+        } else {
+            None
+        }
+    }
+
+    // 次が (keyword ...) なら読み進めて、その中身を返す
+    fn next_list(&mut self, keyword: &str) -> Option<Cursor<'s, 'a>> {
+        let item = self.peek()?;
+        if item.keyword() != Some(keyword) {
+            return None;
+        }
+        self.pos += 1;
+        Some(Self::inner(item))
+    }
+
+    fn atom(&mut self, what: &str) -> Result<(&'a str, usize)> {
+        let item = self.expect(what)?;
+        item.atom()
+            .map(|s| (s, item.offset))
+            .ok_or_else(|| ErrorAt::new(item.offset, format!("expected {}", what)))
+    }
+
+    fn string(&mut self) -> Result<&'s [u8]> {
+        let item = self.expect("string")?;
+        match &item.kind {
+            SexprKind::String(s) => Ok(s),
+            _ => Err(ErrorAt::new(item.offset, "expected string")),
+        }
+    }
+
+    fn finish(&self) -> Result<()> {
+        match self.peek() {
+            Some(item) => Err(ErrorAt::new(item.offset, "unexpected token")),
+            None => Ok(()),
+        }
+    }
+}
+
+// (keyword ...) の keyword と中身
+fn field_cursor<'s, 'a>(item: &'s Sexpr<'a>, what: &str) -> Result<(&'a str, Cursor<'s, 'a>)> {
+    match item.keyword() {
+        Some(keyword) => Ok((keyword, Cursor::inner(item))),
+        None => Err(ErrorAt::new(item.offset, format!("expected {}", what))),
+    }
+}
+
+#[derive(Default)]
+struct Names<'a> {
+    map: HashMap<&'a str, u32>,
+    count: u32,
+}
+
+impl<'a> Names<'a> {
+    fn define(&mut self, id: Option<&'a str>, offset: usize) -> Result<u32> {
+        let index = self.count;
+        if let Some(id) = id {
+            if self.map.insert(id, index).is_some() {
+                return Err(ErrorAt::new(
+                    offset,
+                    format!("duplicate identifier ${}", id),
+                ));
+            }
+        }
+        self.count += 1;
+        Ok(index)
+    }
+
+    fn resolve(&self, item: &Sexpr<'a>, what: &str) -> Result<u32> {
+        match item.kind {
+            SexprKind::Id(id) => self
+                .map
+                .get(id)
+                .copied()
+                .ok_or_else(|| ErrorAt::new(item.offset, format!("unknown {} ${}", what, id))),
+            SexprKind::Atom(s) => parse_u32(s)
+                .ok_or_else(|| ErrorAt::new(item.offset, format!("invalid {} index", what))),
+            _ => Err(ErrorAt::new(
+                item.offset,
+                format!("expected {} index", what),
+            )),
+        }
+    }
+}
+
+// import/export の種類。値はバイナリ形式の externtype の値と同じ
+#[derive(Clone, Copy)]
+enum Kind {
+    Func,
+    Table,
+    Memory,
+    Global,
+}
+
+impl Kind {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "func" => Some(Self::Func),
+            "table" => Some(Self::Table),
+            "memory" => Some(Self::Memory),
+            "global" => Some(Self::Global),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Func => "func",
+            Self::Table => "table",
+            Self::Memory => "memory",
+            Self::Global => "global",
+        }
+    }
+}
+
+#[derive(PartialEq)]
+struct FuncType {
+    params: Vec<u8>,
+    results: Vec<u8>,
+}
+
+// (type x)? (param ...)* (result ...)*
+struct TypeUse<'s, 'a> {
+    index: Option<&'s Sexpr<'a>>,
+    params: Vec<(Option<&'a str>, u8)>,
+    results: Vec<u8>,
+}
+
+fn typeuse<'s, 'a>(cur: &mut Cursor<'s, 'a>) -> Result<TypeUse<'s, 'a>> {
+    let mut index = None;
+    if let Some(mut c) = cur.next_list("type") {
+        index = Some(c.expect("type index")?);
+        c.finish()?;
+    }
+    let mut params = Vec::new();
+    while let Some(mut c) = cur.next_list("param") {
+        if let Some(id) = c.next_id() {
+            params.push((Some(id), value_type(c.expect("value type")?)?));
+            c.finish()?;
+        } else {
+            while let Some(item) = c.next() {
+                params.push((None, value_type(item)?));
+            }
+        }
+    }
+    let mut results = Vec::new();
+    while let Some(mut c) = cur.next_list("result") {
+        while let Some(item) = c.next() {
+            results.push(value_type(item)?);
+        }
+    }
+    Ok(TypeUse {
+        index,
+        params,
+        results,
+    })
+}
+
+fn value_type(item: &Sexpr<'_>) -> Result<u8> {
+    match item.atom() {
+        Some("i32") => Ok(0x7f),
+        Some("i64") => Ok(0x7E),
+        Some("f32") => Ok(0x7D),
+        Some("f64") => Ok(0x7C),
+        Some("v128") => Ok(0x7B),
+        Some(s) => reference_type(s)
+            .ok_or_else(|| ErrorAt::new(item.offset, format!("unknown value type {}", s))),
+        None => Err(ErrorAt::new(item.offset, "expected value type")),
+    }
+}
+
+fn reference_type(s: &str) -> Option<u8> {
+    match s {
+        "funcref" => Some(0x70),
+        "externref" => Some(0x6f),
+        _ => None,
+    }
+}
+
+// 関数本体の中で使う名前
+#[derive(Default)]
+struct FuncContext<'a> {
+    locals: Names<'a>,
+    labels: Vec<Option<&'a str>>,
+}
+
+// flat に書いた block/loop/if のうち、end をまだ読んでいないもの
+struct FlatBlock<'a> {
+    label: Option<&'a str>,
+    // else を書ける if か
+    accepts_else: bool,
+}
+
+#[derive(Default)]
+struct Builder<'a> {
+    types: Vec<FuncType>,
+    type_names: Names<'a>,
+    // Kind の順に func, table, memory, global の index space
+    spaces: [Names<'a>; 4],
+    elem_names: Names<'a>,
+    data_names: Names<'a>,
+    imports: Vec<Vec<u8>>,
+    funcs: Vec<u32>,
+    tables: Vec<Vec<u8>>,
+    memories: Vec<Vec<u8>>,
+    globals: Vec<Vec<u8>>,
+    exports: Vec<Vec<u8>>,
+    start: Option<u32>,
+    elems: Vec<Vec<u8>>,
+    codes: Vec<Vec<u8>>,
+    datas: Vec<Vec<u8>>,
+    // memory.init と data.drop は data count section を必要とする
+    uses_data_count: bool,
+}
+
+/// WAT のテキストをバイナリ形式のモジュールに変換する
+pub(super) fn parse(text: &str) -> Result<Vec<u8>> {
+    let tree = build_tree(lexer::tokenize(text)?)?;
+    // (module ...) で囲まずに field を並べる書き方も受け付ける
+    let fields = match tree.as_slice() {
+        [module] if module.keyword() == Some("module") => {
+            let items = &module.list().unwrap()[1..];
+            match items.first() {
+                Some(Sexpr {
+                    kind: SexprKind::Id(_),
+                    ..
+                }) => &items[1..],
+                _ => items,
+            }
+        }
+        _ => &tree[..],
+    };
+    let mut builder = Builder::default();
+    let indexies = builder.declare(fields)?;
+    builder.define(fields, &indexies)?;
+    Ok(builder.finish())
+}
+
+impl<'a> Builder<'a> {
+    fn space(&mut self, kind: Kind) -> &mut Names<'a> {
+        &mut self.spaces[kind as usize]
+    }
+
+    // 各 field の index を決める。import は同じ種類の定義より前の index になる
+    fn declare(&mut self, fields: &[Sexpr<'a>]) -> Result<Vec<u32>> {
+        let mut indexies = vec![0; fields.len()];
+        for imports in [true, false] {
+            for (field, index) in fields.iter().zip(indexies.iter_mut()) {
+                let (keyword, mut cur) = field_cursor(field, "module field")?;
+                match keyword {
+                    "type" if imports => {
+                        self.type_names.define(cur.next_id(), field.offset)?;
+                        let mut c = cur
+                            .next_list("func")
+                            .ok_or_else(|| ErrorAt::new(field.offset, "expected (func ...)"))?;
+                        let t = typeuse(&mut c)?;
+                        c.finish()?;
+                        cur.finish()?;
+                        self.types.push(FuncType {
+                            params: t.params.iter().map(|p| p.1).collect(),
+                            results: t.results,
+                        });
+                    }
+                    "import" if imports => {
+                        cur.string()?;
+                        cur.string()?;
+                        let desc = cur.expect("import description")?;
+                        let (keyword, mut c) = field_cursor(desc, "import description")?;
+                        let kind = Kind::from_keyword(keyword).ok_or_else(|| {
+                            ErrorAt::new(desc.offset, format!("unknown import kind {}", keyword))
+                        })?;
+                        *index = self.space(kind).define(c.next_id(), desc.offset)?;
+                    }
+                    "func" | "table" | "memory" | "global" => {
+                        let kind = Kind::from_keyword(keyword).unwrap();
+                        let id = cur.next_id();
+                        while cur.next_list("export").is_some() {}
+                        let imported = cur.peek().and_then(Sexpr::keyword) == Some("import");
+                        if imported != imports {
+                            continue;
+                        }
+                        *index = self.space(kind).define(id, field.offset)?;
+                        // 中に書いた elem/data は名前のない segment になる
+                        if !imported && inline_segment(keyword, &mut cur).is_some() {
+                            match kind {
+                                Kind::Table => self.elem_names.define(None, field.offset)?,
+                                _ => self.data_names.define(None, field.offset)?,
+                            };
+                        }
+                    }
+                    "elem" if !imports => {
+                        *index = self.elem_names.define(cur.next_id(), field.offset)?;
+                    }
+                    "data" if !imports => {
+                        *index = self.data_names.define(cur.next_id(), field.offset)?;
+                    }
+                    "type" | "import" | "elem" | "data" | "export" | "start" => {}
+                    _ => {
+                        return Err(ErrorAt::new(
+                            field.offset,
+                            format!("unknown module field {}", keyword),
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(indexies)
+    }
+
+    // field を順に section の要素に変換する
+    fn define(&mut self, fields: &[Sexpr<'a>], indexies: &[u32]) -> Result<()> {
+        for (field, &index) in fields.iter().zip(indexies) {
+            let (keyword, mut cur) = field_cursor(field, "module field")?;
+            match keyword {
+                // 型は declare で登録済み
+                "type" => continue,
+                "import" => {
+                    let module = cur.string()?;
+                    let name = cur.string()?;
+                    let (keyword, mut c) = field_cursor(cur.expect("import description")?, "")?;
+                    c.next_id();
+                    self.import(module, name, Kind::from_keyword(keyword).unwrap(), &mut c)?;
+                    c.finish()?;
+                }
+                "func" | "table" | "memory" | "global" => {
+                    let kind = Kind::from_keyword(keyword).unwrap();
+                    cur.next_id();
+                    while let Some(mut c) = cur.next_list("export") {
+                        let name = c.string()?;
+                        c.finish()?;
+                        self.export(name, kind, index);
+                    }
+                    if let Some(mut c) = cur.next_list("import") {
+                        let module = c.string()?;
+                        let name = c.string()?;
+                        c.finish()?;
+                        self.import(module, name, kind, &mut cur)?;
+                    } else {
+                        match kind {
+                            Kind::Func => self.func(&mut cur)?,
+                            Kind::Table => self.table(index, &mut cur)?,
+                            Kind::Memory => self.memory(&mut cur)?,
+                            Kind::Global => self.global(&mut cur)?,
+                        }
+                    }
+                }
+                "export" => {
+                    let name = cur.string()?;
+                    let desc = cur.expect("export description")?;
+                    let (keyword, mut c) = field_cursor(desc, "export description")?;
+                    let kind = Kind::from_keyword(keyword).ok_or_else(|| {
+                        ErrorAt::new(desc.offset, format!("unknown export kind {}", keyword))
+                    })?;
+                    let index = self.space(kind).resolve(c.expect("index")?, kind.name())?;
+                    c.finish()?;
+                    self.export(name, kind, index);
+                }
+                "start" => {
+                    if self.start.is_some() {
+                        return Err(ErrorAt::new(field.offset, "multiple start functions"));
+                    }
+                    let func = cur.expect("function index")?;
+                    self.start = Some(self.space(Kind::Func).resolve(func, "func")?);
+                }
+                "elem" => self.elem(&mut cur)?,
+                "data" => self.data(&mut cur)?,
+                _ => unreachable!("unknown fields are rejected in declare"),
+            }
+            cur.finish()?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut buf = b"\0asm".to_vec();
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        let types: Vec<Vec<u8>> = self
+            .types
+            .iter()
+            .map(|t| {
+                let mut buf = vec![0x60];
+                encode::encode_len(&mut buf, &t.params);
+                encode::encode_len(&mut buf, &t.results);
+                buf
+            })
+            .collect();
+        let funcs: Vec<Vec<u8>> = self
+            .funcs
+            .iter()
+            .map(|&index| {
+                let mut buf = Vec::new();
+                encode::encode_varint_u32(&mut buf, index);
+                buf
+            })
+            .collect();
+        write_section(&mut buf, 1, &types);
+        write_section(&mut buf, 2, &self.imports);
+        write_section(&mut buf, 3, &funcs);
+        write_section(&mut buf, 4, &self.tables);
+        write_section(&mut buf, 5, &self.memories);
+        write_section(&mut buf, 6, &self.globals);
+        write_section(&mut buf, 7, &self.exports);
+        if let Some(start) = self.start {
+            let mut payload = Vec::new();
+            encode::encode_varint_u32(&mut payload, start);
+            buf.push(8);
+            encode::encode_len(&mut buf, &payload);
+        }
+        write_section(&mut buf, 9, &self.elems);
+        if self.uses_data_count {
+            let mut payload = Vec::new();
+            encode::encode_varint_u32(&mut payload, self.datas.len() as u32);
+            buf.push(12);
+            encode::encode_len(&mut buf, &payload);
+        }
+        write_section(&mut buf, 10, &self.codes);
+        write_section(&mut buf, 11, &self.datas);
+        buf
+    }
+
+    fn resolve_type(&mut self, t: &TypeUse<'_, 'a>) -> Result<u32> {
+        if let Some(index) = t.index {
+            return self.type_names.resolve(index, "type");
+        }
+        // 同じ型があればそれを使い、なければ末尾に追加する
+        let func_type = FuncType {
+            params: t.params.iter().map(|p| p.1).collect(),
+            results: t.results.clone(),
+        };
+        let index = match self.types.iter().position(|t| *t == func_type) {
+            Some(index) => index,
+            None => {
+                self.types.push(func_type);
+                self.types.len() - 1
+            }
+        };
+        Ok(index as u32)
+    }
+
+    fn import(
+        &mut self,
+        module: &[u8],
+        name: &[u8],
+        kind: Kind,
+        cur: &mut Cursor<'_, 'a>,
+    ) -> Result<()> {
+        let mut buf = Vec::new();
+        encode::encode_len(&mut buf, module);
+        encode::encode_len(&mut buf, name);
+        buf.push(kind as u8);
+        match kind {
+            Kind::Func => {
+                let t = typeuse(cur)?;
+                let index = self.resolve_type(&t)?;
+                encode::encode_varint_u32(&mut buf, index);
+            }
+            Kind::Table => table_type(cur, &mut buf)?,
+            Kind::Memory => limits(cur, &mut buf)?,
+            Kind::Global => global_type(cur, &mut buf)?,
+        }
+        self.imports.push(buf);
+        Ok(())
+    }
+
+    fn export(&mut self, name: &[u8], kind: Kind, index: u32) {
+        let mut buf = Vec::new();
+        encode::encode_len(&mut buf, name);
+        buf.push(kind as u8);
+        encode::encode_varint_u32(&mut buf, index);
+        self.exports.push(buf);
+    }
+
+    fn func(&mut self, cur: &mut Cursor<'_, 'a>) -> Result<()> {
+        let t = typeuse(cur)?;
+        let type_index = self.resolve_type(&t)?;
+        let mut ctx = FuncContext::default();
+        if t.params.is_empty() {
+            // 範囲外になるのは (type N) で index を直接書いた場合だけ
+            let Some(func_type) = self.types.get(type_index as usize) else {
+                let offset = t.index.map_or(cur.offset, |index| index.offset);
+                return Err(ErrorAt::new(offset, format!("unknown type {}", type_index)));
+            };
+            for _ in &func_type.params {
+                ctx.locals.define(None, cur.offset)?;
+            }
+        }
+        for (id, _) in &t.params {
+            ctx.locals.define(*id, cur.offset)?;
+        }
+        let mut locals = Vec::new();
+        while let Some(mut c) = cur.next_list("local") {
+            if let Some(id) = c.next_id() {
+                ctx.locals.define(Some(id), c.offset)?;
+                locals.push(value_type(c.expect("value type")?)?);
+                c.finish()?;
+            } else {
+                while let Some(item) = c.next() {
+                    ctx.locals.define(None, item.offset)?;
+                    locals.push(value_type(item)?);
+                }
+            }
+        }
+        // 同じ型が続く local は (個数, 型) にまとめる
+        let mut runs: Vec<(u32, u8)> = Vec::new();
+        for t in locals {
+            match runs.last_mut() {
+                Some((count, last)) if *last == t => *count += 1,
+                _ => runs.push((1, t)),
+            }
+        }
+        let mut body = Vec::new();
+        encode::encode_vec(&mut body, &runs, |buf, (count, t)| {
+            encode::encode_varint_u32(buf, *count);
+            buf.push(*t);
+        });
+        self.instrs(&mut ctx, cur, &mut body)?;
+        body.push(0x0b);
+        let mut buf = Vec::new();
+        encode::encode_len(&mut buf, &body);
+        self.funcs.push(type_index);
+        self.codes.push(buf);
+        Ok(())
+    }
+
+    fn table(&mut self, index: u32, cur: &mut Cursor<'_, 'a>) -> Result<()> {
+        let Some((ref_type, mut c)) = inline_segment("table", cur) else {
+            let mut buf = Vec::new();
+            table_type(cur, &mut buf)?;
+            self.tables.push(buf);
+            return Ok(());
+        };
+        // 要素の数をちょうど持つ table と、その先頭に置く active segment になる
+        let ref_type = ref_type.unwrap();
+        let mut init = Vec::new();
+        let (count, uses_expression) = self.elem_list(&mut c, &mut init)?;
+        let mut buf = vec![ref_type, 0x01];
+        encode::encode_varint_u32(&mut buf, count);
+        encode::encode_varint_u32(&mut buf, count);
+        self.tables.push(buf);
+        let offset = vec![0x41, 0x00, 0x0b];
+        let mode = ElemMode::Active {
+            table: index,
+            offset,
+        };
+        self.push_elem(mode, ref_type, uses_expression, &init);
+        Ok(())
+    }
+
+    fn memory(&mut self, cur: &mut Cursor<'_, 'a>) -> Result<()> {
+        let Some((_, mut c)) = inline_segment("memory", cur) else {
+            let mut buf = Vec::new();
+            limits(cur, &mut buf)?;
+            self.memories.push(buf);
+            return Ok(());
+        };
+        // 初期値がちょうど収まるページ数の memory と、その先頭に置く active segment になる
+        let mut init = Vec::new();
+        while c.peek().is_some() {
+            init.extend_from_slice(c.string()?);
+        }
+        let pages = init.len().div_ceil(0x10000) as u32;
+        let mut buf = vec![0x01];
+        encode::encode_varint_u32(&mut buf, pages);
+        encode::encode_varint_u32(&mut buf, pages);
+        self.memories.push(buf);
+        let mut buf = vec![0x00, 0x41, 0x00, 0x0b];
+        encode::encode_len(&mut buf, &init);
+        self.datas.push(buf);
+        Ok(())
+    }
+
+    fn global(&mut self, cur: &mut Cursor<'_, 'a>) -> Result<()> {
+        let mut buf = Vec::new();
+        global_type(cur, &mut buf)?;
+        self.instrs(&mut FuncContext::default(), cur, &mut buf)?;
+        buf.push(0x0b);
+        self.globals.push(buf);
+        Ok(())
+    }
+
+    fn elem(&mut self, cur: &mut Cursor<'_, 'a>) -> Result<()> {
+        cur.next_id();
+        let mode = if cur.peek_atom() == Some("declare") {
+            cur.next();
+            ElemMode::Declarative
+        } else {
+            let mut table = None;
+            if let Some(mut c) = cur.next_list("table") {
+                let index = c.expect("table index")?;
+                table = Some(self.space(Kind::Table).resolve(index, "table")?);
+                c.finish()?;
+            }
+            // elemlist は func か reference type で始まるので、リストが来れば offset
+            match cur.peek() {
+                Some(item) if item.list().is_some() => {
+                    cur.next();
+                    ElemMode::Active {
+                        table: table.unwrap_or(0),
+                        offset: self.offset_expr(item, "offset")?,
+                    }
+                }
+                _ if table.is_some() => return Err(ErrorAt::new(cur.offset, "expected offset")),
+                _ => ElemMode::Passive,
+            }
+        };
+        let ref_type = match cur.peek_atom() {
+            Some("func") => {
+                cur.next();
+                None
+            }
+            Some(s) => reference_type(s).inspect(|_| {
+                cur.next();
+            }),
+            None => None,
+        };
+        let mut init = Vec::new();
+        let (_, uses_expression) = self.elem_list(cur, &mut init)?;
+        if uses_expression && ref_type.is_none() {
+            return Err(ErrorAt::new(cur.offset, "expected reference type"));
+        }
+        self.push_elem(mode, ref_type.unwrap_or(0x70), uses_expression, &init);
+        Ok(())
+    }
+
+    // function index の並びか、(item ...) または 1 つの folded 命令の並び
+    fn elem_list(&mut self, cur: &mut Cursor<'_, 'a>, buf: &mut Vec<u8>) -> Result<(u32, bool)> {
+        let uses_expression = cur.peek().is_some_and(|item| item.list().is_some());
+        let mut items = Vec::new();
+        let mut count = 0;
+        while let Some(item) = cur.next() {
+            if uses_expression {
+                items.extend(self.offset_expr(item, "item")?);
+            } else {
+                let index = self.space(Kind::Func).resolve(item, "func")?;
+                encode::encode_varint_u32(&mut items, index);
+            }
+            count += 1;
+        }
+        encode::encode_varint_u32(buf, count);
+        buf.extend(items);
+        Ok((count, uses_expression))
+    }
+
+    // flag の決め方は section::Element::encode と同じ
+    fn push_elem(&mut self, mode: ElemMode, ref_type: u8, uses_expression: bool, init: &[u8]) {
+        let mut flag = match &mode {
+            ElemMode::Active { table: 0, .. } if ref_type == 0x70 => 0b000,
+            ElemMode::Active { .. } => 0b010,
+            ElemMode::Passive => 0b001,
+            ElemMode::Declarative => 0b011,
+        };
+        if uses_expression {
+            flag |= 0b100;
+        }
+        let mut buf = Vec::new();
+        encode::encode_varint_u32(&mut buf, flag);
+        if let ElemMode::Active { table, offset } = &mode {
+            if flag & 0b010 != 0 {
+                encode::encode_varint_u32(&mut buf, *table);
+            }
+            buf.extend_from_slice(offset);
+        }
+        if flag & 0b011 != 0 {
+            buf.push(if uses_expression { ref_type } else { 0x00 });
+        }
+        buf.extend_from_slice(init);
+        self.elems.push(buf);
+    }
+
+    fn data(&mut self, cur: &mut Cursor<'_, 'a>) -> Result<()> {
+        cur.next_id();
+        let mut memory = None;
+        if let Some(mut c) = cur.next_list("memory") {
+            let index = c.expect("memory index")?;
+            memory = Some(self.space(Kind::Memory).resolve(index, "memory")?);
+            c.finish()?;
+        }
+        let mut buf = Vec::new();
+        match cur.peek() {
+            Some(item) if item.list().is_some() => {
+                cur.next();
+                match memory {
+                    None | Some(0) => buf.push(0x00),
+                    Some(index) => {
+                        buf.push(0x02);
+                        encode::encode_varint_u32(&mut buf, index);
+                    }
+                }
+                buf.extend(self.offset_expr(item, "offset")?);
+            }
+            _ if memory.is_some() => return Err(ErrorAt::new(cur.offset, "expected offset")),
+            _ => buf.push(0x01),
+        }
+        let mut init = Vec::new();
+        while cur.peek().is_some() {
+            init.extend_from_slice(cur.string()?);
+        }
+        encode::encode_len(&mut buf, &init);
+        self.datas.push(buf);
+        Ok(())
+    }
+
+    // (keyword instr*) または 1 つの folded 命令で書いた定数式
+    fn offset_expr(&mut self, item: &Sexpr<'a>, keyword: &str) -> Result<Vec<u8>> {
+        let mut ctx = FuncContext::default();
+        let mut buf = Vec::new();
+        if item.keyword() == Some(keyword) {
+            let mut c = Cursor::inner(item);
+            self.instrs(&mut ctx, &mut c, &mut buf)?;
+            c.finish()?;
+        } else {
+            self.folded(&mut ctx, item, &mut buf)?;
+        }
+        buf.push(0x0b);
+        Ok(buf)
+    }
+
+    // else か end の手前まで命令を読む
+    // flat な block は同じリストの中で入れ子になるので、再帰せず開いている block をスタックに積む
+    fn instrs(
+        &mut self,
+        ctx: &mut FuncContext<'a>,
+        cur: &mut Cursor<'_, 'a>,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        let mut blocks: Vec<FlatBlock<'a>> = Vec::new();
+        loop {
+            let item = match cur.peek() {
+                Some(item) => item,
+                None if blocks.is_empty() => return Ok(()),
+                None => return Err(ErrorAt::new(cur.offset, "expected end")),
+            };
+            match item.kind {
+                SexprKind::List(_) => {
+                    cur.next();
+                    self.folded(ctx, item, buf)?;
+                }
+                SexprKind::Atom("else") => match blocks.last_mut() {
+                    None => return Ok(()),
+                    Some(block) if block.accepts_else => {
+                        cur.next();
+                        check_label(cur, block.label)?;
+                        block.accepts_else = false;
+                        buf.push(0x05);
+                    }
+                    Some(_) => return Err(ErrorAt::new(item.offset, "expected end")),
+                },
+                SexprKind::Atom("end") => {
+                    let block = match blocks.pop() {
+                        Some(block) => block,
+                        None => return Ok(()),
+                    };
+                    cur.next();
+                    check_label(cur, block.label)?;
+                    ctx.labels.pop();
+                    buf.push(0x0b);
+                }
+                SexprKind::Atom(name) => {
+                    cur.next();
+                    let op = opcode::find_by_name(name).ok_or_else(|| {
+                        ErrorAt::new(item.offset, format!("unknown instruction {}", name))
+                    })?;
+                    if op.immediate != Immediate::Block {
+                        self.instr(ctx, op, cur, buf)?;
+                        continue;
+                    }
+                    let label = cur.next_id();
+                    buf.push(op.code);
+                    self.block_type(cur, buf)?;
+                    ctx.labels.push(label);
+                    blocks.push(FlatBlock {
+                        label,
+                        accepts_else: name == "if",
+                    });
+                }
+                _ => return Err(ErrorAt::new(item.offset, "expected instruction")),
+            }
+        }
+    }
+
+    // folded の先頭に書いた、block/loop/if 以外の命令
+    fn plain(
+        &mut self,
+        ctx: &mut FuncContext<'a>,
+        name: &str,
+        offset: usize,
+        cur: &mut Cursor<'_, 'a>,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        let op = opcode::find_by_name(name)
+            .ok_or_else(|| ErrorAt::new(offset, format!("unknown instruction {}", name)))?;
+        self.instr(ctx, op, cur, buf)
+    }
+
+    // (op immediate* operand*) は operand を先に書く
+    fn folded(
+        &mut self,
+        ctx: &mut FuncContext<'a>,
+        item: &Sexpr<'a>,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        let (name, mut cur) = field_cursor(item, "instruction")?;
+        match name {
+            "block" | "loop" => {
+                let label = cur.next_id();
+                buf.push(if name == "block" { 0x02 } else { 0x03 });
+                self.block_type(&mut cur, buf)?;
+                ctx.labels.push(label);
+                self.instrs(ctx, &mut cur, buf)?;
+                ctx.labels.pop();
+            }
+            "if" => {
+                let label = cur.next_id();
+                let mut block_type = Vec::new();
+                self.block_type(&mut cur, &mut block_type)?;
+                // then より前は条件を計算する folded 命令
+                while let Some(cond) = cur
+                    .peek()
+                    .filter(|c| c.list().is_some() && c.keyword() != Some("then"))
+                {
+                    cur.next();
+                    self.folded(ctx, cond, buf)?;
+                }
+                buf.push(0x04);
+                buf.extend(block_type);
+                ctx.labels.push(label);
+                let mut then = cur
+                    .next_list("then")
+                    .ok_or_else(|| ErrorAt::new(item.offset, "expected (then ...)"))?;
+                self.instrs(ctx, &mut then, buf)?;
+                then.finish()?;
+                if let Some(mut els) = cur.next_list("else") {
+                    buf.push(0x05);
+                    self.instrs(ctx, &mut els, buf)?;
+                    els.finish()?;
+                }
+                ctx.labels.pop();
+            }
+            "then" | "else" | "end" => {
+                return Err(ErrorAt::new(item.offset, format!("unexpected {}", name)))
+            }
+            _ => {
+                let mut instr = Vec::new();
+                let offset = item.list().unwrap()[0].offset;
+                self.plain(ctx, name, offset, &mut cur, &mut instr)?;
+                while let Some(operand) = cur.next() {
+                    if operand.list().is_none() {
+                        return Err(ErrorAt::new(operand.offset, "unexpected token"));
+                    }
+                    self.folded(ctx, operand, buf)?;
+                }
+                buf.extend(instr);
+                return Ok(());
+            }
+        }
+        cur.finish()?;
+        buf.push(0x0b);
+        Ok(())
+    }
+
+    fn block_type(&mut self, cur: &mut Cursor<'_, 'a>, buf: &mut Vec<u8>) -> Result<()> {
+        let t = typeuse(cur)?;
+        if t.index.is_none() && t.params.is_empty() && t.results.len() <= 1 {
+            buf.push(t.results.first().copied().unwrap_or(0x40));
+        } else {
+            let index = self.resolve_type(&t)?;
+            encode::encode_varint_s33(buf, index as i64);
+        }
+        Ok(())
+    }
+
+    fn instr(
+        &mut self,
+        ctx: &mut FuncContext<'a>,
+        op: &Opcode,
+        cur: &mut Cursor<'_, 'a>,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        if op.immediate == Immediate::Select {
+            // 型を書いた select は別の opcode になる
+            let mut types = Vec::new();
+            while let Some(mut c) = cur.next_list("result") {
+                while let Some(item) = c.next() {
+                    types.push(value_type(item)?);
+                }
+            }
+            if types.is_empty() {
+                buf.push(0x1B);
+            } else {
+                buf.push(0x1C);
+                encode::encode_len(buf, &types);
+            }
+            return Ok(());
+        }
+        buf.push(op.code);
+        if let Some(sub) = op.sub {
+            encode::encode_varint_u32(buf, sub);
+        }
+        match op.immediate {
+            Immediate::None => {}
+            Immediate::Block | Immediate::Select => unreachable!("handled by the caller"),
+            Immediate::Label => {
+                let label = label(ctx, cur.expect("label")?)?;
+                encode::encode_varint_u32(buf, label);
+            }
+            Immediate::BrTable => {
+                let mut labels = Vec::new();
+                while let Some(item) = cur.next_index() {
+                    labels.push(label(ctx, item)?);
+                }
+                let default = labels
+                    .pop()
+                    .ok_or_else(|| ErrorAt::new(cur.offset, "expected label"))?;
+                encode::encode_vec(buf, &labels, |buf, l| encode::encode_varint_u32(buf, *l));
+                encode::encode_varint_u32(buf, default);
+            }
+            Immediate::Func => self.index(Kind::Func, cur, buf)?,
+            Immediate::Global => self.index(Kind::Global, cur, buf)?,
+            Immediate::CallIndirect => {
+                let table = self.optional_index(Kind::Table, cur)?;
+                let t = typeuse(cur)?;
+                let type_index = self.resolve_type(&t)?;
+                encode::encode_varint_u32(buf, type_index);
+                encode::encode_varint_u32(buf, table);
+            }
+            Immediate::Local => {
+                let index = ctx.locals.resolve(cur.expect("local index")?, "local")?;
+                encode::encode_varint_u32(buf, index);
+            }
+            Immediate::Table => {
+                let table = self.optional_index(Kind::Table, cur)?;
+                encode::encode_varint_u32(buf, table);
+            }
+            Immediate::TableInit => {
+                // table.init $table? $elem
+                let first = cur.expect("elem index")?;
+                let (table, elem) = match cur.next_index() {
+                    Some(elem) => (self.space(Kind::Table).resolve(first, "table")?, elem),
+                    None => (0, first),
+                };
+                let elem = self.elem_names.resolve(elem, "elem")?;
+                encode::encode_varint_u32(buf, elem);
+                encode::encode_varint_u32(buf, table);
+            }
+            Immediate::TableCopy => {
+                let dst = self.optional_index(Kind::Table, cur)?;
+                let src = self.optional_index(Kind::Table, cur)?;
+                encode::encode_varint_u32(buf, dst);
+                encode::encode_varint_u32(buf, src);
+            }
+            Immediate::Elem => {
+                let elem = self.elem_names.resolve(cur.expect("elem index")?, "elem")?;
+                encode::encode_varint_u32(buf, elem);
+            }
+            Immediate::Data => {
+                self.uses_data_count = true;
+                let data = self.data_names.resolve(cur.expect("data index")?, "data")?;
+                encode::encode_varint_u32(buf, data);
+            }
+            Immediate::MemArg(natural) => memarg(cur, natural, buf)?,
+            Immediate::MemArgLane(natural) => {
+                memarg(cur, natural, buf)?;
+                buf.push(literal(cur, "lane index", parse_u8)?);
+            }
+            Immediate::Memory => {
+                let memory = self.optional_index(Kind::Memory, cur)?;
+                encode::encode_varint_u32(buf, memory);
+            }
+            Immediate::MemoryInit => {
+                // memory.init $memory? $data
+                self.uses_data_count = true;
+                let first = cur.expect("data index")?;
+                let (memory, data) = match cur.next_index() {
+                    Some(data) => (self.space(Kind::Memory).resolve(first, "memory")?, data),
+                    None => (0, first),
+                };
+                let data = self.data_names.resolve(data, "data")?;
+                encode::encode_varint_u32(buf, data);
+                encode::encode_varint_u32(buf, memory);
+            }
+            Immediate::MemoryCopy => {
+                let dst = self.optional_index(Kind::Memory, cur)?;
+                let src = self.optional_index(Kind::Memory, cur)?;
+                encode::encode_varint_u32(buf, dst);
+                encode::encode_varint_u32(buf, src);
+            }
+            Immediate::I32 => {
+                let value = literal(cur, "i32 literal", |s| parse_int(s, 32))?;
+                encode::encode_varint_s32(buf, value as u32 as i32);
+            }
+            Immediate::I64 => {
+                let value = literal(cur, "i64 literal", |s| parse_int(s, 64))?;
+                encode::encode_varint_s64(buf, value as i64);
+            }
+            Immediate::F32 => {
+                let value = literal(cur, "f32 literal", parse_f32)?;
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+            Immediate::F64 => {
+                let value = literal(cur, "f64 literal", parse_f64)?;
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+            Immediate::V128 => v128(cur, buf)?,
+            Immediate::Shuffle => {
+                for _ in 0..16 {
+                    buf.push(literal(cur, "lane index", parse_u8)?);
+                }
+            }
+            Immediate::Lane => buf.push(literal(cur, "lane index", parse_u8)?),
+            Immediate::RefNull => {
+                let (s, offset) = cur.atom("heap type")?;
+                buf.push(match s {
+                    "func" | "funcref" => 0x70,
+                    "extern" | "externref" => 0x6f,
+                    _ => return Err(ErrorAt::new(offset, format!("unknown heap type {}", s))),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn index(&mut self, kind: Kind, cur: &mut Cursor<'_, 'a>, buf: &mut Vec<u8>) -> Result<()> {
+        let item = cur.expect(&format!("{} index", kind.name()))?;
+        let index = self.space(kind).resolve(item, kind.name())?;
+        encode::encode_varint_u32(buf, index);
+        Ok(())
+    }
+
+    // table や memory の index は省略すると 0
+    fn optional_index(&mut self, kind: Kind, cur: &mut Cursor<'_, 'a>) -> Result<u32> {
+        match cur.next_index() {
+            Some(item) => self.space(kind).resolve(item, kind.name()),
+            None => Ok(0),
+        }
+    }
+}
+
+enum ElemMode {
+    Passive,
+    Active { table: u32, offset: Vec<u8> },
+    Declarative,
+}
+
+// (table reftype (elem ...)) と (memory (data ...)) の中身。table の場合は reference type も返す
+fn inline_segment<'s, 'a>(
+    keyword: &str,
+    cur: &mut Cursor<'s, 'a>,
+) -> Option<(Option<u8>, Cursor<'s, 'a>)> {
+    let (ref_type, segment) = match keyword {
+        "table" => (Some(reference_type(cur.peek_atom()?)?), "elem"),
+        "memory" => (None, "data"),
+        _ => return None,
+    };
+    let skip = ref_type.map_or(0, |_| 1);
+    let item = cur.items.get(cur.pos + skip)?;
+    if item.keyword() != Some(segment) {
+        return None;
+    }
+    cur.pos += skip + 1;
+    Some((ref_type, Cursor::inner(item)))
+}
+
+fn write_section(buf: &mut Vec<u8>, id: u8, entries: &[Vec<u8>]) {
+    if entries.is_empty() {
+        return;
+    }
+    let mut payload = Vec::new();
+    encode::encode_varint_u32(&mut payload, entries.len() as u32);
+    for entry in entries {
+        payload.extend_from_slice(entry);
+    }
+    buf.push(id);
+    encode::encode_len(buf, &payload);
+}
+
+// end や else の後に書いた label は block の label と同じでなければならない
+fn check_label(cur: &mut Cursor<'_, '_>, label: Option<&str>) -> Result<()> {
+    let offset = cur.peek().map_or(cur.offset, |item| item.offset);
+    match cur.next_id() {
+        Some(id) if Some(id) != label => {
+            Err(ErrorAt::new(offset, format!("mismatched label ${}", id)))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn label(ctx: &FuncContext<'_>, item: &Sexpr<'_>) -> Result<u32> {
+    match item.kind {
+        SexprKind::Id(id) => ctx
+            .labels
+            .iter()
+            .rev()
+            .position(|l| *l == Some(id))
+            .map(|depth| depth as u32)
+            .ok_or_else(|| ErrorAt::new(item.offset, format!("unknown label ${}", id))),
+        SexprKind::Atom(s) => {
+            parse_u32(s).ok_or_else(|| ErrorAt::new(item.offset, "invalid label index"))
+        }
+        _ => Err(ErrorAt::new(item.offset, "expected label")),
+    }
+}
+
+fn limits(cur: &mut Cursor<'_, '_>, buf: &mut Vec<u8>) -> Result<()> {
+    let min = literal(cur, "limits", parse_u32)?;
+    let has_max = cur
+        .peek()
+        .is_some_and(|item| item.atom().is_some() && item.is_index());
+    match has_max.then(|| literal(cur, "limits", parse_u32)) {
+        Some(max) => {
+            buf.push(0x01);
+            encode::encode_varint_u32(buf, min);
+            encode::encode_varint_u32(buf, max?);
+        }
+        None => {
+            buf.push(0x00);
+            encode::encode_varint_u32(buf, min);
+        }
+    }
+    Ok(())
+}
+
+fn table_type(cur: &mut Cursor<'_, '_>, buf: &mut Vec<u8>) -> Result<()> {
+    let mut limits_buf = Vec::new();
+    limits(cur, &mut limits_buf)?;
+    let (s, offset) = cur.atom("reference type")?;
+    let ref_type = reference_type(s)
+        .ok_or_else(|| ErrorAt::new(offset, format!("unknown reference type {}", s)))?;
+    buf.push(ref_type);
+    buf.extend(limits_buf);
+    Ok(())
+}
+
+fn global_type(cur: &mut Cursor<'_, '_>, buf: &mut Vec<u8>) -> Result<()> {
+    if let Some(mut c) = cur.next_list("mut") {
+        buf.push(value_type(c.expect("value type")?)?);
+        buf.push(0x01);
+        return c.finish();
+    }
+    buf.push(value_type(cur.expect("global type")?)?);
+    buf.push(0x00);
+    Ok(())
+}
+
+// offset=N と align=N は省略できる。align はバイト数で書き、2 の指数でエンコードする
+fn memarg(cur: &mut Cursor<'_, '_>, natural: u32, buf: &mut Vec<u8>) -> Result<()> {
+    let mut offset = 0;
+    if let Some(value) = cur.peek_atom().and_then(|s| s.strip_prefix("offset=")) {
+        offset = parse_u32(value)
+            .ok_or_else(|| ErrorAt::new(cur.peek().unwrap().offset, "invalid offset"))?;
+        cur.next();
+    }
+    let mut align = natural;
+    if let Some(value) = cur.peek_atom().and_then(|s| s.strip_prefix("align=")) {
+        align = parse_u32(value)
+            .filter(|a| a.is_power_of_two())
+            .ok_or_else(|| {
+                ErrorAt::new(
+                    cur.peek().unwrap().offset,
+                    "alignment must be a power of two",
+                )
+            })?
+            .trailing_zeros();
+        cur.next();
+    }
+    encode::encode_varint_u32(buf, align);
+    encode::encode_varint_u32(buf, offset);
+    Ok(())
+}
+
+fn v128(cur: &mut Cursor<'_, '_>, buf: &mut Vec<u8>) -> Result<()> {
+    let (shape, offset) = cur.atom("vector shape")?;
+    match shape {
+        "i8x16" => {
+            for _ in 0..16 {
+                buf.push(literal(cur, "i8 literal", |s| parse_int(s, 8))? as u8);
+            }
+        }
+        "i16x8" => {
+            for _ in 0..8 {
+                let lane = literal(cur, "i16 literal", |s| parse_int(s, 16))? as u16;
+                buf.extend_from_slice(&lane.to_le_bytes());
+            }
+        }
+        "i32x4" => {
+            for _ in 0..4 {
+                let lane = literal(cur, "i32 literal", |s| parse_int(s, 32))? as u32;
+                buf.extend_from_slice(&lane.to_le_bytes());
+            }
+        }
+        "i64x2" => {
+            for _ in 0..2 {
+                let lane = literal(cur, "i64 literal", |s| parse_int(s, 64))?;
+                buf.extend_from_slice(&lane.to_le_bytes());
+            }
+        }
+        "f32x4" => {
+            for _ in 0..4 {
+                buf.extend_from_slice(&literal(cur, "f32 literal", parse_f32)?.to_le_bytes());
+            }
+        }
+        "f64x2" => {
+            for _ in 0..2 {
+                buf.extend_from_slice(&literal(cur, "f64 literal", parse_f64)?.to_le_bytes());
+            }
+        }
+        _ => {
+            return Err(ErrorAt::new(
+                offset,
+                format!("unknown vector shape {}", shape),
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn literal<T, F>(cur: &mut Cursor<'_, '_>, what: &str, parse: F) -> Result<T>
+where
+    F: Fn(&str) -> Option<T>,
+{
+    let (s, offset) = cur.atom(what)?;
+    parse(s).ok_or_else(|| ErrorAt::new(offset, format!("invalid {} {}", what, s)))
+}
+
+fn split_sign(s: &str) -> (bool, &str) {
+    match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    }
+}
+
+// 10 進数か 0x で始まる 16 進数。数字の間に _ を書ける
+fn parse_uint(s: &str) -> Option<u64> {
+    let (radix, digits) = match s.strip_prefix("0x") {
+        Some(hex) => (16, hex),
+        None => (10, s),
+    };
+    if !digits.starts_with(|c: char| c.is_digit(radix)) || digits.ends_with('_') {
+        return None;
+    }
+    u64::from_str_radix(&digits.replace('_', ""), radix).ok()
+}
+
+fn parse_u32(s: &str) -> Option<u32> {
+    u32::try_from(parse_uint(s)?).ok()
+}
+
+fn parse_u8(s: &str) -> Option<u8> {
+    u8::try_from(parse_uint(s)?).ok()
+}
+
+// 符号付きと符号なしのどちらの範囲でも書ける。bits ビットの 2 の補数表現で返す
+fn parse_int(s: &str, bits: u32) -> Option<u64> {
+    let (negative, digits) = split_sign(s);
+    let magnitude = parse_uint(digits)?;
+    let max = u64::MAX >> (64 - bits);
+    if negative {
+        (magnitude <= 1 << (bits - 1)).then(|| magnitude.wrapping_neg() & max)
+    } else {
+        (magnitude <= max).then_some(magnitude)
+    }
+}
+
+fn parse_f32(s: &str) -> Option<u32> {
+    let (negative, body) = split_sign(s);
+    let bits = match body {
+        "inf" => 0x7f80_0000,
+        "nan" => 0x7fc0_0000,
+        _ => match body.strip_prefix("nan:") {
+            Some(payload) => {
+                let payload = parse_uint(payload).filter(|p| (1..1 << 23).contains(p))?;
+                0x7f80_0000 | payload as u32
+            }
+            None => match body.strip_prefix("0x") {
+                Some(hex) => parse_hex_float(hex, 23, 8)? as u32,
+                None => parse_decimal::<f32>(body)?.to_bits(),
+            },
+        },
+    };
+    Some(if negative { bits | 1 << 31 } else { bits })
+}
+
+fn parse_f64(s: &str) -> Option<u64> {
+    let (negative, body) = split_sign(s);
+    let bits = match body {
+        "inf" => 0x7ff0_0000_0000_0000,
+        "nan" => 0x7ff8_0000_0000_0000,
+        _ => match body.strip_prefix("nan:") {
+            Some(payload) => {
+                let payload = parse_uint(payload).filter(|p| (1..1 << 52).contains(p))?;
+                0x7ff0_0000_0000_0000 | payload
+            }
+            None => match body.strip_prefix("0x") {
+                Some(hex) => parse_hex_float(hex, 52, 11)?,
+                None => parse_decimal::<f64>(body)?.to_bits(),
+            },
+        },
+    };
+    Some(if negative { bits | 1 << 63 } else { bits })
+}
+
+fn parse_decimal<T: std::str::FromStr>(s: &str) -> Option<T> {
+    // str::parse は inf や符号も受け付けてしまうので先頭は数字に限る
+    if !s.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    s.replace('_', "").parse().ok()
+}
+
+// 0x の後の 1.8p3 のような 16 進数の浮動小数点数を、仮数部が mantissa_bits ビット、
+// 指数部が exp_bits ビットの形式のビット列 (符号なし) にする
+// 一度だけ最近接偶数に丸める。表せないほど大きければ inf、小さければ 0 になる
+fn parse_hex_float(s: &str, mantissa_bits: u32, exp_bits: u32) -> Option<u64> {
+    let s = s.replace('_', "");
+    let (mantissa, exp) = match s.split_once(['p', 'P']) {
+        Some((mantissa, exp)) => (mantissa, parse_exponent(exp)?),
+        None => (&s[..], 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int.is_empty() {
+        return None;
+    }
+    // 値は sig * 2^exp。sig に入りきらない桁は捨て、0 でない桁があったかを sticky に残す
+    let (mut sig, mut exp, mut sticky) = (0u64, exp, false);
+    for (i, c) in int.chars().chain(frac.chars()).enumerate() {
+        let digit = c.to_digit(16)? as u64;
+        let in_frac = i >= int.len();
+        if sig < 1 << 56 {
+            sig = sig * 16 + digit;
+            if in_frac {
+                exp = exp.saturating_sub(4);
+            }
+        } else {
+            sticky |= digit != 0;
+            if !in_frac {
+                exp = exp.saturating_add(4);
+            }
+        }
+    }
+    if sig == 0 {
+        return Some(0);
+    }
+    let bias = (1i64 << (exp_bits - 1)) - 1;
+    let (emin, emax) = (1 - bias, bias);
+    let inf = ((1u64 << exp_bits) - 1) << mantissa_bits;
+    // 最上位ビットの指数。非正規化数では emin の位置に揃える
+    let top = (63 - sig.leading_zeros() as i64).saturating_add(exp);
+    if top > emax {
+        return Some(inf);
+    }
+    let mut e = top.max(emin);
+    // 捨てるビット数。桁を捨てた時は sig が 56 ビット以上あるので、必ず 1 以上になる
+    let shift = (e - mantissa_bits as i64).saturating_sub(exp);
+    let mut q = if shift <= 0 {
+        sig << -shift
+    } else if shift >= 64 {
+        0
+    } else {
+        let q = sig >> shift;
+        let rest = sig & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        if rest > half || (rest == half && (sticky || q & 1 == 1)) {
+            q + 1
+        } else {
+            q
+        }
+    };
+    if q >> (mantissa_bits + 1) != 0 {
+        q >>= 1;
+        e += 1;
+    }
+    if e > emax {
+        return Some(inf);
+    }
+    let implicit = 1u64 << mantissa_bits;
+    Some(if q >= implicit {
+        ((e + bias) as u64) << mantissa_bits | (q - implicit)
+    } else {
+        q
+    })
+}
+
+// p の後の 10 進数の指数。大きすぎる値は飽和させる
+fn parse_exponent(s: &str) -> Option<i64> {
+    let (negative, digits) = split_sign(s);
+    if digits.is_empty() {
+        return None;
+    }
+    let mut exp = 0i64;
+    for c in digits.chars() {
+        let digit = c.to_digit(10)? as i64;
+        exp = exp.saturating_mul(10).saturating_add(digit);
+    }
+    Some(if negative { -exp } else { exp })
+}
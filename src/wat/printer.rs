@@ -0,0 +1,1180 @@
+// module を WAT のテキストにする
+//
+// 命令は一度バイナリ形式に書き戻し、opcode の表から名前と即値の種類を引いて出力する
+use std::collections::{HashMap, HashSet};
+
+use super::lexer::is_idchar;
+use super::opcode::{self, Immediate};
+use super::{Style, MAX_NESTING};
+use crate::ast::instruction::{BlockType, ControlInstruction, Expression, Instruction};
+use crate::ast::module::Module;
+use crate::ast::name::{IndirectNameMap, NameMap};
+use crate::ast::section::{
+    DataMode, ElementInit, ElementMode, ExportDesc, ImportDesc, SectionData,
+};
+use crate::ast::wasm_type::{
+    FunctionType, GlobalType, Limits, Mutability, NumberType, ReferenceType, TableType, ValueType,
+    VectorType,
+};
+use crate::ast::Error;
+use crate::decode;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const INDENT: &str = "  ";
+// flat 形式の block の入れ子には上限がないので、出力が大きくなりすぎないよう字下げはここまでにする
+const MAX_INDENT: usize = 50;
+
+// name section の名前のうち、$name として書けるもの
+#[derive(Default)]
+struct Names {
+    funcs: HashMap<u32, String>,
+    locals: HashMap<u32, HashMap<u32, String>>,
+    labels: HashMap<u32, HashMap<u32, String>>,
+    types: HashMap<u32, String>,
+    tables: HashMap<u32, String>,
+    memories: HashMap<u32, String>,
+    globals: HashMap<u32, String>,
+    elems: HashMap<u32, String>,
+    data: HashMap<u32, String>,
+}
+
+impl Names {
+    fn new(module: &Module) -> Self {
+        // 壊れた name section は無視して index で書く
+        let section = match module.name_section() {
+            Some(Ok(section)) => section,
+            _ => return Self::default(),
+        };
+        let indirect = |map: &IndirectNameMap| {
+            map.iter()
+                .map(|(index, names)| (index, ids(names)))
+                .collect()
+        };
+        Self {
+            funcs: ids(section.funcs()),
+            locals: indirect(section.locals()),
+            labels: indirect(section.labels()),
+            types: ids(section.types()),
+            tables: ids(section.tables()),
+            memories: ids(section.memories()),
+            globals: ids(section.globals()),
+            elems: ids(section.elems()),
+            data: ids(section.data()),
+        }
+    }
+}
+
+// $name として書ける名前だけを残す。重複した名前は index で書く
+fn ids(names: &NameMap) -> HashMap<u32, String> {
+    let mut used = HashSet::new();
+    names
+        .iter()
+        .filter(|(_, name)| !name.is_empty() && name.iter().all(|&by| is_idchar(by)))
+        .filter(|(_, name)| used.insert(*name))
+        .map(|(index, name)| (index, format!("${}", String::from_utf8_lossy(name))))
+        .collect()
+}
+
+fn id_or_index(ids: &HashMap<u32, String>, index: u32) -> String {
+    match ids.get(&index) {
+        Some(id) => id.clone(),
+        None => index.to_string(),
+    }
+}
+
+// 定義する側は名前がなければ index をコメントで書く
+fn definition(ids: &HashMap<u32, String>, index: u32) -> String {
+    match ids.get(&index) {
+        Some(id) => format!(" {}", id),
+        None => format!(" (;{};)", index),
+    }
+}
+
+struct Label {
+    // br で値をいくつ渡すか。block と if は結果、loop は引数の数
+    arity: usize,
+    id: Option<String>,
+}
+
+// 関数本体の中で使う情報
+#[derive(Default)]
+struct FuncContext<'n> {
+    locals: Option<&'n HashMap<u32, String>>,
+    label_names: Option<&'n HashMap<u32, String>>,
+    // label の index は関数の中で block/loop/if が現れた順に数える
+    label_count: u32,
+    results: usize,
+    labels: Vec<Label>,
+}
+
+impl FuncContext<'_> {
+    // block/loop/if に入る。label に名前があれば " $name" を返す
+    fn enter(&mut self, arity: usize) -> String {
+        let id = self
+            .label_names
+            .and_then(|names| names.get(&self.label_count))
+            .cloned();
+        self.label_count += 1;
+        let text = id.as_ref().map_or(String::new(), |id| format!(" {}", id));
+        self.labels.push(Label { arity, id });
+        text
+    }
+
+    fn exit(&mut self) {
+        self.labels.pop();
+    }
+
+    // 参照する label の書き方と arity
+    // 内側に同じ名前の label があると隠れてしまうので、その場合は深さで書く
+    fn label(&self, depth: u32) -> (String, usize) {
+        let i = match self.labels.len().checked_sub(depth as usize + 1) {
+            Some(i) => i,
+            None => return (depth.to_string(), 0),
+        };
+        let label = &self.labels[i];
+        let text = match &label.id {
+            Some(id)
+                if self.labels[i + 1..]
+                    .iter()
+                    .all(|l| l.id.as_ref() != Some(id)) =>
+            {
+                id.clone()
+            }
+            _ => depth.to_string(),
+        };
+        (text, label.arity)
+    }
+
+    fn local(&self, index: u32) -> String {
+        self.locals
+            .map_or_else(|| index.to_string(), |locals| id_or_index(locals, index))
+    }
+}
+
+// flat 形式で書くときに、後で書く残りの命令
+enum Flat<'i> {
+    Instrs(&'i [Instruction], usize),
+    Else(usize),
+    End(usize),
+}
+
+fn indent(depth: usize) -> String {
+    INDENT.repeat(depth.min(MAX_INDENT))
+}
+
+// folded 形式の命令。operands は命令の前に実行される
+struct Node {
+    head: String,
+    // S 式にすると深くなりすぎる block は flat 形式の行で書く
+    flat: Vec<String>,
+    operands: Vec<Node>,
+    // block/loop の本体は keyword なし、if は then と else
+    bodies: Vec<(&'static str, Vec<Node>)>,
+    // 自分を含めた S 式の入れ子の深さ
+    height: usize,
+}
+
+impl Node {
+    fn new(head: String) -> Self {
+        Self {
+            head,
+            flat: Vec::new(),
+            operands: Vec::new(),
+            bodies: Vec::new(),
+            height: 1,
+        }
+    }
+
+    // block の型は (result ...) のように S 式で書くので、高さは 1 とする
+    fn flat(lines: Vec<String>) -> Self {
+        Self {
+            flat: lines,
+            ..Self::new(String::new())
+        }
+    }
+
+    fn push_body(&mut self, keyword: &'static str, body: Vec<Node>) {
+        let inner = body.iter().map(|node| node.height).max().unwrap_or(0);
+        let inner = if keyword.is_empty() { inner } else { inner + 1 };
+        self.height = self.height.max(inner + 1);
+        self.bodies.push((keyword, body));
+    }
+
+    fn render(&self, depth: usize, lines: &mut Vec<String>) {
+        if !self.flat.is_empty() {
+            let indent = INDENT.repeat(depth);
+            lines.extend(self.flat.iter().map(|line| format!("{}{}", indent, line)));
+            return;
+        }
+        lines.push(format!("{}({}", INDENT.repeat(depth), self.head));
+        for operand in &self.operands {
+            operand.render(depth + 1, lines);
+        }
+        for (keyword, body) in &self.bodies {
+            let depth = if keyword.is_empty() {
+                depth + 1
+            } else {
+                lines.push(format!("{}({}", INDENT.repeat(depth + 1), keyword));
+                depth + 2
+            };
+            for node in body {
+                node.render(depth, lines);
+            }
+            if !keyword.is_empty() {
+                close(lines);
+            }
+        }
+        close(lines);
+    }
+
+    // 定数式のように 1 行で書く
+    fn render_inline(&self) -> String {
+        let mut text = format!("({}", self.head);
+        for operand in &self.operands {
+            text.push(' ');
+            text.push_str(&operand.render_inline());
+        }
+        for (keyword, body) in &self.bodies {
+            let body: Vec<String> = body.iter().map(Node::render_inline).collect();
+            match keyword.is_empty() {
+                true => text.push_str(&format!(" {}", body.join(" "))),
+                false => text.push_str(&format!(" ({} {})", keyword, body.join(" "))),
+            }
+        }
+        text.push(')');
+        text
+    }
+}
+
+fn close(lines: &mut [String]) {
+    lines.last_mut().unwrap().push(')');
+}
+
+pub(super) struct Printer<'m> {
+    module: &'m Module,
+    style: Style,
+    names: Names,
+    types: Vec<&'m FunctionType>,
+    // import も含めた関数ごとの型 index
+    func_types: Vec<u32>,
+}
+
+impl<'m> Printer<'m> {
+    pub(super) fn new(module: &'m Module, style: Style) -> Self {
+        let mut types = Vec::new();
+        let mut func_types = Vec::new();
+        for section in module.sections() {
+            match section.payload_data() {
+                SectionData::Type(section) => types.extend(section.funcs()),
+                SectionData::Import(section) => {
+                    for import in section.imports() {
+                        if let ImportDesc::TypeIndex(index) = import.desc() {
+                            func_types.push(*index);
+                        }
+                    }
+                }
+                SectionData::Function(section) => func_types.extend(section.indexies()),
+                _ => {}
+            }
+        }
+        Self {
+            module,
+            style,
+            names: Names::new(module),
+            types,
+            func_types,
+        }
+    }
+
+    pub(super) fn print(&self) -> Result<String> {
+        let mut lines = vec!["(module".to_string()];
+        let (mut funcs, mut tables, mut memories, mut globals) = (0, 0, 0, 0);
+        let mut defined_funcs = 0;
+        for section in self.module.sections() {
+            match section.payload_data() {
+                SectionData::Type(section) => {
+                    for (index, func_type) in section.funcs().iter().enumerate() {
+                        lines.push(format!(
+                            "{}(type{} (func{}))",
+                            INDENT,
+                            definition(&self.names.types, index as u32),
+                            signature(func_type, None)
+                        ));
+                    }
+                }
+                SectionData::Import(section) => {
+                    for import in section.imports() {
+                        let desc = match import.desc() {
+                            ImportDesc::TypeIndex(index) => {
+                                funcs += 1;
+                                format!(
+                                    "(func{}{})",
+                                    definition(&self.names.funcs, funcs - 1),
+                                    self.type_use(*index, None)
+                                )
+                            }
+                            ImportDesc::Table(table_type) => {
+                                tables += 1;
+                                format!(
+                                    "(table{} {})",
+                                    definition(&self.names.tables, tables - 1),
+                                    table(table_type)
+                                )
+                            }
+                            ImportDesc::Memory(memory_type) => {
+                                memories += 1;
+                                format!(
+                                    "(memory{} {})",
+                                    definition(&self.names.memories, memories - 1),
+                                    limits(memory_type.limits())
+                                )
+                            }
+                            ImportDesc::Global(global_type) => {
+                                globals += 1;
+                                format!(
+                                    "(global{} {})",
+                                    definition(&self.names.globals, globals - 1),
+                                    global(global_type)
+                                )
+                            }
+                        };
+                        lines.push(format!(
+                            "{}(import {} {} {})",
+                            INDENT,
+                            string(import.module()),
+                            string(import.name()),
+                            desc
+                        ));
+                    }
+                }
+                SectionData::Table(section) => {
+                    for table_type in section.tables() {
+                        lines.push(format!(
+                            "{}(table{} {})",
+                            INDENT,
+                            definition(&self.names.tables, tables),
+                            table(table_type)
+                        ));
+                        tables += 1;
+                    }
+                }
+                SectionData::Memory(section) => {
+                    for memory_type in section.memories() {
+                        lines.push(format!(
+                            "{}(memory{} {})",
+                            INDENT,
+                            definition(&self.names.memories, memories),
+                            limits(memory_type.limits())
+                        ));
+                        memories += 1;
+                    }
+                }
+                SectionData::Global(section) => {
+                    for g in section.globals() {
+                        lines.push(format!(
+                            "{}(global{} {} {})",
+                            INDENT,
+                            definition(&self.names.globals, globals),
+                            global(g.global_type()),
+                            self.const_expr(g.init())?
+                        ));
+                        globals += 1;
+                    }
+                }
+                SectionData::Export(section) => {
+                    for export in section.exports() {
+                        let desc = match export.desc() {
+                            ExportDesc::FuncIndex(index) => format!("func {}", self.func(*index)),
+                            ExportDesc::TableIndex(index) => {
+                                format!("table {}", id_or_index(&self.names.tables, *index))
+                            }
+                            ExportDesc::MemIndex(index) => {
+                                format!("memory {}", id_or_index(&self.names.memories, *index))
+                            }
+                            ExportDesc::GlobalIndex(index) => {
+                                format!("global {}", id_or_index(&self.names.globals, *index))
+                            }
+                        };
+                        lines.push(format!(
+                            "{}(export {} ({}))",
+                            INDENT,
+                            string(export.name()),
+                            desc
+                        ));
+                    }
+                }
+                SectionData::Start(section) => {
+                    lines.push(format!(
+                        "{}(start {})",
+                        INDENT,
+                        self.func(section.func_index())
+                    ));
+                }
+                SectionData::Element(section) => {
+                    for (index, element) in section.elements().iter().enumerate() {
+                        let mut text = format!(
+                            "{}(elem{}",
+                            INDENT,
+                            definition(&self.names.elems, index as u32)
+                        );
+                        match element.mode() {
+                            ElementMode::Passive => {}
+                            ElementMode::Declarative => text.push_str(" declare"),
+                            ElementMode::Active { table, offset } => {
+                                if *table != 0 {
+                                    let table = id_or_index(&self.names.tables, *table);
+                                    text.push_str(&format!(" (table {})", table));
+                                }
+                                text.push_str(&format!(
+                                    " {}",
+                                    self.wrapped_expr("offset", offset)?
+                                ));
+                            }
+                        }
+                        match element.init() {
+                            ElementInit::FuncIndexies(indexies) => {
+                                text.push_str(" func");
+                                for index in indexies {
+                                    text.push_str(&format!(" {}", self.func(*index)));
+                                }
+                            }
+                            ElementInit::Expressions(exprs) => {
+                                text.push_str(&format!(
+                                    " {}",
+                                    reference_type(element.element_type())
+                                ));
+                                for expr in exprs {
+                                    text.push_str(&format!(
+                                        " {}",
+                                        self.wrapped_expr("item", expr)?
+                                    ));
+                                }
+                            }
+                        }
+                        text.push(')');
+                        lines.push(text);
+                    }
+                }
+                SectionData::Code(section) => {
+                    for code in section.codes() {
+                        let index = self.func_types.len() - section.codes().len() + defined_funcs;
+                        self.code(index as u32, code.locals()?, code.expression()?, &mut lines)?;
+                        defined_funcs += 1;
+                    }
+                }
+                SectionData::Data(section) => {
+                    for (index, data) in section.data().iter().enumerate() {
+                        let mut text = format!(
+                            "{}(data{}",
+                            INDENT,
+                            definition(&self.names.data, index as u32)
+                        );
+                        if let DataMode::Active { memory, offset, .. } = data.mode() {
+                            if *memory != 0 {
+                                let memory = id_or_index(&self.names.memories, *memory);
+                                text.push_str(&format!(" (memory {})", memory));
+                            }
+                            text.push_str(&format!(" {}", self.wrapped_expr("offset", offset)?));
+                        }
+                        text.push_str(&format!(" {})", string(data.init())));
+                        lines.push(text);
+                    }
+                }
+                // 関数の宣言は code と一緒に書き、data count は命令から分かる
+                // custom section はテキスト形式では表せない
+                SectionData::Function(_) | SectionData::DataCount(_) | SectionData::Custom(_) => {}
+            }
+        }
+        close(&mut lines);
+        let mut text = lines.join("\n");
+        text.push('\n');
+        Ok(text)
+    }
+
+    fn func(&self, index: u32) -> String {
+        id_or_index(&self.names.funcs, index)
+    }
+
+    fn func_type(&self, type_index: u32) -> Option<&FunctionType> {
+        self.types.get(type_index as usize).copied()
+    }
+
+    // (type N) に加えて、読みやすさのため引数と結果も書く
+    fn type_use(&self, type_index: u32, locals: Option<&HashMap<u32, String>>) -> String {
+        let type_name = id_or_index(&self.names.types, type_index);
+        match self.func_type(type_index) {
+            Some(func_type) => format!(" (type {}){}", type_name, signature(func_type, locals)),
+            None => format!(" (type {})", type_name),
+        }
+    }
+
+    fn code(
+        &self,
+        index: u32,
+        locals: &[ValueType],
+        expression: &Expression,
+        lines: &mut Vec<String>,
+    ) -> Result<()> {
+        let type_index = self.func_types[index as usize];
+        let local_names = self.names.locals.get(&index);
+        lines.push(format!(
+            "{}(func{}{}",
+            INDENT,
+            definition(&self.names.funcs, index),
+            self.type_use(type_index, local_names)
+        ));
+        let func_type = self.func_type(type_index);
+        let params = func_type.map_or(0, |t| t.params_types().valu_types().len());
+        if !locals.is_empty() {
+            let text = typed_list("local", locals, params as u32, local_names);
+            lines.push(format!("{}{}", INDENT.repeat(2), text.join(" ")));
+        }
+        let mut ctx = FuncContext {
+            locals: local_names,
+            label_names: self.names.labels.get(&index),
+            results: func_type.map_or(0, |t| t.return_types().valu_types().len()),
+            ..Default::default()
+        };
+        match self.style {
+            Style::Flat => self.flat(expression.instrs(), &mut ctx, 2, lines)?,
+            Style::Folded => {
+                for node in self.fold(expression.instrs(), &mut ctx, 2)? {
+                    node.render(2, lines);
+                }
+            }
+        }
+        close(lines);
+        Ok(())
+    }
+
+    // block の入れ子に上限はないので、再帰せずに残りの命令をスタックに積んで書く
+    fn flat(
+        &self,
+        instrs: &[Instruction],
+        ctx: &mut FuncContext<'_>,
+        depth: usize,
+        lines: &mut Vec<String>,
+    ) -> Result<()> {
+        let mut work = vec![Flat::Instrs(instrs, depth)];
+        while let Some(item) = work.pop() {
+            let (instrs, depth) = match item {
+                Flat::Instrs(instrs, depth) => (instrs, depth),
+                Flat::Else(depth) => {
+                    lines.push(format!("{}else", indent(depth)));
+                    continue;
+                }
+                Flat::End(depth) => {
+                    ctx.exit();
+                    lines.push(format!("{}end", indent(depth)));
+                    continue;
+                }
+            };
+            for (i, instr) in instrs.iter().enumerate() {
+                let (head, block_type, label, bodies) = match instr {
+                    Instruction::Control(ControlInstruction::Block { block_type, instrs }) => {
+                        let (_, results) = self.block_arity(block_type);
+                        ("block", block_type, results, vec![instrs])
+                    }
+                    Instruction::Control(ControlInstruction::Loop { block_type, instrs }) => {
+                        let (params, _) = self.block_arity(block_type);
+                        ("loop", block_type, params, vec![instrs])
+                    }
+                    Instruction::Control(ControlInstruction::IfElse {
+                        block_type,
+                        then_instrs,
+                        else_instrs,
+                    }) => {
+                        let (_, results) = self.block_arity(block_type);
+                        let mut bodies = vec![then_instrs];
+                        bodies.extend(else_instrs);
+                        ("if", block_type, results, bodies)
+                    }
+                    instr => {
+                        lines.push(format!("{}{}", indent(depth), self.plain(instr, ctx)?.0));
+                        continue;
+                    }
+                };
+                let id = ctx.enter(label);
+                lines.push(format!(
+                    "{}{}{}{}",
+                    indent(depth),
+                    head,
+                    id,
+                    self.block_type(block_type)
+                ));
+                work.push(Flat::Instrs(&instrs[i + 1..], depth));
+                work.push(Flat::End(depth));
+                for (j, body) in bodies.into_iter().enumerate().rev() {
+                    work.push(Flat::Instrs(body, depth + 1));
+                    if j > 0 {
+                        work.push(Flat::Else(depth));
+                    }
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // 直前の命令が 1 つずつ値を積んでいれば、それを operand として入れ子にする
+    // 命令の順番は変えないので、入れ子にできない値はそのまま前に並べる
+    // depth は instrs を並べるリストの深さで、入れ子にすると上限を超える operand も前に並べる
+    // 本体が上限を超える block は flat 形式で書く
+    fn fold(
+        &self,
+        instrs: &[Instruction],
+        ctx: &mut FuncContext<'_>,
+        depth: usize,
+    ) -> Result<Vec<Node>> {
+        let mut out = Vec::new();
+        let mut stack: Vec<Node> = Vec::new();
+        for instr in instrs {
+            let (mut node, pops, pushes) = match instr {
+                // (if (then ...)) の本体が一番深い
+                Instruction::Control(
+                    ControlInstruction::Block { .. }
+                    | ControlInstruction::Loop { .. }
+                    | ControlInstruction::IfElse { .. },
+                ) if depth + 3 > MAX_NESTING => {
+                    let mut lines = Vec::new();
+                    self.flat(std::slice::from_ref(instr), ctx, 0, &mut lines)?;
+                    // 値は operand にせず、前後の命令と同じ順で並べる
+                    (Node::flat(lines), 0, 0)
+                }
+                Instruction::Control(ControlInstruction::Block { block_type, instrs }) => {
+                    let (_, results) = self.block_arity(block_type);
+                    let node = self.fold_block("block", block_type, results, instrs, ctx, depth)?;
+                    (node, 0, results)
+                }
+                Instruction::Control(ControlInstruction::Loop { block_type, instrs }) => {
+                    let (params, results) = self.block_arity(block_type);
+                    let node = self.fold_block("loop", block_type, params, instrs, ctx, depth)?;
+                    (node, 0, results)
+                }
+                Instruction::Control(ControlInstruction::IfElse {
+                    block_type,
+                    then_instrs,
+                    else_instrs,
+                }) => {
+                    let (params, results) = self.block_arity(block_type);
+                    let id = ctx.enter(results);
+                    let mut node = Node::new(format!("if{}{}", id, self.block_type(block_type)));
+                    node.push_body("then", self.fold(then_instrs, ctx, depth + 2)?);
+                    if let Some(else_instrs) = else_instrs {
+                        node.push_body("else", self.fold(else_instrs, ctx, depth + 2)?);
+                    }
+                    ctx.exit();
+                    (node, params + 1, results)
+                }
+                instr => {
+                    let (text, pops, pushes) = self.plain(instr, ctx)?;
+                    (Node::new(text), pops, pushes)
+                }
+            };
+            if pops > 0 {
+                let operands = stack.len().checked_sub(pops).map(|start| &stack[start..]);
+                let height = operands
+                    .and_then(|operands| operands.iter().map(|operand| operand.height).max())
+                    .map_or(0, |height| height + 1);
+                if operands.is_some() && depth + height <= MAX_NESTING {
+                    node.operands = stack.split_off(stack.len() - pops);
+                    node.height = node.height.max(height);
+                } else {
+                    out.append(&mut stack);
+                }
+            }
+            if pushes == 1 {
+                stack.push(node);
+            } else {
+                out.append(&mut stack);
+                out.push(node);
+            }
+        }
+        out.append(&mut stack);
+        Ok(out)
+    }
+
+    fn fold_block(
+        &self,
+        head: &str,
+        block_type: &BlockType,
+        label: usize,
+        instrs: &[Instruction],
+        ctx: &mut FuncContext<'_>,
+        depth: usize,
+    ) -> Result<Node> {
+        let id = ctx.enter(label);
+        let mut node = Node::new(format!("{}{}{}", head, id, self.block_type(block_type)));
+        node.push_body("", self.fold(instrs, ctx, depth + 1)?);
+        ctx.exit();
+        Ok(node)
+    }
+
+    fn block_type(&self, block_type: &BlockType) -> String {
+        match block_type {
+            BlockType::Empty => String::new(),
+            BlockType::Value(t) => format!(" (result {})", value_type(t)),
+            BlockType::TypeIndex(index) => self.type_use(*index, None),
+        }
+    }
+
+    // (引数の数, 結果の数)
+    fn block_arity(&self, block_type: &BlockType) -> (usize, usize) {
+        match block_type {
+            BlockType::Empty => (0, 0),
+            BlockType::Value(_) => (0, 1),
+            BlockType::TypeIndex(index) => self.type_arity(*index),
+        }
+    }
+
+    fn type_arity(&self, type_index: u32) -> (usize, usize) {
+        self.func_type(type_index).map_or((0, 0), |t| {
+            (
+                t.params_types().valu_types().len(),
+                t.return_types().valu_types().len(),
+            )
+        })
+    }
+
+    // 定数式は 1 行で書く
+    fn const_expr(&self, expr: &Expression) -> Result<String> {
+        let mut ctx = FuncContext {
+            results: 1,
+            ..Default::default()
+        };
+        let texts: Vec<String> = match self.style {
+            Style::Flat => expr
+                .instrs()
+                .iter()
+                .map(|instr| Ok(self.plain(instr, &ctx)?.0))
+                .collect::<Result<_>>()?,
+            // 一番深いのは (module (elem (item ...))) の中
+            Style::Folded => self
+                .fold(expr.instrs(), &mut ctx, 3)?
+                .iter()
+                .map(Node::render_inline)
+                .collect(),
+        };
+        Ok(texts.join(" "))
+    }
+
+    // folded 形式で 1 つの命令にまとまる場合は keyword を省略する
+    fn wrapped_expr(&self, keyword: &str, expr: &Expression) -> Result<String> {
+        let text = self.const_expr(expr)?;
+        if self.style == Style::Folded && expr.instrs().len() == 1 {
+            return Ok(text);
+        }
+        Ok(format!("({} {})", keyword, text))
+    }
+
+    // block/loop/if 以外の命令と、(pop する値の数, push する値の数)
+    fn plain(&self, instr: &Instruction, ctx: &FuncContext<'_>) -> Result<(String, usize, usize)> {
+        let mut buf = Vec::new();
+        instr.encode(&mut buf);
+        let data = &mut &buf[..];
+        let [code] = decode::decode_8bit(data)?;
+        if code == 0x1C {
+            // 型を書いた select は表にない
+            let count = decode::decode_varint_u32(data)?;
+            let types = data[..count as usize]
+                .iter()
+                .map(|&by| value_type_byte(by))
+                .collect::<Vec<_>>();
+            return Ok((format!("select (result {})", types.join(" ")), 3, 1));
+        }
+        let sub = match code {
+            0xFC | 0xFD => Some(decode::decode_varint_u32(data)?),
+            _ => None,
+        };
+        let op = opcode::find_by_code(code, sub).expect("encoded instructions are in the table");
+        let name = op.name;
+        let mut text = name.to_string();
+        let (pops, pushes) = match op.immediate {
+            Immediate::None => match name {
+                "unreachable" | "nop" => (0, 0),
+                "return" => (ctx.results, 0),
+                "drop" => (1, 0),
+                _ => operator_arity(name),
+            },
+            Immediate::Block | Immediate::Select => (3, 1),
+            Immediate::Label => {
+                let (l, arity) = ctx.label(decode::decode_varint_u32(data)?);
+                text.push_str(&format!(" {}", l));
+                match name {
+                    "br" => (arity, 0),
+                    _ => (arity + 1, arity),
+                }
+            }
+            Immediate::BrTable => {
+                // 最後が default の label
+                let count = decode::decode_varint_u32(data)?;
+                let mut arity = 0;
+                for _ in 0..=count {
+                    let (l, a) = ctx.label(decode::decode_varint_u32(data)?);
+                    text.push_str(&format!(" {}", l));
+                    arity = a;
+                }
+                (arity + 1, 0)
+            }
+            Immediate::Func => {
+                let index = decode::decode_varint_u32(data)?;
+                text.push_str(&format!(" {}", self.func(index)));
+                match name {
+                    "call" => {
+                        let type_index = self.func_types.get(index as usize).copied();
+                        type_index.map_or((0, 0), |t| self.type_arity(t))
+                    }
+                    _ => (0, 1),
+                }
+            }
+            Immediate::CallIndirect => {
+                let type_index = decode::decode_varint_u32(data)?;
+                let table = decode::decode_varint_u32(data)?;
+                if table != 0 {
+                    text.push_str(&format!(" {}", id_or_index(&self.names.tables, table)));
+                }
+                let type_name = id_or_index(&self.names.types, type_index);
+                text.push_str(&format!(" (type {})", type_name));
+                let (params, results) = self.type_arity(type_index);
+                (params + 1, results)
+            }
+            Immediate::Local => {
+                let index = decode::decode_varint_u32(data)?;
+                text.push_str(&format!(" {}", ctx.local(index)));
+                match name {
+                    "local.get" => (0, 1),
+                    "local.set" => (1, 0),
+                    _ => (1, 1),
+                }
+            }
+            Immediate::Global => {
+                let index = decode::decode_varint_u32(data)?;
+                text.push_str(&format!(" {}", id_or_index(&self.names.globals, index)));
+                match name {
+                    "global.get" => (0, 1),
+                    _ => (1, 0),
+                }
+            }
+            Immediate::Table => {
+                let index = decode::decode_varint_u32(data)?;
+                text.push_str(&format!(" {}", id_or_index(&self.names.tables, index)));
+                match name {
+                    "table.get" => (1, 1),
+                    "table.set" => (2, 0),
+                    "table.size" => (0, 1),
+                    "table.grow" => (2, 1),
+                    _ => (3, 0),
+                }
+            }
+            Immediate::TableInit => {
+                let elem = decode::decode_varint_u32(data)?;
+                let table = decode::decode_varint_u32(data)?;
+                let table = id_or_index(&self.names.tables, table);
+                let elem = id_or_index(&self.names.elems, elem);
+                text.push_str(&format!(" {} {}", table, elem));
+                (3, 0)
+            }
+            Immediate::TableCopy => {
+                let dst = decode::decode_varint_u32(data)?;
+                let src = decode::decode_varint_u32(data)?;
+                let dst = id_or_index(&self.names.tables, dst);
+                let src = id_or_index(&self.names.tables, src);
+                text.push_str(&format!(" {} {}", dst, src));
+                (3, 0)
+            }
+            Immediate::MemoryInit => {
+                // memory index は 0 のみなので data index だけを書く
+                let index = decode::decode_varint_u32(data)?;
+                text.push_str(&format!(" {}", id_or_index(&self.names.data, index)));
+                (3, 0)
+            }
+            Immediate::Elem => {
+                let index = decode::decode_varint_u32(data)?;
+                text.push_str(&format!(" {}", id_or_index(&self.names.elems, index)));
+                (0, 0)
+            }
+            Immediate::Data => {
+                let index = decode::decode_varint_u32(data)?;
+                text.push_str(&format!(" {}", id_or_index(&self.names.data, index)));
+                (0, 0)
+            }
+            Immediate::MemArg(natural) => {
+                text.push_str(&memarg(data, natural)?);
+                match name.contains("store") {
+                    true => (2, 0),
+                    false => (1, 1),
+                }
+            }
+            Immediate::MemArgLane(natural) => {
+                text.push_str(&memarg(data, natural)?);
+                text.push_str(&format!(" {}", decode::decode_8bit(data)?[0]));
+                match name.contains("store") {
+                    true => (2, 0),
+                    false => (2, 1),
+                }
+            }
+            Immediate::Memory | Immediate::MemoryCopy => {
+                // memory index は予約バイトなので書かない
+                match name {
+                    "memory.size" => (0, 1),
+                    "memory.grow" => (1, 1),
+                    _ => (3, 0),
+                }
+            }
+            Immediate::I32 => {
+                text.push_str(&format!(" {}", decode::decode_varint_s32(data)?));
+                (0, 1)
+            }
+            Immediate::I64 => {
+                text.push_str(&format!(" {}", decode::decode_varint_s64(data)?));
+                (0, 1)
+            }
+            Immediate::F32 => {
+                let bits = u32::from_le_bytes(decode::decode_32bit(data)?);
+                text.push_str(&format!(" {}", f32_literal(bits)));
+                (0, 1)
+            }
+            Immediate::F64 => {
+                let bits = u64::from_le_bytes(decode::decode_64bit(data)?);
+                text.push_str(&format!(" {}", f64_literal(bits)));
+                (0, 1)
+            }
+            Immediate::V128 => {
+                let bytes = decode::decode_128bit(data)?;
+                text.push_str(" i32x4");
+                for lane in bytes.chunks(4) {
+                    let lane = u32::from_le_bytes([lane[0], lane[1], lane[2], lane[3]]);
+                    text.push_str(&format!(" 0x{:08x}", lane));
+                }
+                (0, 1)
+            }
+            Immediate::Shuffle => {
+                for lane in decode::decode_128bit(data)? {
+                    text.push_str(&format!(" {}", lane));
+                }
+                (2, 1)
+            }
+            Immediate::Lane => {
+                text.push_str(&format!(" {}", decode::decode_8bit(data)?[0]));
+                match name.contains("replace_lane") {
+                    true => (2, 1),
+                    false => (1, 1),
+                }
+            }
+            Immediate::RefNull => {
+                let [by] = decode::decode_8bit(data)?;
+                text.push_str(if by == 0x6f { " extern" } else { " func" });
+                (0, 1)
+            }
+        };
+        Ok((text, pops, pushes))
+    }
+}
+
+// 2 つの値を取る演算子。これと bitselect 以外の数値演算は 1 つの値を取る
+const BINARY_OPERATORS: &[&str] = &[
+    "add",
+    "sub",
+    "mul",
+    "div",
+    "div_s",
+    "div_u",
+    "rem_s",
+    "rem_u",
+    "and",
+    "andnot",
+    "or",
+    "xor",
+    "shl",
+    "shr_s",
+    "shr_u",
+    "rotl",
+    "rotr",
+    "min",
+    "max",
+    "min_s",
+    "min_u",
+    "max_s",
+    "max_u",
+    "pmin",
+    "pmax",
+    "copysign",
+    "eq",
+    "ne",
+    "lt",
+    "lt_s",
+    "lt_u",
+    "gt",
+    "gt_s",
+    "gt_u",
+    "le",
+    "le_s",
+    "le_u",
+    "ge",
+    "ge_s",
+    "ge_u",
+    "add_sat_s",
+    "add_sat_u",
+    "sub_sat_s",
+    "sub_sat_u",
+    "avgr_u",
+    "q15mulr_sat_s",
+    "swizzle",
+];
+
+fn operator_arity(name: &str) -> (usize, usize) {
+    let operator = name.split_once('.').map_or(name, |(_, op)| op);
+    if operator == "bitselect" {
+        (3, 1)
+    } else if BINARY_OPERATORS.contains(&operator)
+        || ["narrow_", "extmul_", "dot_"]
+            .iter()
+            .any(|prefix| operator.starts_with(prefix))
+    {
+        (2, 1)
+    } else {
+        (1, 1)
+    }
+}
+
+fn memarg(data: &mut &[u8], natural: u32) -> Result<String> {
+    let align = decode::decode_varint_u32(data)?;
+    let offset = decode::decode_varint_u32(data)?;
+    let mut text = String::new();
+    if offset != 0 {
+        text.push_str(&format!(" offset={}", offset));
+    }
+    if align != natural {
+        text.push_str(&format!(" align={}", 1u64 << align.min(63)));
+    }
+    Ok(text)
+}
+
+fn f32_literal(bits: u32) -> String {
+    let sign = if bits >> 31 == 1 { "-" } else { "" };
+    let value = f32::from_bits(bits);
+    match bits & 0x7fff_ffff {
+        0x7fc0_0000 => format!("{}nan", sign),
+        _ if value.is_nan() => format!("{}nan:0x{:x}", sign, bits & 0x7f_ffff),
+        _ if value.is_infinite() => format!("{}inf", sign),
+        // Debug は読み戻すと同じ値になる最短の表記
+        _ => format!("{:?}", value),
+    }
+}
+
+fn f64_literal(bits: u64) -> String {
+    let sign = if bits >> 63 == 1 { "-" } else { "" };
+    let value = f64::from_bits(bits);
+    match bits & 0x7fff_ffff_ffff_ffff {
+        0x7ff8_0000_0000_0000 => format!("{}nan", sign),
+        _ if value.is_nan() => format!("{}nan:0x{:x}", sign, bits & 0xf_ffff_ffff_ffff),
+        _ if value.is_infinite() => format!("{}inf", sign),
+        _ => format!("{:?}", value),
+    }
+}
+
+// 印字できない文字と " \ はエスケープする
+fn string(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+    for &by in bytes {
+        match by {
+            b'"' | b'\\' => text.push_str(&format!("\\{}", by as char)),
+            0x20..=0x7e => text.push(by as char),
+            _ => text.push_str(&format!("\\{:02x}", by)),
+        }
+    }
+    text.push('"');
+    text
+}
+
+// (param ...) (result ...)
+fn signature(func_type: &FunctionType, names: Option<&HashMap<u32, String>>) -> String {
+    let mut parts = typed_list("param", func_type.params_types().valu_types(), 0, names);
+    let results = func_type.return_types().valu_types();
+    if !results.is_empty() {
+        let results: Vec<&str> = results.iter().map(value_type).collect();
+        parts.push(format!("(result {})", results.join(" ")));
+    }
+    parts.iter().map(|p| format!(" {}", p)).collect()
+}
+
+// 名前のある param/local は 1 つずつ、名前のないものはまとめて書く
+fn typed_list(
+    keyword: &str,
+    types: &[ValueType],
+    start: u32,
+    names: Option<&HashMap<u32, String>>,
+) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut unnamed: Vec<&str> = Vec::new();
+    for (i, t) in types.iter().enumerate() {
+        match names.and_then(|names| names.get(&(start + i as u32))) {
+            Some(id) => {
+                if !unnamed.is_empty() {
+                    parts.push(format!("({} {})", keyword, unnamed.join(" ")));
+                    unnamed.clear();
+                }
+                parts.push(format!("({} {} {})", keyword, id, value_type(t)));
+            }
+            None => unnamed.push(value_type(t)),
+        }
+    }
+    if !unnamed.is_empty() {
+        parts.push(format!("({} {})", keyword, unnamed.join(" ")));
+    }
+    parts
+}
+
+fn value_type(t: &ValueType) -> &'static str {
+    match t {
+        ValueType::Number(NumberType::I32) => "i32",
+        ValueType::Number(NumberType::I64) => "i64",
+        ValueType::Number(NumberType::F32) => "f32",
+        ValueType::Number(NumberType::F64) => "f64",
+        ValueType::Vector(VectorType::V128) => "v128",
+        ValueType::Reference(t) => reference_type(t),
+    }
+}
+
+fn value_type_byte(by: u8) -> &'static str {
+    match by {
+        0x7e => "i64",
+        0x7d => "f32",
+        0x7c => "f64",
+        0x7b => "v128",
+        0x70 => "funcref",
+        0x6f => "externref",
+        _ => "i32",
+    }
+}
+
+fn reference_type(t: &ReferenceType) -> &'static str {
+    match t {
+        ReferenceType::FunctionRef => "funcref",
+        ReferenceType::ExternRef => "externref",
+    }
+}
+
+fn limits(limits: &Limits) -> String {
+    match limits.max() {
+        Some(max) => format!("{} {}", limits.min(), max),
+        None => limits.min().to_string(),
+    }
+}
+
+fn table(table_type: &TableType) -> String {
+    format!(
+        "{} {}",
+        limits(table_type.limits()),
+        reference_type(table_type.element_type())
+    )
+}
+
+fn global(global_type: &GlobalType) -> String {
+    let t = value_type(global_type.value_type());
+    match global_type.mutability() {
+        Mutability::Const => t.to_string(),
+        Mutability::Var => format!("(mut {})", t),
+    }
+}